@@ -4,7 +4,12 @@ enum class ImageExtensions {
     JPEG,
     JXL,
     PPM,
-
+    WEBP,
+    GIF,
+    TIFF,
+    RAW,
+    EXR,
+    SVG,
 }
 
 fun ImageExtensions.extensions(): List<String> {
@@ -13,6 +18,12 @@ fun ImageExtensions.extensions(): List<String> {
         ImageExtensions.JPEG -> listOf("jpeg", "jpg","JPG","JPEG")
         ImageExtensions.JXL -> listOf("jxl")
         ImageExtensions.PPM -> listOf("ppm", "pam", "pbm", "pfm")
+        ImageExtensions.WEBP -> listOf("webp", "WEBP")
+        ImageExtensions.GIF -> listOf("gif", "GIF")
+        ImageExtensions.TIFF -> listOf("tiff", "tif", "TIFF", "TIF")
+        ImageExtensions.RAW -> listOf("dng", "cr2", "nef", "arw", "DNG", "CR2", "NEF", "ARW")
+        ImageExtensions.EXR -> listOf("exr", "EXR")
+        ImageExtensions.SVG -> listOf("svg", "svgz", "SVG")
     }
 }
 
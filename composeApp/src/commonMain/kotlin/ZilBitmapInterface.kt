@@ -89,6 +89,10 @@ interface ZilBitmapInterface {
         innerInterface().save(name, format)
     }
 
+    fun save(name: String, format: ZilImageFormat, options: ZilEncodeOptions) {
+        innerInterface().save(name, format, options)
+    }
+
     fun rotate(angle: Float, bitmap: ProtectedBitmapInterface) {
         innerInterface().rotate(angle)
         postProcessAlloc(bitmap)
@@ -18,6 +18,12 @@ internal class ZilImageJni : Cleaner.Cleanable, ZilImageInterface {
         this.imagePtr = imagePtr
     }
 
+    /**
+     * Opaque handle to the native image.
+     *
+     * This is not a pointer, the native side validates it on every call
+     * and throws if the image was already destroyed
+     * */
     private var imagePtr: Long = 0
 
 
@@ -27,6 +33,61 @@ internal class ZilImageJni : Cleaner.Cleanable, ZilImageInterface {
 
     private external fun loadImageNative(imagePtr: Long, fileName: String)
 
+    private external fun loadImageFromBytesNative(imagePtr: Long, bytes: ByteArray)
+
+    /**
+     * Decode from a direct buffer, reading bytes between its position and limit
+     */
+    private external fun loadImageFromBufferNative(imagePtr: Long, buffer: ByteBuffer)
+
+    private external fun loadImageWithOptionsNative(imagePtr: Long, fileName: String, options: ZilDecodeOptions)
+
+    private external fun loadImageFromBytesWithOptionsNative(imagePtr: Long, bytes: ByteArray, options: ZilDecodeOptions)
+
+    private external fun loadImageFromBufferWithOptionsNative(imagePtr: Long, buffer: ByteBuffer, options: ZilDecodeOptions)
+
+    private external fun loadSvgNative(imagePtr: Long, fileName: String, options: ZilSvgOptions, decodeOptions: ZilDecodeOptions?)
+
+    private external fun loadSvgFromBytesNative(imagePtr: Long, bytes: ByteArray, options: ZilSvgOptions, decodeOptions: ZilDecodeOptions?)
+
+    private external fun loadExrLayerNative(imagePtr: Long, fileName: String, layer: String, options: ZilDecodeOptions?)
+
+    private external fun loadPsdLayerNative(imagePtr: Long, fileName: String, index: Int, options: ZilDecodeOptions?)
+
+    private external fun getFrameCountNative(imagePtr: Long): Long
+
+    private external fun getFrameDelaysNative(imagePtr: Long): IntArray
+
+    private external fun setFrameDelaysNative(imagePtr: Long, delays: IntArray)
+
+    private external fun getLoopCountNative(imagePtr: Long): Long
+
+    private external fun setLoopCountNative(imagePtr: Long, loopCount: Long)
+
+    private external fun getActiveFrameNative(imagePtr: Long): Long
+
+    private external fun setActiveFrameNative(imagePtr: Long, frame: Long)
+
+    private external fun setFilterAllFramesNative(imagePtr: Long, allFrames: Boolean)
+
+    private external fun iccProfileNative(imagePtr: Long, source: Boolean): ZilIccProfile?
+
+    private external fun iccProfileBytesNative(imagePtr: Long, source: Boolean): ByteArray?
+
+    private external fun appliedOrientationNative(imagePtr: Long): Int
+
+    private external fun removeExifTagsNative(imagePtr: Long, tags: Array<String>)
+
+    private external fun removeExifGroupNative(imagePtr: Long, group: ZilExifGroup)
+
+    private external fun setExifDateNative(imagePtr: Long, date: ZilExifDate, value: String)
+
+    private external fun shiftExifDatesNative(imagePtr: Long, seconds: Long)
+
+    private external fun setExifArtistNative(imagePtr: Long, artist: String?)
+
+    private external fun setExifCopyrightNative(imagePtr: Long, copyright: String?)
+
     private external fun cloneNative(imagePtr: Long): Long
 
     private external fun getImageWidthNative(imagePtr: Long): Long
@@ -41,6 +102,22 @@ internal class ZilImageJni : Cleaner.Cleanable, ZilImageInterface {
 
     private external fun saveToNative(imagePtr: Long, filename: String, imageFormat: Long)
 
+    private external fun saveToWithOptionsNative(
+        imagePtr: Long,
+        filename: String,
+        imageFormat: Long,
+        options: ZilEncodeOptions
+    )
+
+    private external fun encodeNative(imagePtr: Long, imageFormat: Long, options: ZilEncodeOptions?): ByteArray
+
+    private external fun encodeToBufferNative(
+        imagePtr: Long,
+        imageFormat: Long,
+        options: ZilEncodeOptions?,
+        buffer: ByteBuffer
+    ): Long
+
     private external fun contrastNative(imagePtr: Long, value: Float)
 
     private external fun cropNative(imagePtr: Long, newWidth: Long, newHeight: Long, x: Long, y: Long)
@@ -78,6 +155,26 @@ internal class ZilImageJni : Cleaner.Cleanable, ZilImageInterface {
 
     private external fun exifMetadataNative(imagePtr: Long, metadata: Map<String, String>)
 
+    private external fun exifFieldsNative(imagePtr: Long): Array<ZilExifField>
+
+    private external fun exifFieldBytesNative(imagePtr: Long, ifd: String, tag: Int): ByteArray?
+
+    private external fun exifFieldStringNative(imagePtr: Long, ifd: String, tag: Int): String?
+
+    private external fun captureInfoNative(imagePtr: Long): ZilCaptureInfo?
+
+    private external fun xmpPropertiesNative(imagePtr: Long): Array<ZilXmpProperty>
+
+    private external fun setXmpPropertyNative(imagePtr: Long, key: String, kind: ZilXmpKind, values: Array<String>)
+
+    private external fun removeXmpPropertyNative(imagePtr: Long, key: String)
+
+    private external fun writeXmpSidecarNative(imagePtr: Long, filename: String)
+
+    private external fun iptcDatasetsNative(imagePtr: Long): Array<ZilIptcDataset>
+
+    private external fun setIptcDatasetNative(imagePtr: Long, dataset: Int, values: Array<String>)
+
     private external fun boxBlurNative(imagePtr: Long, radius: Long)
 
     private external fun gaussianBlurNative(imagePtr: Long, radius: Long)
@@ -166,6 +263,34 @@ internal class ZilImageJni : Cleaner.Cleanable, ZilImageInterface {
         saveToNative(imagePtr, filename, format.toNum().toLong())
     }
 
+    override fun save(filename: String, format: ZilImageFormat, options: ZilEncodeOptions) {
+        saveToWithOptionsNative(imagePtr, filename, format.toNum().toLong(), options)
+    }
+
+    override fun encode(format: ZilImageFormat, options: ZilEncodeOptions?): ByteArray {
+        return encodeNative(imagePtr, format.toNum().toLong(), options)
+    }
+
+    /**
+     * Encode the image into a direct buffer, starting at its position
+     *
+     * On success the buffer's position is advanced past the encoded bytes.
+     * If the encoded image does not fit between the position and limit nothing is
+     * written and a [ZilInvalidArgumentException] is thrown whose message states the
+     * required and available sizes
+     *
+     * @return The number of bytes written
+     * */
+    @Throws(Exception::class)
+    fun encodeToBuffer(format: ZilImageFormat, buffer: ByteBuffer, options: ZilEncodeOptions? = null): Long {
+        if (!buffer.isDirect) {
+            throw Exception("Native buffer should be direct")
+        }
+        val written = encodeToBufferNative(imagePtr, format.toNum().toLong(), options, buffer)
+        buffer.position(buffer.position() + written.toInt())
+        return written
+    }
+
     override fun convertColorspace(to: ZilColorspace) {
         convertColorSpaceNative(imagePtr, to.toNum().toLong())
     }
@@ -178,6 +303,273 @@ internal class ZilImageJni : Cleaner.Cleanable, ZilImageInterface {
         loadImageNative(imagePtr, file)
     }
 
+    /**
+     * Load a file, refusing images that exceed the limits in [options]
+     *
+     * @throws ZilLimitExceededException if the image is too large
+     * */
+    fun loadFile(file: String, options: ZilDecodeOptions) {
+        loadImageWithOptionsNative(imagePtr, file, options)
+    }
+
+    /**
+     * Load an image from encoded bytes, e.g. clipboard data or a content URI stream
+     *
+     * The format is detected from the bytes
+     * */
+    fun loadBytes(bytes: ByteArray) {
+        loadImageFromBytesNative(imagePtr, bytes)
+    }
+
+    /**
+     * Load an image from encoded bytes, refusing images that exceed the limits in [options]
+     * */
+    fun loadBytes(bytes: ByteArray, options: ZilDecodeOptions) {
+        loadImageFromBytesWithOptionsNative(imagePtr, bytes, options)
+    }
+
+    /**
+     * Load an image from a direct buffer containing encoded bytes
+     *
+     * Only the bytes between the buffer's position and limit are decoded
+     * */
+    @Throws(Exception::class)
+    fun loadBuffer(buffer: ByteBuffer) {
+        if (!buffer.isDirect) {
+            throw Exception("Native buffer should be direct")
+        }
+        loadImageFromBufferNative(imagePtr, buffer)
+    }
+
+    /**
+     * Load an image from a direct buffer, refusing images that exceed the limits in [options]
+     * */
+    @Throws(Exception::class)
+    fun loadBuffer(buffer: ByteBuffer, options: ZilDecodeOptions) {
+        if (!buffer.isDirect) {
+            throw Exception("Native buffer should be direct")
+        }
+        loadImageFromBufferWithOptionsNative(imagePtr, buffer, options)
+    }
+
+    /**
+     * Rasterize an SVG (or SVGZ) file to RGBA at the size given by [options]
+     *
+     * [loadFile] also opens SVGs, at their own size
+     *
+     * @param decodeOptions Limits the file size and the rasterized size is checked against,
+     * files from untrusted sources should set them since [options] can ask for any size
+     * */
+    fun loadSvg(file: String, options: ZilSvgOptions, decodeOptions: ZilDecodeOptions? = null) {
+        loadSvgNative(imagePtr, file, options, decodeOptions)
+    }
+
+    /**
+     * Rasterize an SVG held in memory to RGBA at the size given by [options]
+     *
+     * @param decodeOptions Limits the data size and the rasterized size is checked against
+     * */
+    fun loadSvgBytes(bytes: ByteArray, options: ZilSvgOptions, decodeOptions: ZilDecodeOptions? = null) {
+        loadSvgFromBytesNative(imagePtr, bytes, options, decodeOptions)
+    }
+
+    /**
+     * Load a single layer of an OpenEXR file
+     *
+     * @param layer One of the names returned by [exrLayers], an empty string is the default layer
+     * @throws ZilInvalidArgumentException if the file has no layer called [layer]
+     * */
+    fun loadExrLayer(file: String, layer: String, options: ZilDecodeOptions? = null) {
+        loadExrLayerNative(imagePtr, file, layer, options)
+    }
+
+    /**
+     * Load a single layer of a Photoshop document, covering the layer's bounds
+     *
+     * The pixels are loaded as stored, the layer's opacity, blend mode and masks are not applied.
+     * [loadFile] loads the flattened image instead
+     *
+     * @param index Position of the layer in the array returned by [psdLayers]
+     * @throws ZilInvalidArgumentException if there's no such layer or it's empty
+     * */
+    fun loadPsdLayer(file: String, index: Int, options: ZilDecodeOptions? = null) {
+        loadPsdLayerNative(imagePtr, file, index, options)
+    }
+
+    /**
+     * Number of frames in the image, animation frames or pages, one for still images
+     * */
+    fun frameCount(): Long = getFrameCountNative(imagePtr)
+
+    /**
+     * How long each frame is shown in milliseconds, zero where the file didn't say
+     * */
+    fun frameDelays(): IntArray = getFrameDelaysNative(imagePtr)
+
+    /**
+     * Set how long each frame is shown in milliseconds
+     *
+     * Saving to GIF, WebP or PNG writes these along with every frame
+     *
+     * @throws ZilInvalidArgumentException if [delays] doesn't have one entry per frame or one is negative
+     * */
+    fun setFrameDelays(delays: IntArray) {
+        setFrameDelaysNative(imagePtr, delays)
+    }
+
+    /**
+     * How many times the animation plays, zero loops forever
+     * */
+    fun loopCount(): Long = getLoopCountNative(imagePtr)
+
+    /**
+     * Set how many times the animation plays, zero loops forever
+     * */
+    fun setLoopCount(loopCount: Long) {
+        setLoopCountNative(imagePtr, loopCount)
+    }
+
+    /**
+     * The frame [writeToBuffer] reads and single frame filters modify
+     * */
+    fun activeFrame(): Long = getActiveFrameNative(imagePtr)
+
+    /**
+     * Select the frame [writeToBuffer] reads, and the one saved to formats that can't hold animations
+     *
+     * @throws ZilInvalidArgumentException if the image has no such frame
+     * */
+    fun setActiveFrame(frame: Long) {
+        setActiveFrameNative(imagePtr, frame)
+    }
+
+    /**
+     * Choose whether filters run on every frame (the default) or only on the [activeFrame]
+     *
+     * Filters changing the dimensions, colorspace or depth always run on every frame
+     * and throw [ZilInvalidArgumentException] when limited to one
+     * */
+    fun setFilterAllFrames(allFrames: Boolean) {
+        setFilterAllFramesNative(imagePtr, allFrames)
+    }
+
+    /**
+     * The ICC profile of the image
+     *
+     * Images are converted to the `workingSpace` of [ZilDecodeOptions] on load, sRGB by default,
+     * this is the profile of that working space and the one JPEG, PNG and WebP files embed on save.
+     * Null for sRGB images
+     *
+     * @param source Return the profile embedded in the file the image was loaded from instead,
+     * null if the file had none
+     * */
+    fun iccProfile(source: Boolean = false): ZilIccProfile? = iccProfileNative(imagePtr, source)
+
+    /**
+     * The raw bytes of the profile returned by [iccProfile]
+     * */
+    fun iccProfileBytes(source: Boolean = false): ByteArray? = iccProfileBytesNative(imagePtr, source)
+
+    /**
+     * The EXIF orientation, 1-8, the image was turned upright from when it was loaded
+     *
+     * Only loads with `applyOrientation` set in [ZilDecodeOptions] apply it,
+     * 1 if the image wasn't rotated or mirrored
+     * */
+    fun appliedOrientation(): Int = appliedOrientationNative(imagePtr)
+
+    /**
+     * Remove EXIF tags, e.g. `BodySerialNumber`
+     *
+     * Tags are named as in the keys of [exifMetadata], changes are written
+     * out when the image is saved with `keepMetadata` set in [ZilEncodeOptions]
+     * */
+    fun removeExifTags(tags: List<String>) {
+        removeExifTagsNative(imagePtr, tags.toTypedArray())
+    }
+
+    /**
+     * Remove a group of related EXIF tags, e.g. everything revealing where a picture was taken
+     * */
+    fun removeExifGroup(group: ZilExifGroup) {
+        removeExifGroupNative(imagePtr, group)
+    }
+
+    /**
+     * Set an EXIF date, adding EXIF to the image if it has none
+     *
+     * @param value The date in the EXIF format, `YYYY:MM:DD HH:MM:SS`
+     * @throws ZilInvalidArgumentException if [value] isn't a valid date
+     * */
+    fun setExifDate(date: ZilExifDate, value: String) {
+        setExifDateNative(imagePtr, date, value)
+    }
+
+    /**
+     * Move every EXIF date by [seconds], e.g. to fix a camera clock set to the wrong time zone
+     *
+     * @throws ZilInvalidArgumentException if a date would end up outside years 0-9999
+     * */
+    fun shiftExifDates(seconds: Long) {
+        shiftExifDatesNative(imagePtr, seconds)
+    }
+
+    /**
+     * Set the EXIF author of the image, null removes it
+     * */
+    fun setExifArtist(artist: String?) {
+        setExifArtistNative(imagePtr, artist)
+    }
+
+    /**
+     * Set the EXIF copyright notice of the image, null removes it
+     * */
+    fun setExifCopyright(copyright: String?) {
+        setExifCopyrightNative(imagePtr, copyright)
+    }
+
+    /**
+     * Set an XMP property, replacing its previous values and adding XMP to the image if it has none
+     *
+     * @param key Prefix and name of the property, e.g. `dc:subject`. The prefix must be
+     * one of the common ones, such as `dc`, `xmp` or `photoshop`, or one the image's XMP already uses
+     * @param kind [ZilXmpKind.Text] for a single value or one of the list kinds
+     * @throws ZilInvalidArgumentException if the prefix is unknown, [kind] is [ZilXmpKind.Xml]
+     * or a text property isn't given exactly one value
+     * */
+    fun setXmpProperty(key: String, kind: ZilXmpKind, values: List<String>) {
+        setXmpPropertyNative(imagePtr, key, kind, values.toTypedArray())
+    }
+
+    /**
+     * Remove an XMP property, e.g. `xmp:Rating`
+     * */
+    fun removeXmpProperty(key: String) {
+        removeXmpPropertyNative(imagePtr, key)
+    }
+
+    /**
+     * Write the image's XMP to the sidecar of [imageFile], `<file>.<ext>.xmp` if it exists,
+     * otherwise the same path with an `.xmp` extension
+     *
+     * Saving does this for formats that can't embed XMP, this is for files
+     * whose pixels didn't change, e.g. after editing keywords of a raw file
+     * */
+    fun writeXmpSidecar(imageFile: String) {
+        writeXmpSidecarNative(imagePtr, imageFile)
+    }
+
+    /**
+     * Replace every value of an IPTC dataset in the application record, an empty list removes it
+     *
+     * IPTC records are only written to JPEG files, values are stored as UTF-8
+     *
+     * @param dataset Dataset number, e.g. 25 for `Keywords` or 120 for `Caption-Abstract`
+     * */
+    fun setIptcDataset(dataset: Int, values: List<String>) {
+        setIptcDatasetNative(imagePtr, dataset, values.toTypedArray())
+    }
+
     override fun clone(): ZilImageJni {
         val newPtr = cloneNative(imagePtr)
         return ZilImageJni(newPtr)
@@ -229,6 +621,18 @@ internal class ZilImageJni : Cleaner.Cleanable, ZilImageInterface {
         return map
     }
 
+    override fun exifFields(): List<ZilExifField> = exifFieldsNative(imagePtr).toList()
+
+    override fun exifFieldBytes(ifd: String, tag: Int): ByteArray? = exifFieldBytesNative(imagePtr, ifd, tag)
+
+    override fun exifFieldString(ifd: String, tag: Int): String? = exifFieldStringNative(imagePtr, ifd, tag)
+
+    override fun captureInfo(): ZilCaptureInfo? = captureInfoNative(imagePtr)
+
+    override fun xmpProperties(): List<ZilXmpProperty> = xmpPropertiesNative(imagePtr).toList()
+
+    override fun iptcDatasets(): List<ZilIptcDataset> = iptcDatasetsNative(imagePtr).toList()
+
     override fun gaussianBlur(radius: Long) {
         gaussianBlurNative(imagePtr, radius)
     }
@@ -290,5 +694,169 @@ internal class ZilImageJni : Cleaner.Cleanable, ZilImageInterface {
         init {
             System.loadLibrary("zune_jni_bindings")
         }
+
+        @JvmStatic
+        private external fun probeNative(fileName: String): ZilImageInfo
+
+        /**
+         * Read an image's dimensions, format, depth and colorspace
+         * from its headers without decoding it
+         * */
+        fun probe(fileName: String): ZilImageInfo = probeNative(fileName)
+
+        @JvmStatic
+        private external fun rawMetadataNative(fileName: String): ZilRawMetadata
+
+        /**
+         * Read the camera and sensor properties of a RAW file
+         * (DNG, CR2, NEF, ARW, ...) without developing it
+         * */
+        fun rawMetadata(fileName: String): ZilRawMetadata = rawMetadataNative(fileName)
+
+        @JvmStatic
+        private external fun exrLayersNative(fileName: String): Array<String>
+
+        /**
+         * List the layers of an OpenEXR file, e.g. `diffuse` or `specular`
+         *
+         * The unnamed default layer is returned as an empty string
+         * */
+        fun exrLayers(fileName: String): Array<String> = exrLayersNative(fileName)
+
+        @JvmStatic
+        private external fun psdLayersNative(fileName: String): Array<ZilPsdLayer>
+
+        /**
+         * List the layers of a Photoshop document, top-most first as in the layers panel
+         *
+         * Groups are not listed, each layer carries the path of the groups it's in
+         * */
+        fun psdLayers(fileName: String): Array<ZilPsdLayer> = psdLayersNative(fileName)
+
+        @JvmStatic
+        private external fun sidecarTagsNative(fileName: String): ZilSidecarTags
+
+        @JvmStatic
+        private external fun setSidecarRatingNative(fileName: String, rating: Int)
+
+        @JvmStatic
+        private external fun setSidecarLabelNative(fileName: String, label: String?)
+
+        @JvmStatic
+        private external fun setSidecarKeywordsNative(fileName: String, keywords: Array<String>)
+
+        @JvmStatic
+        private external fun directoryRatingsNative(directory: String, ratings: Map<String, Int>)
+
+        /**
+         * Read the rating, color label and keywords of an image from its `.xmp` sidecar,
+         * `<file>.<ext>.xmp` as darktable writes them if it exists, otherwise the image's
+         * path with an `.xmp` extension
+         *
+         * Images without a sidecar are unrated and have no label or keywords
+         * */
+        fun sidecarTags(fileName: String): ZilSidecarTags = sidecarTagsNative(fileName)
+
+        /**
+         * Rate an image in its `.xmp` sidecar, creating the sidecar if needed
+         *
+         * @param rating 1-5 stars, -1 to reject the shot or 0 to remove the rating
+         * @throws ZilInvalidArgumentException if [rating] is outside -1 to 5
+         * */
+        fun setSidecarRating(fileName: String, rating: Int) = setSidecarRatingNative(fileName, rating)
+
+        /**
+         * Set the color label of an image in its `.xmp` sidecar, e.g. `Red`, null removes it
+         * */
+        fun setSidecarLabel(fileName: String, label: String?) = setSidecarLabelNative(fileName, label)
+
+        /**
+         * Replace the keywords of an image in its `.xmp` sidecar, an empty list removes them
+         * */
+        fun setSidecarKeywords(fileName: String, keywords: List<String>) =
+            setSidecarKeywordsNative(fileName, keywords.toTypedArray())
+
+        /**
+         * Ratings of every rated image in [directory], read from their `.xmp` sidecars
+         *
+         * Keys are file names, images without a sidecar or rating are left out
+         * */
+        fun directoryRatings(directory: String): Map<String, Int> {
+            val map = HashMap<String, Int>()
+            directoryRatingsNative(directory, map)
+            return map
+        }
+
+        @JvmStatic
+        private external fun thumbnailNative(fileName: String, maxEdge: Int): ZilThumbnail
+
+        /**
+         * Make a thumbnail of an image file whose longest side is at most [maxEdge] pixels,
+         * turned upright according to its EXIF orientation
+         *
+         * The JPEG thumbnail cameras embed in EXIF is used when it's at least half of [maxEdge],
+         * otherwise the file is decoded and shrunk, which is much slower. Images are never enlarged
+         *
+         * @throws ZilInvalidArgumentException if [maxEdge] isn't positive
+         * */
+        fun thumbnail(fileName: String, maxEdge: Int): ZilThumbnail = thumbnailNative(fileName, maxEdge)
+
+        @JvmStatic
+        private external fun encodeAnimationNative(
+            imagePtrs: LongArray,
+            delays: IntArray,
+            loopCount: Long,
+            imageFormat: Long,
+            options: ZilEncodeOptions?
+        ): ByteArray
+
+        @JvmStatic
+        private external fun saveAnimationNative(
+            fileName: String,
+            imagePtrs: LongArray,
+            delays: IntArray,
+            loopCount: Long,
+            imageFormat: Long,
+            options: ZilEncodeOptions?
+        )
+
+        /**
+         * Encode the active frame of each image as one frame of an animated GIF, PNG or WebP
+         *
+         * Frames are converted to 8 bit RGBA and must all have the same dimensions.
+         * Each frame only stores the region that changed since the previous one,
+         * and identical consecutive frames are merged
+         *
+         * @param delays How long each frame is shown in milliseconds, one per image
+         * @param loopCount How many times the animation plays, zero loops forever
+         * @param options `gifSpeed` sets how hard GIF palettes are quantized
+         * @throws ZilInvalidArgumentException if the frames differ in size or [delays] doesn't match [images]
+         * */
+        fun encodeAnimation(
+            images: List<ZilImageJni>,
+            delays: IntArray,
+            loopCount: Long = 0,
+            format: ZilImageFormat = ZilImageFormat.GIF,
+            options: ZilEncodeOptions? = null
+        ): ByteArray {
+            val ptrs = images.map { it.imagePtr }.toLongArray()
+            return encodeAnimationNative(ptrs, delays, loopCount, format.toNum().toLong(), options)
+        }
+
+        /**
+         * Write the active frame of each image as one frame of an animated GIF, PNG or WebP,
+         * see [encodeAnimation]
+         * */
+        fun saveAnimation(
+            fileName: String,
+            images: List<ZilImageJni>,
+            delays: IntArray,
+            loopCount: Long = 0,
+            format: ZilImageFormat = ZilImageFormat.GIF,
+            options: ZilEncodeOptions? = null
+        ) {
+            val ptrs = images.map { it.imagePtr }.toLongArray()
+            saveAnimationNative(fileName, ptrs, delays, loopCount, format.toNum().toLong(), options)
+        }
     }
 }
\ No newline at end of file
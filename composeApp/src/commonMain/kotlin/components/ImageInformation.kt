@@ -1,6 +1,7 @@
 package components
 
 import AppContext
+import ZilCaptureInfo
 import androidx.compose.foundation.layout.*
 import androidx.compose.material.Divider
 import androidx.compose.material.Text
@@ -75,12 +76,65 @@ fun ImageInformationComponent(appCtx: AppContext) {
                 );
             }
 
-
+            val capture = innerImage.captureInfo()
+            if (capture != null) {
+                for ((name, value) in captureRows(capture)) {
+                    Divider()
+                    InformationRow(name, value)
+                }
+            }
         }
 
     }
 }
 
+@Composable
+private fun InformationRow(name: String, value: String) {
+    Row(
+        modifier = Modifier.fillMaxWidth().padding(10.dp),
+        horizontalArrangement = Arrangement.SpaceBetween
+    ) {
+        Text(
+            name,
+            modifier = Modifier.padding(end = 10.dp),
+            style = TextStyle(fontSize = TextUnit(14F, TextUnitType.Sp))
+        )
+        Text(value, style = TextStyle(fontSize = TextUnit(14F, TextUnitType.Sp)))
+    }
+}
+
+/**
+ * Name and formatted value of each capture detail the image records
+ * */
+private fun captureRows(capture: ZilCaptureInfo): List<Pair<String, String>> {
+    val decimal = DecimalFormat("#.#")
+    val rows = mutableListOf<Pair<String, String>>()
+
+    val camera = listOfNotNull(capture.make, capture.model).joinToString(" ")
+    if (camera.isNotEmpty()) rows.add("Camera" to camera)
+    capture.lensModel?.let { rows.add("Lens" to it) }
+    capture.exposureTime?.let {
+        // short exposures are written as fractions of a second
+        val time = if (it > 0 && it < 1) "1/" + Math.round(1 / it) else decimal.format(it)
+        rows.add("Exposure" to "$time s")
+    }
+    capture.fNumber?.let { rows.add("Aperture" to "f/" + decimal.format(it)) }
+    capture.iso?.let { rows.add("ISO" to it.toString()) }
+    capture.focalLength?.let { rows.add("Focal Length" to decimal.format(it) + " mm") }
+    capture.dateTime?.let { dateTime ->
+        val offset = capture.offsetMinutes?.let {
+            val sign = if (it < 0) "-" else "+"
+            " UTC$sign%02d:%02d".format(Math.abs(it) / 60, Math.abs(it) % 60)
+        } ?: ""
+        rows.add("Taken" to dateTime.replace('T', ' ') + offset)
+    }
+    if (capture.latitude != null && capture.longitude != null) {
+        val altitude = capture.altitude?.let { ", " + decimal.format(it) + " m" } ?: ""
+        rows.add("Location" to "%.5f, %.5f".format(capture.latitude, capture.longitude) + altitude)
+    }
+    return rows
+}
+
 
 fun formatSize(bytes: Long): String {
     var bytes = bytes.toDouble();
@@ -0,0 +1,48 @@
+/**
+ * Color space RGB pixels are converted to when an image is loaded
+ *
+ * The native side reads the ordinal, so the order must not change
+ * without updating `WorkingSpace` in `rust/src/icc.rs`
+ * */
+enum class ZilWorkingSpace {
+    /**
+     * Keep the pixels as decoded, along with the ICC profile embedded in the file
+     */
+    Original,
+    SRGB,
+    DisplayP3,
+    AdobeRGB,
+    ProPhotoRGB
+}
+
+/**
+ * Options applied when decoding, the size limits guard against images from untrusted sources
+ *
+ * With any size limit set, images whose headers can't be parsed are rejected
+ * rather than decoded unchecked.
+ *
+ * Read field by field by the native side (see `rust/src/decoder.rs`),
+ * so field names must not change without updating it.
+ *
+ * @param maxWidth Maximum image width, zero uses the decoder default of 16384
+ * @param maxHeight Maximum image height, zero uses the decoder default of 16384
+ * @param maxPixels Maximum of width*height, zero means no limit
+ * @param memoryBudget Maximum bytes the encoded file and decoded pixels may each occupy,
+ * zero means no limit
+ * @param strict If true, recoverable errors such as bad checksums or truncated data fail the decode
+ * @param rawFloat If true, camera RAW files are developed to 32 bit float instead of 16 bit
+ * @param workingSpace Color space RGB pixels are converted to using the file's ICC profile,
+ * files without one are assumed to be sRGB
+ * @param applyOrientation If true, images are rotated and mirrored as their EXIF orientation asks
+ * and the tag is reset to 1, see [ZilImageJni.appliedOrientation]
+ * */
+data class ZilDecodeOptions(
+    val maxWidth: Long = 0,
+    val maxHeight: Long = 0,
+    val maxPixels: Long = 0,
+    val memoryBudget: Long = 0,
+    val strict: Boolean = false,
+    val rawFloat: Boolean = false,
+    val workingSpace: ZilWorkingSpace = ZilWorkingSpace.SRGB,
+    val applyOrientation: Boolean = false
+)
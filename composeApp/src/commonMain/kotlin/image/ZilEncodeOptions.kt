@@ -0,0 +1,64 @@
+/**
+ * Row filter a PNG encoder applies before compressing
+ *
+ * The native side reads the ordinal, so the order must not change
+ * without updating `PngFilter` in `rust/src/codecs/png.rs`
+ * */
+enum class ZilPngFilter {
+    /**
+     * Pick the best filter for each row
+     */
+    Adaptive,
+    NoFilter,
+    Sub,
+    Up,
+    Average,
+    Paeth
+}
+
+/**
+ * Options used when encoding an image
+ *
+ * Each format only reads the options prefixed with its name, `quality`
+ * applies to JPEG and lossy WebP, `progressive` to JPEG.
+ *
+ * Read field by field by the native side (see `rust/src/encoder.rs`),
+ * so field names must not change without updating it.
+ *
+ * @param quality JPEG and WebP quality, 1-100
+ * @param progressive Write a progressive JPEG
+ * @param pngCompression zlib compression level, 0 stores the image uncompressed, 9 is the smallest
+ * @param pngFilter The row filter used before compression
+ * @param jxlEffort How hard the JPEG-XL encoder looks for a smaller file, 0-127
+ * @param jxlLossless Must be true, the JPEG-XL encoder only supports lossless output
+ * @param qoiLinear Mark QOI pixels as linear instead of sRGB
+ * @param qoiChannels 3 or 4 channels, 0 keeps alpha only if the image has it
+ * @param bmpBitsPerPixel 24 or 32 bits, 0 writes 32 bits only if the image has alpha
+ * @param webpLossless Write a lossless WebP, `quality` then trades encoding speed for size
+ * @param webpMethod How hard the WebP encoder looks for a smaller file, 0-6
+ * @param gifSpeed Speed of the palette quantizer, 1 gives the best palette, 30 is fastest
+ * @param icoSizes Edge lengths of the square images stored in an icon, 1-256
+ * @param exrHalf Store EXR channels as 16 bit half floats instead of 32 bit floats
+ * @param keepMetadata Copy the EXIF, XMP and IPTC of the source file into JPEG, PNG, WebP and JPEG-XL
+ * output, with its dimensions and software tags updated to match the saved image. Saving to other
+ * formats merges the XMP into a `.xmp` sidecar instead, keeping the properties it already holds,
+ * as it may belong to another file of the same name. Off by default, the metadata may hold GPS
+ * locations and camera serial numbers
+ * */
+data class ZilEncodeOptions(
+    val quality: Int = 80,
+    val progressive: Boolean = false,
+    val pngCompression: Int = 6,
+    val pngFilter: ZilPngFilter = ZilPngFilter.Adaptive,
+    val jxlEffort: Int = 4,
+    val jxlLossless: Boolean = true,
+    val qoiLinear: Boolean = false,
+    val qoiChannels: Int = 0,
+    val bmpBitsPerPixel: Int = 0,
+    val webpLossless: Boolean = false,
+    val webpMethod: Int = 4,
+    val gifSpeed: Int = 10,
+    val icoSizes: IntArray = intArrayOf(16, 32, 48, 256),
+    val exrHalf: Boolean = false,
+    val keepMetadata: Boolean = false
+)
@@ -0,0 +1,106 @@
+/**
+ * EXIF tags removed together by [ZilImageJni.removeExifGroup]
+ *
+ * The native side reads the ordinal, so the order must not change
+ * without updating `ExifGroup` in `rust/src/metadata.rs`
+ * */
+enum class ZilExifGroup {
+    /**
+     * Location, along with the time and altitude it was recorded at
+     */
+    GPS,
+
+    /**
+     * Camera specific data, often holding serial numbers
+     */
+    MakerNote,
+
+    /**
+     * The embedded preview image
+     */
+    Thumbnail
+}
+
+/**
+ * EXIF date fields
+ *
+ * The native side reads the ordinal, so the order must not change
+ * without updating `ExifDate` in `rust/src/metadata.rs`
+ * */
+enum class ZilExifDate {
+    /**
+     * When the file was last changed, the `DateTime` tag
+     */
+    Modified,
+
+    /**
+     * When the picture was taken, the `DateTimeOriginal` tag
+     */
+    Original,
+
+    /**
+     * When the picture was stored digitally, the `DateTimeDigitized` tag
+     */
+    Digitized
+}
+
+/**
+ * A single EXIF field, as stored in the file
+ *
+ * Constructed by the native side, see `rust/src/metadata.rs`
+ *
+ * @param ifd Directory the field is stored in, `IFD0` for the image, `IFD1` for its thumbnail,
+ * `Exif`, `GPS` or `Interop` for the directories the image points to
+ * @param tag Numeric tag id, e.g. `0x010F` for `Make`
+ * @param name Name of the tag, e.g. `Make`, or `Tag(Tiff, 12345)` for tags the native side doesn't know
+ * @param type TIFF type of the value, e.g. 2 for ASCII or 5 for unsigned rational
+ * @param count Number of values of [type] the field holds
+ * @param raw The value's bytes as stored in the file, ASCII values keep their NUL terminators.
+ * For types EXIF doesn't define, the four bytes of the entry's value or offset field.
+ * Null if longer than 256 bytes, see [ZilImageInterface.exifFieldBytes]
+ * @param littleEndian Byte order of the numbers in [raw]
+ * @param formatted The value formatted for display, shortened to 256 characters,
+ * see [ZilImageInterface.exifFieldString]
+ * @param complete False if [raw] or [formatted] were left out or shortened
+ * */
+class ZilExifField(
+    val ifd: String,
+    val tag: Int,
+    val name: String,
+    val type: Int,
+    val count: Long,
+    val raw: ByteArray?,
+    val littleEndian: Boolean,
+    val formatted: String,
+    val complete: Boolean
+)
+
+/**
+ * Where, when and how a picture was taken, read from its EXIF
+ *
+ * Constructed by the native side, see `rust/src/metadata.rs`.
+ * Every property is null if the EXIF doesn't record it
+ *
+ * @param latitude Decimal degrees, negative south of the equator
+ * @param longitude Decimal degrees, negative west of Greenwich
+ * @param altitude Meters, negative below sea level
+ * @param dateTime Local time the picture was taken in the ISO 8601 format without an offset,
+ * e.g. `2024-05-01T12:30:05.25`
+ * @param offsetMinutes Offset of [dateTime] from UTC
+ * @param exposureTime Exposure time in seconds
+ * @param focalLength Focal length in millimeters
+ * */
+class ZilCaptureInfo(
+    val make: String?,
+    val model: String?,
+    val lensModel: String?,
+    val latitude: Double?,
+    val longitude: Double?,
+    val altitude: Double?,
+    val dateTime: String?,
+    val offsetMinutes: Int?,
+    val exposureTime: Double?,
+    val fNumber: Double?,
+    val iso: Int?,
+    val focalLength: Double?
+)
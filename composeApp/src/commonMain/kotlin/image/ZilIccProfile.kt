@@ -0,0 +1,20 @@
+/**
+ * Properties of an ICC profile
+ *
+ * Constructed by the native side, see `rust/src/icc.rs`
+ *
+ * @param description Name of the profile, e.g. `Display P3`
+ * @param copyright Copyright notice of the profile, empty if it has none
+ * @param colorSpace Color space of the pixels the profile describes, e.g. `RGB` or `GRAY`
+ * @param profileClass Device class signature, e.g. `mntr` for displays
+ * @param version Profile format version, e.g. `4.3.0`
+ * @param size Size of the profile in bytes
+ * */
+class ZilIccProfile(
+    val description: String,
+    val copyright: String,
+    val colorSpace: String,
+    val profileClass: String,
+    val version: String,
+    val size: Long
+)
@@ -0,0 +1,79 @@
+/**
+ * Base class of all errors raised by the native image library.
+ *
+ * The native side (see `rust/src/errors.rs`) constructs these directly,
+ * so constructor signatures must not change without updating it.
+ *
+ * @param reason Human readable description of the failure
+ * @param operation The native call that failed, e.g. `loadImageNative`
+ * */
+open class ZilImageException(val reason: String, val operation: String) : Exception("$operation: $reason")
+
+/**
+ * The image could not be decoded, usually a corrupt or truncated file
+ *
+ * @param format The detected image format, or null if it could not be determined
+ * @param path The file being decoded, or null for in-memory sources
+ * */
+class ZilDecodeException(reason: String, operation: String, val format: String?, val path: String?) :
+    ZilImageException(reason, operation)
+
+/**
+ * The image could not be encoded into the requested format
+ *
+ * @param format The format we were encoding to
+ * @param path The destination file, or null for in-memory destinations
+ * */
+class ZilEncodeException(reason: String, operation: String, val format: String?, val path: String?) :
+    ZilImageException(reason, operation)
+
+/**
+ * The format, colorspace or depth is not supported by the requested operation
+ *
+ * @param format The format in question if the failure was format related
+ * */
+class ZilUnsupportedException(reason: String, operation: String, val format: String?) :
+    ZilImageException(reason, operation)
+
+/**
+ * Reading or writing a file failed, retrying may succeed
+ *
+ * @param path The file that could not be accessed
+ * */
+class ZilIoException(reason: String, operation: String, val path: String?) :
+    ZilImageException(reason, operation)
+
+/**
+ * Image dimensions or buffer sizes did not match what the operation expected
+ * */
+class ZilInvalidDimensionsException(reason: String, operation: String, val expected: Long, val found: Long) :
+    ZilImageException(reason, operation)
+
+/**
+ * An argument passed to the native library was out of range or unknown
+ * */
+class ZilInvalidArgumentException(reason: String, operation: String) : ZilImageException(reason, operation)
+
+/**
+ * The image handle is null, unknown or refers to an image that was already destroyed
+ * */
+class ZilInvalidHandleException(reason: String, operation: String, val handle: Long) :
+    ZilImageException(reason, operation)
+
+/**
+ * The native library hit an internal bug, the image it was working on may be in
+ * an inconsistent state
+ * */
+class ZilNativePanicException(reason: String, operation: String) : ZilImageException(reason, operation)
+
+/**
+ * The image is larger than one of the limits in [ZilDecodeOptions].
+ *
+ * Raised from the image headers, before any pixel memory is allocated
+ *
+ * @param limit Name of the limit that was exceeded, e.g. `maxPixels`
+ * @param value The value the image requires
+ * @param max The configured maximum
+ * */
+class ZilLimitExceededException(reason: String, operation: String, val limit: String, val value: Long, val max: Long) :
+    ZilImageException(reason, operation)
@@ -44,10 +44,45 @@ enum class ZilImageFormat {
      */
     HDR,
 
+    /**
+     * OpenEXR, half or full float channels
+     */
+    EXR,
+
     /**
      * Windows Bitmap Files
      */
-    BMP
+    BMP,
+
+    /**
+     * Google's WebP, lossy or lossless
+     */
+    WEBP,
+
+    /**
+     * Graphics Interchange Format
+     */
+    GIF,
+
+    /**
+     * Windows icon, holding the image at several sizes
+     */
+    ICO,
+
+    /**
+     * Tagged Image File Format, decoding only
+     */
+    TIFF,
+
+    /**
+     * Camera RAW files, e.g. DNG, CR2, NEF and ARW, decoding only
+     */
+    RAW,
+
+    /**
+     * Scalable Vector Graphics, rasterized on load
+     */
+    SVG
 }
 
 fun ZilImageFormat.hasEncoder(): Boolean {
@@ -61,6 +96,13 @@ fun ZilImageFormat.hasEncoder(): Boolean {
         ZilImageFormat.QOI -> true
         ZilImageFormat.JPEG_XL -> true
         ZilImageFormat.HDR -> true
-        ZilImageFormat.BMP -> false
+        ZilImageFormat.EXR -> true
+        ZilImageFormat.BMP -> true
+        ZilImageFormat.WEBP -> true
+        ZilImageFormat.GIF -> true
+        ZilImageFormat.ICO -> true
+        ZilImageFormat.TIFF -> false
+        ZilImageFormat.RAW -> false
+        ZilImageFormat.SVG -> false
     }
 }
\ No newline at end of file
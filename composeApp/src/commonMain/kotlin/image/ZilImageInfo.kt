@@ -0,0 +1,23 @@
+import image.zilColorSpaceFromInt
+import image.zilDepthFromNum
+import image.zilImageFormatFromNum
+
+/**
+ * Image properties read from the file headers, without decoding any pixels
+ *
+ * Constructed by the native side, see `rust/src/probe.rs`
+ * */
+class ZilImageInfo(val width: Long, val height: Long, formatId: Long, depthId: Long, colorspaceId: Long) {
+    val format: ZilImageFormat = zilImageFormatFromNum(formatId.toUInt())
+
+    /**
+     * Depth of the decoded pixels, [ZilDepth.Unknown] if the format
+     * doesn't declare it in its headers
+     * */
+    val depth: ZilDepth = zilDepthFromNum(depthId.toUInt())
+
+    /**
+     * Colorspace the pixels will be in once decoded
+     * */
+    val colorspace: ZilColorspace = zilColorSpaceFromInt(colorspaceId.toUInt())
+}
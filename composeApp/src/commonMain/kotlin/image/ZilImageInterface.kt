@@ -119,6 +119,25 @@ interface ZilImageInterface {
      * */
     public fun save(file: String, format: ZilImageFormat)
 
+    /**
+     * Save the image with a specific format and encoder options
+     *
+     * @param file: The file name to save to
+     * @param format: The image format to use, this will take precedence over file
+     * extension.
+     * @param options: Quality, compression and other encoder settings
+     * */
+    public fun save(file: String, format: ZilImageFormat, options: ZilEncodeOptions)
+
+    /**
+     * Encode the image in memory, without touching the filesystem
+     *
+     * @param format: The image format to encode to
+     * @param options: Encoder settings, null uses the defaults
+     * @return The encoded file contents
+     * */
+    public fun encode(format: ZilImageFormat, options: ZilEncodeOptions? = null): ByteArray
+
     /**
      * Save the image, determining the image format from extension
      *
@@ -221,11 +240,56 @@ interface ZilImageInterface {
      * Return the image exif metadata
      *
      * This returns an empty map if the image doesn't have exif info,
-     * otherwise returns exif information in key-value pair
+     * otherwise returns exif information in key-value pair.
+     * Values of 100 characters or more are left out, see [exifFields]
+     * for every field along with its raw value
      *
      * */
     fun exifMetadata(): Map<String, String>
 
+    /**
+     * Every EXIF field of the image, with the directory it came from and its raw value
+     *
+     * Empty if the image has no EXIF. Values too long to return with the other
+     * fields are fetched with [exifFieldBytes] and [exifFieldString]
+     * */
+    fun exifFields(): List<ZilExifField>
+
+    /**
+     * The raw bytes of an EXIF field, null if the image has no such field
+     *
+     * @param ifd The [ZilExifField.ifd] of the field
+     * @param tag The [ZilExifField.tag] of the field
+     * */
+    fun exifFieldBytes(ifd: String, tag: Int): ByteArray?
+
+    /**
+     * The whole formatted value of an EXIF field, null if the image has no such field
+     *
+     * @param ifd The [ZilExifField.ifd] of the field
+     * @param tag The [ZilExifField.tag] of the field
+     * */
+    fun exifFieldString(ifd: String, tag: Int): String?
+
+    /**
+     * Location, date and camera settings read from the image's EXIF,
+     * null if the image has no EXIF
+     * */
+    fun captureInfo(): ZilCaptureInfo?
+
+    /**
+     * Every XMP property of the image, read from the file or, for formats
+     * that can't embed XMP, from its `.xmp` sidecar
+     *
+     * Empty if the image has no XMP
+     * */
+    fun xmpProperties(): List<ZilXmpProperty>
+
+    /**
+     * Every IPTC-IIM dataset of the image, only JPEG files carry them
+     * */
+    fun iptcDatasets(): List<ZilIptcDataset>
+
     /**
      * Perform a gaussian blur on an image
      *
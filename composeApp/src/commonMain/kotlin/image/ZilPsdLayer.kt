@@ -0,0 +1,26 @@
+/**
+ * A layer of a Photoshop document
+ *
+ * Constructed by the native side, see `rust/src/codecs/psd.rs`
+ *
+ * @param name Layer name as shown in Photoshop
+ * @param group Groups the layer is nested in, outermost first, joined by `/`, empty at the top level
+ * @param left Left edge of the layer on the canvas, may be negative
+ * @param top Top edge of the layer on the canvas, may be negative
+ * @param width Width of the layer's pixels, zero for empty layers
+ * @param height Height of the layer's pixels, zero for empty layers
+ * @param opacity Layer opacity, 0 is fully transparent and 255 opaque
+ * @param blendMode Blend mode, e.g. `normal`, `multiply` or `passThrough`
+ * @param visible Whether the layer's eye is on in the layers panel
+ * */
+class ZilPsdLayer(
+    val name: String,
+    val group: String,
+    val left: Int,
+    val top: Int,
+    val width: Long,
+    val height: Long,
+    val opacity: Int,
+    val blendMode: String,
+    val visible: Boolean
+)
@@ -0,0 +1,34 @@
+/**
+ * Camera and sensor properties of a RAW file
+ *
+ * Constructed by the native side, see `rust/src/codecs/raw.rs`
+ *
+ * @param make Camera make as written in the file
+ * @param model Camera model as written in the file
+ * @param cleanMake Camera make, consistent across RAW formats
+ * @param cleanModel Camera model, consistent across RAW formats
+ * @param sensorWidth Width of the whole sensor, including masked borders
+ * @param sensorHeight Height of the whole sensor, including masked borders
+ * @param crops Borders removed when developing, as top, right, bottom and left
+ * @param cfaPattern Color filter array pattern, e.g. `RGGB`, empty if the image isn't mosaiced
+ * @param blackLevels Per channel black levels in RGBE order
+ * @param whiteLevels Per channel white levels in RGBE order
+ * @param whiteBalance As shot white balance multipliers in RGBE order, normalized to green
+ * @param xyzToCamera XYZ to camera color matrix, four rows of three in row-major order
+ * @param orientation EXIF orientation, 1-8, or 0 if unknown
+ * */
+class ZilRawMetadata(
+    val make: String,
+    val model: String,
+    val cleanMake: String,
+    val cleanModel: String,
+    val sensorWidth: Long,
+    val sensorHeight: Long,
+    val crops: IntArray,
+    val cfaPattern: String,
+    val blackLevels: IntArray,
+    val whiteLevels: IntArray,
+    val whiteBalance: FloatArray,
+    val xyzToCamera: FloatArray,
+    val orientation: Int
+)
@@ -0,0 +1,19 @@
+/**
+ * Size an SVG is rasterized at
+ *
+ * With neither [width] nor [height] set, the document's own size is scaled by `dpi / 96`.
+ * With one of them set, the other follows the document's aspect ratio.
+ * With both set, the document is scaled to fit and centered.
+ *
+ * Read field by field by the native side (see `rust/src/decoder.rs`),
+ * so field names must not change without updating it.
+ *
+ * @param width Output width in pixels, zero to derive it
+ * @param height Output height in pixels, zero to derive it
+ * @param dpi Resolution used when neither width nor height is set
+ * */
+data class ZilSvgOptions(
+    val width: Long = 0,
+    val height: Long = 0,
+    val dpi: Float = 96f
+)
@@ -0,0 +1,15 @@
+/**
+ * A small preview of an image file, see [ZilImageJni.thumbnail]
+ *
+ * Constructed by the native side, see `rust/src/thumbnail.rs`
+ *
+ * @param pixels 8 bit BGRA pixels, `width * height * 4` bytes
+ * @param embedded True if it is the thumbnail stored in the file's EXIF,
+ * false if it was made from the image itself
+ * */
+class ZilThumbnail(
+    val width: Int,
+    val height: Int,
+    val pixels: ByteArray,
+    val embedded: Boolean
+)
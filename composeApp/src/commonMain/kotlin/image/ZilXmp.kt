@@ -0,0 +1,81 @@
+/**
+ * How an XMP property stores its values
+ *
+ * The native side reads the ordinal, so the order must not change
+ * without updating `XmpKind` in `rust/src/xmp.rs`
+ * */
+enum class ZilXmpKind {
+    /**
+     * A single value
+     */
+    Text,
+
+    /**
+     * An unordered list, e.g. `dc:subject` keywords
+     */
+    Bag,
+
+    /**
+     * An ordered list, e.g. `dc:creator` authors
+     */
+    Seq,
+
+    /**
+     * The same value in several languages, e.g. `dc:title`
+     */
+    Alt,
+
+    /**
+     * A structure, kept as the XML it was read from. These can be removed but not set
+     */
+    Xml
+}
+
+/**
+ * A single XMP property
+ *
+ * Constructed by the native side, see `rust/src/xmp.rs`
+ *
+ * @param key Prefix and name of the property, e.g. `dc:subject`. Common namespaces
+ * always use the same prefix, whichever one the file used
+ * @param namespace Namespace URI the prefix stands for
+ * @param values The text for [ZilXmpKind.Text], the items of lists,
+ * or the element's XML for [ZilXmpKind.Xml]
+ * */
+class ZilXmpProperty(
+    val key: String,
+    val namespace: String,
+    val kind: ZilXmpKind,
+    val values: Array<String>
+)
+
+/**
+ * A single IPTC-IIM dataset, repeated datasets such as keywords have one entry per value
+ *
+ * Constructed by the native side, see `rust/src/iptc.rs`
+ *
+ * @param record Record number, 2 for the caption, keyword and copyright fields
+ * @param dataset Dataset number within the record, e.g. 25 for `Keywords`
+ * @param name Name of the dataset, e.g. `Caption-Abstract`, or `record:dataset` for ones the native side doesn't know
+ * */
+class ZilIptcDataset(
+    val record: Int,
+    val dataset: Int,
+    val name: String,
+    val value: String
+)
+
+/**
+ * Culling fields of an image's `.xmp` sidecar, as written by Lightroom, darktable and Bridge
+ *
+ * Constructed by the native side, see `rust/src/sidecar.rs`
+ *
+ * @param rating `xmp:Rating`, 1-5 stars, -1 for a rejected shot and 0 if unrated
+ * @param label `xmp:Label`, the color label's name, e.g. `Red`
+ * @param keywords `dc:subject`
+ * */
+class ZilSidecarTags(
+    val rating: Int,
+    val label: String?,
+    val keywords: Array<String>
+)
@@ -72,6 +72,35 @@ fun ZilImageFormat.toNum(): UInt {
         ZilImageFormat.JPEG_XL -> 7u
         ZilImageFormat.HDR -> 8u
         ZilImageFormat.BMP -> 9u
+        ZilImageFormat.WEBP -> 10u
+        ZilImageFormat.GIF -> 11u
+        ZilImageFormat.ICO -> 12u
+        ZilImageFormat.TIFF -> 13u
+        ZilImageFormat.RAW -> 14u
+        ZilImageFormat.EXR -> 15u
+        ZilImageFormat.SVG -> 16u
+    }
+}
 
+fun zilImageFormatFromNum(value: UInt): ZilImageFormat {
+    // nb should match rust definition in format_to_im_long
+    return when (value) {
+        1u -> ZilImageFormat.JPEG
+        2u -> ZilImageFormat.PNG
+        3u -> ZilImageFormat.PPM
+        4u -> ZilImageFormat.PSD
+        5u -> ZilImageFormat.Farbfeld
+        6u -> ZilImageFormat.QOI
+        7u -> ZilImageFormat.JPEG_XL
+        8u -> ZilImageFormat.HDR
+        9u -> ZilImageFormat.BMP
+        10u -> ZilImageFormat.WEBP
+        11u -> ZilImageFormat.GIF
+        12u -> ZilImageFormat.ICO
+        13u -> ZilImageFormat.TIFF
+        14u -> ZilImageFormat.RAW
+        15u -> ZilImageFormat.EXR
+        16u -> ZilImageFormat.SVG
+        else -> ZilImageFormat.UnknownFormat
     }
-}
\ No newline at end of file
+}
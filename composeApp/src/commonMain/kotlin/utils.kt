@@ -53,6 +53,8 @@ fun imageFormat(byteArray: UByteArray): ZilImageFormat {
         return ZilImageFormat.HDR
     } else if (byteArray.slice(0..6) == "#?RGBE\n".toList().map { it -> it.code.toUByte() }.toList()) {
         return ZilImageFormat.HDR
+    } else if (byteArray.slice(0..3) == ubyteArrayOf(0x76u, 0x2Fu, 0x31u, 0x01u).toList()) {
+        return ZilImageFormat.EXR
     } else if (byteArray.slice(0..11) == ubyteArrayOf(
             0x00u, 0x00u, 0x00u, 0x0Cu, 0x4Au, 0x58u, 0x4Cu, 0x20u, 0x0Du, 0x0Au, 0x87u, 0x0Au
         ).toList()
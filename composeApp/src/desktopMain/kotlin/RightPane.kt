@@ -181,9 +181,10 @@ fun RightPanel(appCtx: AppContext) {
 @Composable
 fun ExifMetadataPane(appCtx: AppContext) {
 
-    val exif: Map<String, String>? = appCtx.currentImageContext()?.imageToDisplay()?.innerInterface()?.exifMetadata()
+    val image = appCtx.currentImageContext()?.imageToDisplay()?.innerInterface()
+    val exif: List<ZilExifField>? = image?.exifFields()
 
-    if (exif != null) {
+    if (image != null && exif != null) {
 
         Column(modifier = Modifier.fillMaxWidth(), horizontalAlignment = Alignment.CenterHorizontally) {
 
@@ -196,22 +197,28 @@ fun ExifMetadataPane(appCtx: AppContext) {
                 )
                 //Divider()
             }
-            for ((k, v) in exif) {
+            for (field in exif) {
+                // long values are shortened, clicking shows the whole value
+                var expanded by remember(field) { mutableStateOf(false) }
+                val value = remember(field, expanded) {
+                    if (expanded && !field.complete) image.exifFieldString(field.ifd, field.tag) ?: field.formatted
+                    else field.formatted
+                }
                 Row(
-                    modifier = Modifier.fillMaxWidth().padding(all = 10.dp),
+                    modifier = Modifier.fillMaxWidth().clickable { expanded = !expanded }.padding(all = 10.dp),
                     horizontalArrangement = Arrangement.SpaceBetween
                 ) {
                     Text(
-                        k,
+                        field.name,
                         maxLines = 1,
                         overflow = TextOverflow.Ellipsis,
                         modifier = Modifier.padding(end = 10.dp),
                         style = TextStyle(fontSize = TextUnit(14F, TextUnitType.Sp))
                     )
                     Text(
-                        v,
+                        value,
                         overflow = TextOverflow.Ellipsis,
-                        maxLines = 1,
+                        maxLines = if (expanded) Int.MAX_VALUE else 1,
                         style = TextStyle(fontSize = TextUnit(14F, TextUnitType.Sp))
                     )
                 }
@@ -11,6 +11,10 @@ import androidx.compose.ui.unit.TextUnit
 import androidx.compose.ui.unit.TextUnitType
 import androidx.compose.ui.unit.dp
 import extensions.launchOnIoThread
+import kotlinx.coroutines.sync.withLock
+import org.jetbrains.skia.ColorAlphaType
+import org.jetbrains.skia.ColorSpace
+import org.jetbrains.skia.ImageInfo
 import java.io.File
 
 
@@ -26,12 +30,14 @@ fun SingleThumbnail(appContext: AppContext, file: File) {
 
         this.launchOnIoThread {
             if (!isLoaded) {
-                // load image
-                val sharedBuffer = SharedBuffer();
-                val im = ZilBitmap(file.path, sharedBuffer, ZilImageJni(file = file.path))
-                val sizes = calcResize(im, 200, 200)
-                im.inner.resize(sizes[0], sizes[1])
-                im.writeToCanvas(bitmap)
+                // uses the embedded EXIF thumbnail when there is one, rather than decoding the whole file
+                val thumbnail = ZilImageJni.thumbnail(file.path, 200)
+                val info = ImageInfo.makeN32(thumbnail.width, thumbnail.height, ColorAlphaType.UNPREMUL, ColorSpace.sRGB)
+                bitmap.mutex.withLock {
+                    bitmap.image.setImageInfo(info)
+                    assert(bitmap.image.allocPixels(info))
+                    assert(bitmap.image.installPixels(thumbnail.pixels))
+                }
                 isLoaded = true
 
             }
@@ -0,0 +1,293 @@
+//! Frame access for animated and multi-page images
+//!
+//! zune stores a delay in every frame but keeps it private, so the delay
+//! of each frame and the loop count are tracked here instead, next to the
+//! image in its handle. Decoders of animated formats fill them in and the
+//! GIF, WebP and PNG encoders write them back out.
+//!
+//! Each handle also has an active frame, the one the pixel exports read,
+//! and a filter scope deciding whether filters run on every frame or only
+//! the active one.
+use jni::objects::{JClass, JIntArray};
+use jni::sys::{jboolean, jint, jintArray, jlong, jsize};
+use jni::JNIEnv;
+use zune_image::frame::Frame;
+use zune_image::image::Image;
+use zune_image::traits::OperationsTrait;
+
+use crate::errors::{jni_guard, JniErrors};
+use crate::{containers, handles};
+
+/// Timing of an animation
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Animation {
+    /// How long each frame is shown in milliseconds, empty for still images
+    pub delays: Vec<u32>,
+    /// How many times the animation plays, zero loops forever
+    pub loop_count: u32
+}
+
+impl Animation {
+    /// The delay of `frame`, zero if it isn't known
+    pub fn delay(&self, frame: usize) -> u32 {
+        self.delays.get(frame).copied().unwrap_or(0)
+    }
+
+    /// Read the timing of an animated PNG from its `acTL` and `fcTL` chunks
+    ///
+    /// zune decodes every frame of an APNG but doesn't report their delays.
+    /// A default image that isn't part of the animation has no `fcTL` chunk
+    /// before its data and isn't decoded as a frame either.
+    pub fn from_png(data: &[u8]) -> Animation {
+        let mut animation = Animation::default();
+
+        for (kind, chunk) in containers::png_chunks(data) {
+            match kind {
+                b"acTL" if chunk.len() >= 8 => {
+                    animation.loop_count = u32::from_be_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+                }
+                b"fcTL" if chunk.len() >= 26 => {
+                    let numerator = u32::from(u16::from_be_bytes([chunk[20], chunk[21]]));
+                    // a zero denominator means hundredths of a second
+                    let denominator = match u16::from_be_bytes([chunk[22], chunk[23]]) {
+                        0 => 100,
+                        denominator => u32::from(denominator)
+                    };
+                    animation.delays.push(numerator * 1000 / denominator);
+                }
+                b"IEND" => break,
+                _ => ()
+            }
+        }
+        animation
+    }
+}
+
+/// A rectangle of a frame
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Region {
+    pub left: usize,
+    pub top: usize,
+    pub width: usize,
+    pub height: usize
+}
+
+impl Region {
+    pub fn full(width: usize, height: usize) -> Region {
+        Region {
+            left: 0,
+            top: 0,
+            width,
+            height
+        }
+    }
+
+    /// Copy the pixels inside the region out of a frame `frame_width` pixels wide
+    pub fn crop<T: Copy>(&self, pixels: &[T], frame_width: usize, pixel_size: usize) -> Vec<T> {
+        let row_size = self.width * pixel_size;
+        let mut cropped = Vec::with_capacity(row_size * self.height);
+
+        for y in self.top..self.top + self.height {
+            let start = (y * frame_width + self.left) * pixel_size;
+            cropped.extend_from_slice(&pixels[start..start + row_size]);
+        }
+        cropped
+    }
+}
+
+/// The smallest region covering every pixel that differs between two
+/// frames `width` pixels wide, `None` if they're identical
+///
+/// Animation encoders only store this part of a frame, the rest is
+/// left showing the previous one.
+pub fn changed_region<T: PartialEq>(previous: &[T], current: &[T], width: usize, pixel_size: usize) -> Option<Region> {
+    let row_size = width * pixel_size;
+    let (mut left, mut right) = (width, 0);
+    let (mut top, mut bottom) = (usize::MAX, 0);
+
+    for (y, (previous, current)) in previous.chunks_exact(row_size).zip(current.chunks_exact(row_size)).enumerate() {
+        let mut changed = previous
+            .chunks_exact(pixel_size)
+            .zip(current.chunks_exact(pixel_size))
+            .map(|(a, b)| a != b);
+
+        let Some(first) = changed.position(|changed| changed) else {
+            continue;
+        };
+        // position() consumed the iterator up to and including the first change
+        let last = changed.rposition(|changed| changed).map_or(first, |last| first + 1 + last);
+
+        left = left.min(first);
+        right = right.max(last + 1);
+        top = top.min(y);
+        bottom = y + 1;
+    }
+    (top != usize::MAX).then(|| Region {
+        left,
+        top,
+        width: right - left,
+        height: bottom - top
+    })
+}
+
+/// Indices of the frames worth writing along with how long they're shown,
+/// a frame identical to the previous one only extends its delay
+pub fn merge_identical<T: PartialEq>(frames: &[Vec<T>], animation: &Animation) -> Vec<(usize, u32)> {
+    let mut kept: Vec<(usize, u32)> = Vec::with_capacity(frames.len());
+
+    for (index, pixels) in frames.iter().enumerate() {
+        match kept.last_mut() {
+            Some((previous, delay)) if frames[*previous] == *pixels => *delay += animation.delay(index),
+            _ => kept.push((index, animation.delay(index)))
+        }
+    }
+    kept
+}
+
+/// Which frames filters run on
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum FilterScope {
+    #[default]
+    AllFrames,
+    ActiveFrame
+}
+
+/// Run `filter` on the active frame of `image` only
+///
+/// Filters changing the dimensions, colorspace or depth are rejected,
+/// every frame of an image must share them.
+pub fn execute_on_frame<T: OperationsTrait>(image: &mut Image, index: usize, filter: &T) -> Result<(), JniErrors> {
+    let frame = image
+        .frames_ref()
+        .get(index)
+        .ok_or_else(|| frame_out_of_range(index, image.frames_len()))?
+        .clone();
+    let (width, height) = image.dimensions();
+
+    let mut single = Image::new_frames(vec![frame], image.depth(), width, height, image.colorspace());
+    *single.metadata_mut() = image.metadata().clone();
+
+    filter.execute_impl(&mut single)?;
+
+    if single.dimensions() != image.dimensions()
+        || single.colorspace() != image.colorspace()
+        || single.depth() != image.depth()
+    {
+        return Err(JniErrors::InvalidArgument(format!(
+            "{} changes the image's dimensions, colorspace or depth and can only be applied to all frames",
+            filter.name()
+        )));
+    }
+    image.frames_mut()[index] = std::mem::replace(&mut single.frames_mut()[0], Frame::new(vec![]));
+    Ok(())
+}
+
+/// Copy frame `index` into an image of its own
+pub fn frame_image(image: &Image, index: usize) -> Result<Image, JniErrors> {
+    let frame = image
+        .frames_ref()
+        .get(index)
+        .ok_or_else(|| frame_out_of_range(index, image.frames_len()))?;
+    let (width, height) = image.dimensions();
+
+    let mut single = Image::new_frames(vec![frame.clone()], image.depth(), width, height, image.colorspace());
+    *single.metadata_mut() = image.metadata().clone();
+    Ok(single)
+}
+
+pub fn frame_out_of_range(index: usize, frames: usize) -> JniErrors {
+    JniErrors::InvalidArgument(format!("Frame {index} out of range, the image has {frames} frames"))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_getFrameCountNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jlong {
+    jni_guard(&mut env, "getFrameCountNative", |_| {
+        Ok(handles::with_image(image_ptr, |image| image.frames_len())? as jlong)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_getFrameDelaysNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jintArray {
+    jni_guard(&mut env, "getFrameDelaysNative", |env| {
+        let delays: Vec<jint> = handles::with_entry(image_ptr, |entry| {
+            (0..entry.image.frames_len())
+                .map(|frame| entry.animation.delay(frame).min(i32::MAX as u32) as jint)
+                .collect()
+        })?;
+        let array = env.new_int_array(delays.len() as jsize)?;
+        env.set_int_array_region(&array, 0, &delays)?;
+        Ok(array)
+    })
+    .into_raw()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setFrameDelaysNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, delays: JIntArray) {
+    jni_guard(&mut env, "setFrameDelaysNative", |env| {
+        let mut values = vec![0; env.get_array_length(&delays)? as usize];
+        env.get_int_array_region(&delays, 0, &mut values)?;
+
+        if values.iter().any(|delay| *delay < 0) {
+            return Err(JniErrors::InvalidArgument(format!("Frame delays can't be negative, found {values:?}")));
+        }
+        handles::with_entry(image_ptr, |entry| {
+            let frames = entry.image.frames_len();
+            if values.len() != frames {
+                return Err(JniErrors::InvalidArgument(format!(
+                    "Expected {frames} frame delays, found {}",
+                    values.len()
+                )));
+            }
+            entry.animation.delays = values.iter().map(|delay| *delay as u32).collect();
+            Ok(())
+        })?
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_getLoopCountNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jlong {
+    jni_guard(&mut env, "getLoopCountNative", |_| {
+        Ok(handles::with_entry(image_ptr, |entry| entry.animation.loop_count)? as jlong)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setLoopCountNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, loop_count: jlong) {
+    jni_guard(&mut env, "setLoopCountNative", |_| {
+        let loop_count = u16::try_from(loop_count)
+            .map_err(|_| JniErrors::InvalidArgument(format!("Loop count should be between 0 and 65535, found {loop_count}")))?;
+        handles::with_entry(image_ptr, |entry| entry.animation.loop_count = u32::from(loop_count))?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_getActiveFrameNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jlong {
+    jni_guard(&mut env, "getActiveFrameNative", |_| {
+        Ok(handles::with_entry(image_ptr, |entry| entry.active_frame)? as jlong)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setActiveFrameNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, frame: jlong) {
+    jni_guard(&mut env, "setActiveFrameNative", |_| {
+        handles::with_entry(image_ptr, |entry| {
+            let frames = entry.image.frames_len();
+            let index = usize::try_from(frame)
+                .ok()
+                .filter(|index| *index < frames)
+                .ok_or_else(|| JniErrors::InvalidArgument(format!("Frame {frame} out of range, the image has {frames} frames")))?;
+            entry.active_frame = index;
+            Ok(())
+        })?
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setFilterAllFramesNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, all_frames: jboolean) {
+    jni_guard(&mut env, "setFilterAllFramesNative", |_| {
+        let scope = if all_frames != 0 { FilterScope::AllFrames } else { FilterScope::ActiveFrame };
+        handles::with_entry(image_ptr, |entry| entry.filter_scope = scope)?;
+        Ok(())
+    })
+}
@@ -0,0 +1,33 @@
+//! Codecs used in place of, or in addition to, the ones zune-image ships
+//!
+//! Each encoder implements zune's [`EncoderTrait`](zune_image::traits::EncoderTrait),
+//! so converting an image to a colorspace and depth the format supports
+//! is done by zune exactly as it is for its own encoders.
+//!
+//! Decoders for formats zune can't read produce zune [`Image`](zune_image::image::Image)s
+//! directly, one frame per animation frame or page.
+mod bmp;
+mod exr;
+mod gif;
+mod ico;
+mod jpeg;
+mod jpeg_dc;
+mod png;
+mod psd;
+mod raw;
+mod svg;
+mod tiff;
+mod webp;
+
+pub use bmp::BmpEncoder;
+pub use exr::{decode_exr, probe_exr, ExrEncoder};
+pub use gif::{decode_gif, probe_gif, GifEncoder};
+pub use ico::{IcoEncoder, MAX_ICON_SIZE};
+pub use jpeg::{JpegEncoder, MAX_EXIF_LENGTH, MAX_XMP_LENGTH};
+pub use jpeg_dc::decode_jpeg_dc;
+pub use png::{PngEncoder, PngFilter};
+pub use psd::decode_psd_layer;
+pub use raw::{decode_raw, is_camera_raw, probe_raw};
+pub use svg::{decode_svg, decode_svg_to_fit, probe_svg};
+pub use tiff::{decode_tiff, probe_tiff};
+pub use webp::{decode_webp, probe_webp, WebPEncoder};
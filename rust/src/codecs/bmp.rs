@@ -0,0 +1,159 @@
+//! Windows bitmap encoding
+//!
+//! zune-image can read BMP files but has no encoder, this writes
+//! uncompressed bottom-up 24 bit BGR or 32 bit BGRA files.
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::{ImageErrors, ImgEncodeErrors};
+use zune_image::image::Image;
+use zune_image::traits::EncoderTrait;
+
+const FILE_HEADER_SIZE: usize = 14;
+/// BITMAPINFOHEADER, enough for 24 bit images
+const INFO_HEADER_SIZE: usize = 40;
+/// BITMAPV4HEADER, needed to declare an alpha channel
+const V4_HEADER_SIZE: usize = 108;
+
+const BI_RGB: u32 = 0;
+const BI_BITFIELDS: u32 = 3;
+/// `LCS_sRGB` colorspace type
+const LCS_SRGB: u32 = 0x7352_4742;
+
+pub struct BmpEncoder {
+    /// 24 or 32
+    bits_per_pixel: u16
+}
+
+impl BmpEncoder {
+    /// Create an encoder writing `bits_per_pixel` bit pixels, anything
+    /// but 32 writes 24 bit pixels
+    pub fn new(bits_per_pixel: u16) -> BmpEncoder {
+        let bits_per_pixel = if bits_per_pixel == 32 { 32 } else { 24 };
+        BmpEncoder { bits_per_pixel }
+    }
+}
+
+impl EncoderTrait for BmpEncoder {
+    fn name(&self) -> &'static str {
+        "bmp"
+    }
+
+    fn encode_inner(&mut self, image: &Image) -> Result<Vec<u8>, ImageErrors> {
+        let colorspace = image.colorspace();
+        if !self.supported_colorspaces().contains(&colorspace) {
+            return Err(ImgEncodeErrors::UnsupportedColorspace(colorspace, self.supported_colorspaces()).into());
+        }
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Err(ImgEncodeErrors::ImageEncodeErrors(format!("Cannot encode a {width} x {height} image")).into());
+        }
+        let in_components = colorspace.num_components();
+        let out_components = usize::from(self.bits_per_pixel / 8);
+
+        // rows are padded to a multiple of four bytes
+        let stride = (width * out_components + 3) & !3;
+        let header_size = if self.bits_per_pixel == 32 {
+            V4_HEADER_SIZE
+        } else {
+            INFO_HEADER_SIZE
+        };
+        let data_offset = FILE_HEADER_SIZE + header_size;
+        let file_size = stride
+            .checked_mul(height)
+            .and_then(|x| x.checked_add(data_offset))
+            .filter(|x| *x <= u32::MAX as usize && width <= i32::MAX as usize && height <= i32::MAX as usize)
+            .ok_or_else(|| ImgEncodeErrors::ImageEncodeErrors(format!("Too large image dimensions {width} x {height}")))?;
+
+        let mut out = Vec::with_capacity(file_size);
+
+        // BITMAPFILEHEADER
+        out.extend_from_slice(b"BM");
+        out.extend_from_slice(&(file_size as u32).to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(data_offset as u32).to_le_bytes());
+
+        // BITMAPINFOHEADER, positive height means rows are stored bottom up
+        out.extend_from_slice(&(header_size as u32).to_le_bytes());
+        out.extend_from_slice(&(width as i32).to_le_bytes());
+        out.extend_from_slice(&(height as i32).to_le_bytes());
+        out.extend_from_slice(&1_u16.to_le_bytes());
+        out.extend_from_slice(&self.bits_per_pixel.to_le_bytes());
+
+        if self.bits_per_pixel == 32 {
+            out.extend_from_slice(&BI_BITFIELDS.to_le_bytes());
+        } else {
+            out.extend_from_slice(&BI_RGB.to_le_bytes());
+        }
+        out.extend_from_slice(&((stride * height) as u32).to_le_bytes());
+        // 2835 pixels per metre is 72 dpi
+        out.extend_from_slice(&2835_i32.to_le_bytes());
+        out.extend_from_slice(&2835_i32.to_le_bytes());
+        // colors used and important colors
+        out.extend_from_slice(&[0; 8]);
+
+        if self.bits_per_pixel == 32 {
+            // red, green, blue and alpha masks for BGRA pixels
+            for mask in [0x00FF_0000_u32, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000] {
+                out.extend_from_slice(&mask.to_le_bytes());
+            }
+            out.extend_from_slice(&LCS_SRGB.to_le_bytes());
+            // endpoints and gamma are ignored for sRGB
+            out.extend_from_slice(&[0; 48]);
+        }
+
+        let pixels = &image.flatten_frames::<u8>()[0];
+        let padding = stride - width * out_components;
+
+        for row in pixels.chunks_exact(width * in_components).rev() {
+            for pixel in row.chunks_exact(in_components) {
+                let (r, g, b) = match colorspace {
+                    ColorSpace::Luma | ColorSpace::LumaA => (pixel[0], pixel[0], pixel[0]),
+                    _ => (pixel[0], pixel[1], pixel[2])
+                };
+                out.extend_from_slice(&[b, g, r]);
+
+                if out_components == 4 {
+                    let alpha = if colorspace.has_alpha() {
+                        pixel[in_components - 1]
+                    } else {
+                        255
+                    };
+                    out.push(alpha);
+                }
+            }
+            out.extend_from_slice(&[0; 3][..padding]);
+        }
+
+        Ok(out)
+    }
+
+    fn supported_colorspaces(&self) -> &'static [ColorSpace] {
+        &[
+            ColorSpace::Luma,
+            ColorSpace::LumaA,
+            ColorSpace::RGB,
+            ColorSpace::RGBA
+        ]
+    }
+
+    fn format(&self) -> ImageFormat {
+        ImageFormat::BMP
+    }
+
+    fn supported_bit_depth(&self) -> &'static [BitDepth] {
+        &[BitDepth::Eight]
+    }
+
+    fn default_depth(&self, _: BitDepth) -> BitDepth {
+        BitDepth::Eight
+    }
+
+    fn default_colorspace(&self, colorspace: ColorSpace) -> ColorSpace {
+        if colorspace.has_alpha() {
+            ColorSpace::RGBA
+        } else {
+            ColorSpace::RGB
+        }
+    }
+}
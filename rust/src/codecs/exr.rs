@@ -0,0 +1,334 @@
+//! OpenEXR decoding and encoding via the `exr` crate
+//!
+//! An EXR file holds one or more parts, each of which may group its
+//! channels into layers by prefixing their names, e.g. `diffuse.R`.
+//! We flatten both into a single list of layers named `part.prefix`,
+//! the unnamed layer holding plain `R`, `G`, `B` is the default one.
+//!
+//! EXR stores alpha premultiplied, zune images are not, so colors are
+//! divided by alpha on decode and multiplied by it on encode.
+use std::io::Cursor;
+
+use exr::prelude::{
+    f16, AnyChannel, AnyChannels, Encoding, FlatSamples, Image as ExrImage, Layer, LayerAttributes, MetaData,
+    ReadChannels, ReadLayers, SmallVec, Text, WritableImage
+};
+use jni::objects::{JClass, JObject, JString};
+use jni::sys::{jobjectArray, jsize};
+use jni::JNIEnv;
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::{ImageErrors, ImgEncodeErrors};
+use zune_image::image::Image;
+use zune_image::traits::EncoderTrait;
+
+use crate::decoder::DecodeLimits;
+use crate::errors::{jni_guard, JniErrors};
+use crate::format::Format;
+use crate::get_string;
+use crate::probe::ImageInfo;
+
+fn decode_error<S: Into<String>>(msg: S) -> JniErrors {
+    JniErrors::from(ImageErrors::ImageDecodeErrors(msg.into())).with_format(Format::Exr)
+}
+
+fn unsupported(reason: String) -> JniErrors {
+    JniErrors::Unsupported {
+        reason,
+        format: Some(Format::Exr.to_string())
+    }
+}
+
+/// Split a channel name into its layer prefix and base name,
+/// e.g. `diffuse.R` is (`diffuse`, `R`)
+fn split_channel_name(name: &str) -> (&str, &str) {
+    match name.rsplit_once('.') {
+        Some((prefix, base)) => (prefix, base),
+        None => ("", name)
+    }
+}
+
+/// Full name of the layer holding `channel` in a part named `part`
+fn layer_name(part: Option<&Text>, channel: &str) -> String {
+    let (prefix, _) = split_channel_name(channel);
+
+    match part.map(|p| p.to_string()).filter(|p| !p.is_empty()) {
+        Some(part) if prefix.is_empty() => part,
+        Some(part) => format!("{part}.{prefix}"),
+        None => prefix.to_string()
+    }
+}
+
+/// How the channels of a layer map onto an image
+struct LayerLayout {
+    part: usize,
+    width: usize,
+    height: usize,
+    colorspace: ColorSpace,
+    /// Index of the channel in the part for each component of `colorspace`
+    channels: Vec<usize>
+}
+
+/// Work out the colorspace of a layer from its channels' base names
+fn layout(part: usize, width: usize, height: usize, channels: &[(usize, String)]) -> Option<LayerLayout> {
+    let find = |base: &str| channels.iter().find(|(_, b)| *b == base).map(|(i, _)| *i);
+
+    let (colorspace, indices) = match (find("R"), find("G"), find("B"), find("Y"), find("A")) {
+        (Some(r), Some(g), Some(b), _, Some(a)) => (ColorSpace::RGBA, vec![r, g, b, a]),
+        (Some(r), Some(g), Some(b), _, None) => (ColorSpace::RGB, vec![r, g, b]),
+        (_, _, _, Some(y), Some(a)) => (ColorSpace::LumaA, vec![y, a]),
+        (_, _, _, Some(y), None) => (ColorSpace::Luma, vec![y]),
+        // a single data channel, e.g. depth
+        _ if channels.len() == 1 => (ColorSpace::Luma, vec![channels[0].0]),
+        _ => return None
+    };
+    Some(LayerLayout {
+        part,
+        width,
+        height,
+        colorspace,
+        channels: indices
+    })
+}
+
+/// List every layer in the file, along with whether we can decode it
+fn layers(meta: &MetaData) -> Vec<(String, Option<LayerLayout>)> {
+    let mut layers = Vec::new();
+
+    for (part, header) in meta.headers.iter().enumerate() {
+        // layer name and the (index, base name) of its channels
+        let mut groups: Vec<(String, Vec<(usize, String)>)> = Vec::new();
+
+        for (index, channel) in header.channels.list.iter().enumerate() {
+            let full_name = channel.name.to_string();
+            let name = layer_name(header.own_attributes.layer_name.as_ref(), &full_name);
+            let base = split_channel_name(&full_name).1.to_string();
+
+            match groups.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, channels)) => channels.push((index, base)),
+                None => groups.push((name, vec![(index, base)]))
+            }
+        }
+        let (width, height) = (header.layer_size.0, header.layer_size.1);
+
+        for (name, channels) in groups {
+            let subsampled = channels
+                .iter()
+                .any(|(i, _)| header.channels.list[*i].sampling != (1, 1).into());
+            let layout = if header.deep || subsampled {
+                None
+            } else {
+                layout(part, width, height, &channels)
+            };
+            layers.push((name, layout));
+        }
+    }
+    layers
+}
+
+fn read_meta(data: &[u8]) -> Result<MetaData, JniErrors> {
+    MetaData::read_from_buffered(Cursor::new(data), false).map_err(|e| decode_error(e.to_string()))
+}
+
+/// Find the layer called `name`, or the default layer if `name` is `None`
+fn select_layer(meta: &MetaData, name: Option<&str>) -> Result<LayerLayout, JniErrors> {
+    let mut layers = layers(meta);
+
+    let index = match name {
+        Some(name) => layers
+            .iter()
+            .position(|(n, _)| n == name)
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("No EXR layer named \"{name}\"")))?,
+        // prefer the unnamed layer, then color layers, then anything we can decode
+        None => {
+            let is_color = |l: &Option<LayerLayout>| {
+                l.as_ref()
+                    .is_some_and(|l| matches!(l.colorspace, ColorSpace::RGB | ColorSpace::RGBA))
+            };
+            layers
+                .iter()
+                .position(|(n, l)| n.is_empty() && l.is_some())
+                .or_else(|| layers.iter().position(|(_, l)| is_color(l)))
+                .or_else(|| layers.iter().position(|(_, l)| l.is_some()))
+                .ok_or_else(|| unsupported("EXR file contains no layers we can decode".to_string()))?
+        }
+    };
+    let (name, layout) = layers.swap_remove(index);
+
+    layout.ok_or_else(|| unsupported(format!("EXR layer \"{name}\" has no channels we can decode")))
+}
+
+/// Names of all layers in the file, the unnamed default layer is an empty string
+pub fn exr_layers(data: &[u8]) -> Result<Vec<String>, JniErrors> {
+    Ok(layers(&read_meta(data)?).into_iter().map(|(name, _)| name).collect())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_exrLayersNative(mut env: JNIEnv, _class: JClass, filename: JString) -> jobjectArray {
+    jni_guard(&mut env, "exrLayersNative", |env| {
+        let path = get_string(env, &filename)?;
+        let data = std::fs::read(&path).map_err(|e| JniErrors::from(e).with_path(&path))?;
+        let layers = exr_layers(&data).map_err(|e| e.with_path(&path))?;
+
+        let array = env.new_object_array(layers.len() as jsize, "java/lang/String", JObject::null())?;
+        for (index, layer) in layers.iter().enumerate() {
+            let name = env.new_string(layer)?;
+            env.set_object_array_element(&array, index as jsize, name)?;
+        }
+        Ok(array)
+    })
+    .into_raw()
+}
+
+/// Parse the headers of the default layer
+pub fn probe_exr(data: &[u8]) -> Result<ImageInfo, JniErrors> {
+    let layer = select_layer(&read_meta(data)?, None)?;
+
+    Ok(ImageInfo {
+        width: layer.width,
+        height: layer.height,
+        format: Format::Exr,
+        depth: BitDepth::Float32,
+        colorspace: layer.colorspace
+    })
+}
+
+/// Decode the layer called `layer`, or the default layer if it's `None`
+///
+/// Half and unsigned integer channels are converted to float
+pub fn decode_exr(data: &[u8], layer: Option<&str>, limits: &DecodeLimits) -> Result<Image, JniErrors> {
+    let meta = read_meta(data)?;
+    let layout = select_layer(&meta, layer)?;
+
+    // every part is decoded, not only the channels of the layer
+    let decoded_size: usize = meta
+        .headers
+        .iter()
+        .map(|h| h.layer_size.area() * h.channels.list.len() * 4)
+        .sum();
+    limits.check_memory((decoded_size + layout.width * layout.height * layout.channels.len() * 4) as u64)?;
+
+    let image = exr::prelude::read()
+        .no_deep_data()
+        .largest_resolution_level()
+        .all_channels()
+        .all_layers()
+        .all_attributes()
+        .from_buffered(Cursor::new(data))
+        .map_err(|e| decode_error(e.to_string()))?;
+
+    let channels = &image.layer_data[layout.part].channel_data.list;
+    let components = layout.channels.len();
+    let mut pixels = vec![0.0_f32; layout.width * layout.height * components];
+
+    for (c, index) in layout.channels.iter().enumerate() {
+        let samples = channels[*index].sample_data.values_as_f32();
+
+        for (dst, src) in pixels.iter_mut().skip(c).step_by(components).zip(samples) {
+            *dst = src;
+        }
+    }
+    if layout.colorspace.has_alpha() {
+        for pixel in pixels.chunks_exact_mut(components) {
+            let (colors, alpha) = pixel.split_at_mut(components - 1);
+            if alpha[0] > 0.0 {
+                colors.iter_mut().for_each(|c| *c /= alpha[0]);
+            }
+        }
+    }
+    Ok(Image::from_f32(&pixels, layout.width, layout.height, layout.colorspace))
+}
+
+pub struct ExrEncoder {
+    /// Store channels as 16 bit half floats rather than 32 bit floats
+    half: bool
+}
+
+impl ExrEncoder {
+    pub fn new(half: bool) -> ExrEncoder {
+        ExrEncoder { half }
+    }
+}
+
+impl EncoderTrait for ExrEncoder {
+    fn name(&self) -> &'static str {
+        "exr"
+    }
+
+    fn encode_inner(&mut self, image: &Image) -> Result<Vec<u8>, ImageErrors> {
+        let colorspace = image.colorspace();
+        let names: &[&str] = match colorspace {
+            ColorSpace::Luma => &["Y"],
+            ColorSpace::LumaA => &["Y", "A"],
+            ColorSpace::RGB => &["R", "G", "B"],
+            ColorSpace::RGBA => &["R", "G", "B", "A"],
+            _ => {
+                return Err(ImgEncodeErrors::UnsupportedColorspace(colorspace, self.supported_colorspaces()).into())
+            }
+        };
+        let (width, height) = image.dimensions();
+        let mut pixels = image.flatten_frames::<f32>().swap_remove(0);
+        let components = names.len();
+
+        if colorspace.has_alpha() {
+            for pixel in pixels.chunks_exact_mut(components) {
+                let (colors, alpha) = pixel.split_at_mut(components - 1);
+                colors.iter_mut().for_each(|c| *c *= alpha[0]);
+            }
+        }
+
+        let channels: SmallVec<[AnyChannel<FlatSamples>; 4]> = names
+            .iter()
+            .enumerate()
+            .map(|(c, name)| {
+                let values = pixels.iter().skip(c).step_by(components);
+                let samples = if self.half {
+                    FlatSamples::F16(values.map(|v| f16::from_f32(*v)).collect())
+                } else {
+                    FlatSamples::F32(values.copied().collect())
+                };
+                AnyChannel::new(*name, samples)
+            })
+            .collect();
+
+        let layer = Layer::new(
+            (width, height),
+            LayerAttributes::default(),
+            Encoding::SMALL_LOSSLESS,
+            AnyChannels::sort(channels)
+        );
+        let mut output = Cursor::new(Vec::new());
+        ExrImage::from_layer(layer)
+            .write()
+            .to_buffered(&mut output)
+            .map_err(|e| ImgEncodeErrors::ImageEncodeErrors(e.to_string()))?;
+
+        Ok(output.into_inner())
+    }
+
+    fn supported_colorspaces(&self) -> &'static [ColorSpace] {
+        &[ColorSpace::Luma, ColorSpace::LumaA, ColorSpace::RGB, ColorSpace::RGBA]
+    }
+
+    fn format(&self) -> ImageFormat {
+        // zune has no id for EXR
+        ImageFormat::Unknown
+    }
+
+    fn supported_bit_depth(&self) -> &'static [BitDepth] {
+        &[BitDepth::Float32]
+    }
+
+    fn default_depth(&self, _: BitDepth) -> BitDepth {
+        BitDepth::Float32
+    }
+
+    fn default_colorspace(&self, colorspace: ColorSpace) -> ColorSpace {
+        match colorspace {
+            ColorSpace::Luma | ColorSpace::LumaA | ColorSpace::RGB | ColorSpace::RGBA => colorspace,
+            _ if colorspace.has_alpha() => ColorSpace::RGBA,
+            _ => ColorSpace::RGB
+        }
+    }
+}
@@ -0,0 +1,277 @@
+//! GIF encoding and decoding via the `gif` crate
+//!
+//! GIF frames hold at most 256 colors, images are quantized with
+//! NeuQuant into a palette local to the frame.
+//!
+//! Animations are written as patches too, each frame only stores the
+//! region that changed with unchanged pixels in it made transparent,
+//! and identical frames are merged.
+//!
+//! Animated GIFs store frames as patches over the previous one, decoding
+//! composites them so each frame of the resulting image is a full picture.
+use gif::{ColorOutput, DecodeOptions, DisposalMethod, Frame, MemoryLimit, Repeat};
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::{ImageErrors, ImgEncodeErrors};
+use zune_image::frame::Frame as ZuneFrame;
+use zune_image::image::Image;
+use zune_image::traits::EncoderTrait;
+
+use crate::animation::{changed_region, merge_identical, Animation, Region};
+use crate::decoder::DecodeLimits;
+use crate::errors::JniErrors;
+use crate::format::Format;
+use crate::probe::ImageInfo;
+
+pub struct GifEncoder {
+    /// Quantizer speed, 1 (best palette) - 30 (fastest)
+    speed: i32,
+    /// Frame delays and loop count, used when the image has several frames
+    animation: Animation
+}
+
+impl GifEncoder {
+    pub fn new(speed: u8) -> GifEncoder {
+        GifEncoder {
+            speed: i32::from(speed.clamp(1, 30)),
+            animation: Animation::default()
+        }
+    }
+
+    /// Write every frame of the image, timed by `animation`
+    pub fn animated(mut self, animation: Animation) -> GifEncoder {
+        self.animation = animation;
+        self
+    }
+}
+
+/// GIF repetitions from how many times an animation plays, GIFs play once more than they repeat
+fn repeat(loop_count: u32) -> Repeat {
+    match loop_count {
+        0 => Repeat::Infinite,
+        plays => Repeat::Finite(u16::try_from(plays - 1).unwrap_or(u16::MAX))
+    }
+}
+
+/// Every frame as RGBA, GIF pixels are either opaque or transparent so
+/// transparent ones are zeroed to compare equal
+fn rgba_frames(image: &Image) -> Vec<Vec<u8>> {
+    let colorspace = image.colorspace();
+
+    image
+        .flatten_frames::<u8>()
+        .into_iter()
+        .map(|pixels| match colorspace {
+            ColorSpace::RGB => pixels.chunks_exact(3).flat_map(|p| [p[0], p[1], p[2], 255]).collect(),
+            _ => {
+                let mut pixels = pixels;
+                for pixel in pixels.chunks_exact_mut(4).filter(|p| p[3] == 0) {
+                    pixel.fill(0);
+                }
+                pixels
+            }
+        })
+        .collect()
+}
+
+/// Whether `next` is transparent anywhere `current` isn't
+fn reveals_transparency(current: &[u8], next: &[u8]) -> bool {
+    current
+        .chunks_exact(4)
+        .zip(next.chunks_exact(4))
+        .any(|(current, next)| next[3] == 0 && current[3] != 0)
+}
+
+fn loop_count(repeat: Repeat) -> u32 {
+    match repeat {
+        Repeat::Infinite => 0,
+        Repeat::Finite(repetitions) => u32::from(repetitions) + 1
+    }
+}
+
+impl EncoderTrait for GifEncoder {
+    fn name(&self) -> &'static str {
+        "gif"
+    }
+
+    fn encode_inner(&mut self, image: &Image) -> Result<Vec<u8>, ImageErrors> {
+        let colorspace = image.colorspace();
+        let (width, height) = image.dimensions();
+        let (width, height) = match (u16::try_from(width), u16::try_from(height)) {
+            (Ok(w), Ok(h)) => (w, h),
+            _ => {
+                let msg = format!("Too large image dimensions {width} x {height}, maximum is 65535 x 65535");
+                return Err(ImgEncodeErrors::ImageEncodeErrors(msg).into());
+            }
+        };
+        if !matches!(colorspace, ColorSpace::RGB | ColorSpace::RGBA) {
+            return Err(ImgEncodeErrors::UnsupportedColorspace(colorspace, self.supported_colorspaces()).into());
+        }
+        let to_err = |e: gif::EncodingError| ImgEncodeErrors::ImageEncodeErrors(e.to_string());
+
+        let (width, height) = (usize::from(width), usize::from(height));
+        let frames = rgba_frames(image);
+        let kept = merge_identical(&frames, &self.animation);
+
+        let mut encoder = gif::Encoder::new(Vec::new(), width as u16, height as u16, &[]).map_err(to_err)?;
+
+        if kept.len() > 1 {
+            encoder.set_repeat(repeat(self.animation.loop_count)).map_err(to_err)?;
+        }
+        // what a decoder shows before drawing the next frame
+        let mut canvas = vec![0_u8; width * height * 4];
+
+        for (position, (index, delay)) in kept.iter().enumerate() {
+            let pixels = &frames[*index];
+            // a frame that hides pixels the previous one showed can't be drawn over it,
+            // the previous frame has to cover the whole canvas and be cleared instead
+            let clear = kept
+                .get(position + 1)
+                .is_some_and(|(next, _)| reveals_transparency(pixels, &frames[*next]));
+
+            let region = match changed_region(&canvas, pixels, width, 4) {
+                _ if position == 0 || clear => Region::full(width, height),
+                Some(region) => region,
+                // a fully transparent frame over a cleared canvas
+                None => Region { left: 0, top: 0, width: 1, height: 1 }
+            };
+            let mut patch = region.crop(pixels, width, 4);
+            let below = region.crop(&canvas, width, 4);
+
+            // pixels matching what's already shown become transparent, letting it show through
+            for (pixel, below) in patch.chunks_exact_mut(4).zip(below.chunks_exact(4)) {
+                if pixel == below {
+                    pixel.fill(0);
+                }
+            }
+            let mut frame = Frame::from_rgba_speed(region.width as u16, region.height as u16, &mut patch, self.speed);
+            frame.left = region.left as u16;
+            frame.top = region.top as u16;
+            // delays are in hundredths of a second
+            frame.delay = u16::try_from(delay.div_ceil(10)).unwrap_or(u16::MAX);
+            frame.dispose = if clear { DisposalMethod::Background } else { DisposalMethod::Keep };
+
+            encoder.write_frame(&frame).map_err(to_err)?;
+
+            if clear {
+                canvas.fill(0);
+            } else {
+                canvas.copy_from_slice(pixels);
+            }
+        }
+
+        Ok(encoder.into_inner().map_err(to_err)?)
+    }
+
+    fn supported_colorspaces(&self) -> &'static [ColorSpace] {
+        &[ColorSpace::RGB, ColorSpace::RGBA]
+    }
+
+    fn format(&self) -> ImageFormat {
+        // zune has no id for GIF
+        ImageFormat::Unknown
+    }
+
+    fn supported_bit_depth(&self) -> &'static [BitDepth] {
+        &[BitDepth::Eight]
+    }
+
+    fn default_depth(&self, _: BitDepth) -> BitDepth {
+        BitDepth::Eight
+    }
+
+    fn default_colorspace(&self, colorspace: ColorSpace) -> ColorSpace {
+        if colorspace.has_alpha() {
+            ColorSpace::RGBA
+        } else {
+            ColorSpace::RGB
+        }
+    }
+}
+
+fn decode_error(err: gif::DecodingError) -> JniErrors {
+    JniErrors::from(ImageErrors::ImageDecodeErrors(err.to_string())).with_format(Format::Gif)
+}
+
+/// Parse the logical screen descriptor
+pub fn probe_gif(data: &[u8]) -> Result<ImageInfo, JniErrors> {
+    let decoder = DecodeOptions::new().read_info(data).map_err(decode_error)?;
+
+    Ok(ImageInfo {
+        width: usize::from(decoder.width()),
+        height: usize::from(decoder.height()),
+        format: Format::Gif,
+        depth: BitDepth::Eight,
+        colorspace: ColorSpace::RGBA
+    })
+}
+
+/// Decode every frame of a GIF to RGBA, along with their timing
+pub fn decode_gif(data: &[u8], limits: &DecodeLimits) -> Result<(Image, Animation), JniErrors> {
+    let mut options = DecodeOptions::new();
+    options.set_color_output(ColorOutput::RGBA);
+    // our own limits were checked against the headers already
+    options.set_memory_limit(MemoryLimit::Unlimited);
+
+    let mut decoder = options.read_info(data).map_err(decode_error)?;
+    let (width, height) = (usize::from(decoder.width()), usize::from(decoder.height()));
+    let frame_size = width * height * 4;
+
+    let mut canvas = vec![0_u8; frame_size];
+    let mut frames = Vec::new();
+    let mut delays = Vec::new();
+
+    while let Some(frame) = decoder.read_next_frame().map_err(decode_error)? {
+        limits.check_memory((frame_size * (frames.len() + 1)) as u64)?;
+
+        // the area the frame covers, clipped to the canvas. Frames lying
+        // entirely outside it cover nothing but still take their time
+        let (left, top) = (usize::from(frame.left).min(width), usize::from(frame.top).min(height));
+        let frame_width = usize::from(frame.width);
+
+        let right = (left + frame_width).min(width);
+        let bottom = (top + usize::from(frame.height)).min(height);
+
+        let previous = (frame.dispose == DisposalMethod::Previous).then(|| canvas.clone());
+
+        for y in top..bottom {
+            let src = &frame.buffer[(y - top) * frame_width * 4..][..(right - left) * 4];
+            let dst = &mut canvas[(y * width + left) * 4..(y * width + right) * 4];
+
+            for (dst, src) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+                // transparent pixels let the previous frame show through
+                if src[3] != 0 {
+                    dst.copy_from_slice(src);
+                }
+            }
+        }
+        // delays are in hundredths of a second
+        frames.push(ZuneFrame::from_u8(&canvas, ColorSpace::RGBA, usize::from(frame.delay), 100));
+        delays.push(u32::from(frame.delay) * 10);
+
+        match frame.dispose {
+            DisposalMethod::Background => {
+                for y in top..bottom {
+                    canvas[(y * width + left) * 4..(y * width + right) * 4].fill(0);
+                }
+            }
+            DisposalMethod::Previous => {
+                if let Some(previous) = previous {
+                    canvas = previous;
+                }
+            }
+            _ => ()
+        }
+    }
+    if frames.is_empty() {
+        return Err(JniErrors::from(ImageErrors::ImageDecodeErrors("GIF contains no frames".to_string()))
+            .with_format(Format::Gif));
+    }
+    let animation = Animation {
+        delays,
+        loop_count: loop_count(decoder.repeat())
+    };
+    Ok((Image::new_frames(frames, BitDepth::Eight, width, height, ColorSpace::RGBA), animation))
+}
+
@@ -0,0 +1,130 @@
+//! Windows icon encoding
+//!
+//! An icon holds the same image at several sizes, each one is scaled to
+//! fit a square, centered on a transparent background and stored as PNG,
+//! which every Windows version since Vista understands.
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::{ImageErrors, ImgEncodeErrors};
+use zune_image::image::Image;
+use zune_image::traits::{EncoderTrait, OperationsTrait};
+use zune_imageprocs::resize::{Resize, ResizeMethod};
+
+use crate::codecs::{PngEncoder, PngFilter};
+
+const ICONDIR_SIZE: usize = 6;
+const ICONDIRENTRY_SIZE: usize = 16;
+/// Largest edge an icon entry can declare
+pub const MAX_ICON_SIZE: u32 = 256;
+
+pub struct IcoEncoder {
+    /// Edge lengths of the square entries, 1-256
+    sizes: Vec<u32>
+}
+
+impl IcoEncoder {
+    pub fn new(sizes: &[u32]) -> IcoEncoder {
+        let mut sizes: Vec<u32> = sizes
+            .iter()
+            .map(|size| (*size).clamp(1, MAX_ICON_SIZE))
+            .collect();
+        sizes.sort_unstable();
+        sizes.dedup();
+        IcoEncoder { sizes }
+    }
+
+    /// Scale `image` to fit a `size` x `size` square, padding the rest with transparency
+    fn icon_entry(image: &Image, size: u32) -> Result<Vec<u8>, ImageErrors> {
+        let size = size as usize;
+        let (width, height) = image.dimensions();
+        let longest = width.max(height);
+
+        let new_width = (width * size / longest).max(1);
+        let new_height = (height * size / longest).max(1);
+
+        let mut scaled = image.clone();
+        Resize::new(new_width, new_height, ResizeMethod::Bilinear).execute(&mut scaled)?;
+        let pixels = &scaled.flatten_frames::<u8>()[0];
+
+        let mut canvas = vec![0_u8; size * size * 4];
+        let (x, y) = ((size - new_width) / 2, (size - new_height) / 2);
+
+        for (row, dest) in pixels
+            .chunks_exact(new_width * 4)
+            .zip(canvas.chunks_exact_mut(size * 4).skip(y))
+        {
+            dest[x * 4..(x + new_width) * 4].copy_from_slice(row);
+        }
+        let entry = Image::from_u8(&canvas, size, size, ColorSpace::RGBA);
+
+        PngEncoder::new(9, PngFilter::Adaptive).encode(&entry)
+    }
+}
+
+impl EncoderTrait for IcoEncoder {
+    fn name(&self) -> &'static str {
+        "ico"
+    }
+
+    fn encode_inner(&mut self, image: &Image) -> Result<Vec<u8>, ImageErrors> {
+        let colorspace = image.colorspace();
+        if colorspace != ColorSpace::RGBA {
+            return Err(ImgEncodeErrors::UnsupportedColorspace(colorspace, self.supported_colorspaces()).into());
+        }
+        if self.sizes.is_empty() {
+            return Err(ImgEncodeErrors::GenericStatic("An icon needs at least one size").into());
+        }
+        let entries = self
+            .sizes
+            .iter()
+            .map(|size| Ok((*size, IcoEncoder::icon_entry(image, *size)?)))
+            .collect::<Result<Vec<_>, ImageErrors>>()?;
+
+        let mut offset = ICONDIR_SIZE + ICONDIRENTRY_SIZE * entries.len();
+        let total = offset + entries.iter().map(|(_, data)| data.len()).sum::<usize>();
+        let mut out = Vec::with_capacity(total);
+
+        // ICONDIR: reserved, type (1 = icon), count
+        out.extend_from_slice(&0_u16.to_le_bytes());
+        out.extend_from_slice(&1_u16.to_le_bytes());
+        out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+
+        for (size, data) in &entries {
+            // 256 is stored as zero
+            let edge = (*size % MAX_ICON_SIZE) as u8;
+            out.extend_from_slice(&[edge, edge, 0, 0]);
+            // planes and bits per pixel
+            out.extend_from_slice(&1_u16.to_le_bytes());
+            out.extend_from_slice(&32_u16.to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&(offset as u32).to_le_bytes());
+            offset += data.len();
+        }
+        for (_, data) in &entries {
+            out.extend_from_slice(data);
+        }
+        Ok(out)
+    }
+
+    fn supported_colorspaces(&self) -> &'static [ColorSpace] {
+        &[ColorSpace::RGBA]
+    }
+
+    fn format(&self) -> ImageFormat {
+        // zune has no id for ICO
+        ImageFormat::Unknown
+    }
+
+    fn supported_bit_depth(&self) -> &'static [BitDepth] {
+        &[BitDepth::Eight]
+    }
+
+    fn default_depth(&self, _: BitDepth) -> BitDepth {
+        BitDepth::Eight
+    }
+
+    fn default_colorspace(&self, _: ColorSpace) -> ColorSpace {
+        ColorSpace::RGBA
+    }
+}
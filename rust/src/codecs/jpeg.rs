@@ -0,0 +1,166 @@
+//! JPEG encoding via the `jpeg-encoder` crate
+//!
+//! zune-image wraps the same crate, but zune-core's
+//! `set_jpeg_encode_progressive` sets the optimized huffman flag
+//! instead, so there is no way to ask it for a progressive file.
+use jpeg_encoder::ColorType;
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_core::options::EncoderOptions;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::{ImageErrors, ImgEncodeErrors};
+use zune_image::image::Image;
+use zune_image::traits::EncoderTrait;
+
+use crate::metadata::JPEG_EXIF_MARKER;
+use crate::xmp::JPEG_XMP_MARKER;
+
+/// Largest application segment payload, its length field counts itself too
+const MAX_APP_SEGMENT: usize = 65533;
+/// Largest EXIF a JPEG can hold, after its identifier
+pub const MAX_EXIF_LENGTH: usize = MAX_APP_SEGMENT - JPEG_EXIF_MARKER.len();
+/// Largest XMP packet a JPEG can hold without extended XMP, which we don't write
+pub const MAX_XMP_LENGTH: usize = MAX_APP_SEGMENT - JPEG_XMP_MARKER.len();
+
+pub struct JpegEncoder {
+    options: EncoderOptions,
+    progressive: bool,
+    icc: Option<Vec<u8>>,
+    exif: Option<Vec<u8>>,
+    xmp: Option<Vec<u8>>,
+    iptc: Option<Vec<u8>>
+}
+
+impl JpegEncoder {
+    /// Create an encoder using the quality in `options`
+    pub fn new(options: EncoderOptions, progressive: bool) -> JpegEncoder {
+        JpegEncoder {
+            options,
+            progressive,
+            icc: None,
+            exif: None,
+            xmp: None,
+            iptc: None
+        }
+    }
+
+    /// Embed an ICC profile describing the pixels
+    pub fn icc_profile(mut self, profile: Option<Vec<u8>>) -> JpegEncoder {
+        self.icc = profile;
+        self
+    }
+
+    /// Embed EXIF, stored as a TIFF structure
+    pub fn exif(mut self, exif: Option<Vec<u8>>) -> JpegEncoder {
+        self.exif = exif;
+        self
+    }
+
+    /// Embed an XMP packet
+    pub fn xmp(mut self, xmp: Option<Vec<u8>>) -> JpegEncoder {
+        self.xmp = xmp;
+        self
+    }
+
+    /// Embed IPTC records, stored as an `APP13` segment's payload
+    pub fn iptc(mut self, iptc: Option<Vec<u8>>) -> JpegEncoder {
+        self.iptc = iptc;
+        self
+    }
+}
+
+fn color_type(colorspace: ColorSpace) -> Option<ColorType> {
+    match colorspace {
+        ColorSpace::Luma => Some(ColorType::Luma),
+        ColorSpace::RGB => Some(ColorType::Rgb),
+        // the encoder ignores alpha
+        ColorSpace::RGBA => Some(ColorType::Rgba),
+        ColorSpace::YCbCr => Some(ColorType::Ycbcr),
+        ColorSpace::CMYK => Some(ColorType::Cmyk),
+        ColorSpace::YCCK => Some(ColorType::Ycck),
+        _ => None
+    }
+}
+
+impl EncoderTrait for JpegEncoder {
+    fn name(&self) -> &'static str {
+        "jpeg-encoder"
+    }
+
+    fn encode_inner(&mut self, image: &Image) -> Result<Vec<u8>, ImageErrors> {
+        let colorspace = image.colorspace();
+        let color_type = color_type(colorspace).ok_or_else(|| {
+            ImgEncodeErrors::UnsupportedColorspace(colorspace, self.supported_colorspaces())
+        })?;
+
+        let (width, height) = image.dimensions();
+        let max_dims = usize::from(u16::MAX);
+
+        if width > max_dims || height > max_dims {
+            let msg = format!("Too large image dimensions {width} x {height}, maximum is {max_dims} x {max_dims}");
+            return Err(ImgEncodeErrors::ImageEncodeErrors(msg).into());
+        }
+        let pixels = &image.flatten_frames::<u8>()[0];
+
+        let mut encoded = Vec::with_capacity(width * height * colorspace.num_components() / 4);
+        let mut encoder = jpeg_encoder::Encoder::new(&mut encoded, self.options.get_quality());
+        encoder.set_progressive(self.progressive);
+
+        // metadata that doesn't fit a segment is left out rather than failing the save
+        if let Some(exif) = self.exif.as_ref().filter(|exif| exif.len() <= MAX_EXIF_LENGTH) {
+            let segment = [JPEG_EXIF_MARKER, exif].concat();
+            encoder
+                .add_app_segment(1, &segment)
+                .map_err(|e| ImgEncodeErrors::ImageEncodeErrors(e.to_string()))?;
+        }
+        if let Some(xmp) = self.xmp.as_ref().filter(|xmp| xmp.len() <= MAX_XMP_LENGTH) {
+            let segment = [JPEG_XMP_MARKER, xmp].concat();
+            encoder
+                .add_app_segment(1, &segment)
+                .map_err(|e| ImgEncodeErrors::ImageEncodeErrors(e.to_string()))?;
+        }
+        if let Some(iptc) = self.iptc.as_ref().filter(|iptc| iptc.len() <= MAX_APP_SEGMENT) {
+            encoder
+                .add_app_segment(13, iptc)
+                .map_err(|e| ImgEncodeErrors::ImageEncodeErrors(e.to_string()))?;
+        }
+        if let Some(profile) = &self.icc {
+            encoder
+                .add_icc_profile(profile)
+                .map_err(|e| ImgEncodeErrors::ImageEncodeErrors(e.to_string()))?;
+        }
+
+        encoder
+            .encode(pixels, width as u16, height as u16, color_type)
+            .map_err(|e| ImgEncodeErrors::ImageEncodeErrors(e.to_string()))?;
+
+        Ok(encoded)
+    }
+
+    fn supported_colorspaces(&self) -> &'static [ColorSpace] {
+        &[
+            ColorSpace::Luma,
+            ColorSpace::RGB,
+            ColorSpace::RGBA,
+            ColorSpace::YCbCr,
+            ColorSpace::CMYK,
+            ColorSpace::YCCK
+        ]
+    }
+
+    fn format(&self) -> ImageFormat {
+        ImageFormat::JPEG
+    }
+
+    fn supported_bit_depth(&self) -> &'static [BitDepth] {
+        &[BitDepth::Eight]
+    }
+
+    fn default_depth(&self, _: BitDepth) -> BitDepth {
+        BitDepth::Eight
+    }
+
+    fn set_options(&mut self, options: EncoderOptions) {
+        self.options = options;
+    }
+}
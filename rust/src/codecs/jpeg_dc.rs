@@ -0,0 +1,738 @@
+//! Reduced size JPEG decoding for thumbnails
+//!
+//! The DC coefficient of each 8x8 block is that block's average, so
+//! decoding only those gives the image at an eighth of its size, without
+//! the inverse DCT, upsampling, color conversion and full size buffers of
+//! a full decode. The AC coefficients still have to be Huffman decoded to
+//! find where the next block starts, but nothing is done with them.
+//!
+//! Huffman coded sequential and progressive files with one or three
+//! components are supported, progressive files are read up to the end of
+//! their first DC scans. Anything else is left to the full decoder, as are
+//! files whose data ends before the DC coefficients do.
+use zune_core::colorspace::ColorSpace;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::ImageErrors;
+use zune_image::image::Image;
+
+use crate::decoder::DecodeLimits;
+use crate::errors::JniErrors;
+use crate::format::Format;
+
+/// Codes up to this long are decoded with a single table lookup
+const LOOKUP_BITS: u32 = 9;
+
+fn decode_error<S: Into<String>>(msg: S) -> JniErrors {
+    JniErrors::from(ImageErrors::ImageDecodeErrors(msg.into())).with_format(Format::Zune(ImageFormat::JPEG))
+}
+
+fn unsupported(reason: &str) -> JniErrors {
+    JniErrors::Unsupported {
+        reason: reason.to_string(),
+        format: Some(Format::Zune(ImageFormat::JPEG).to_string())
+    }
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Result<u16, JniErrors> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+        .ok_or_else(|| decode_error("Truncated JPEG segment"))
+}
+
+/// A Huffman table
+struct Huffman {
+    /// Largest code of each length, -1 if there are none
+    max_code: [i32; 17],
+    /// Index into `values` of the first code of each length, minus that code
+    offset: [i32; 17],
+    values: Vec<u8>,
+    /// Symbol and length of the codes up to `LOOKUP_BITS` long, indexed
+    /// by the next `LOOKUP_BITS` bits, a zero length marks longer codes
+    lookup: Vec<(u8, u8)>
+}
+
+impl Huffman {
+    /// Build a table from the number of codes of each length and their symbols
+    fn new(counts: &[u8], values: &[u8]) -> Option<Huffman> {
+        let mut max_code = [-1; 17];
+        let mut offset = [0; 17];
+        let mut lookup = vec![(0, 0); 1 << LOOKUP_BITS];
+        let (mut code, mut index) = (0_i32, 0_usize);
+
+        for length in 1..=16 {
+            let count = usize::from(counts[length - 1]);
+            offset[length] = index as i32 - code;
+
+            for _ in 0..count {
+                let symbol = *values.get(index)?;
+                if length <= LOOKUP_BITS as usize {
+                    let shift = LOOKUP_BITS as usize - length;
+                    let start = (code as usize) << shift;
+                    // more codes than fit their length mean a corrupt table
+                    lookup.get_mut(start..start + (1 << shift))?.fill((symbol, length as u8));
+                }
+                code += 1;
+                index += 1;
+            }
+            if count > 0 {
+                max_code[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        Some(Huffman {
+            max_code,
+            offset,
+            values: values[..index].to_vec(),
+            lookup
+        })
+    }
+}
+
+/// Reads the entropy coded data of a scan, removing stuffed zero bytes
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u64,
+    count: u32,
+    /// Zero bits at the end of `buffer` that were read past the end of the data
+    padding: u32
+}
+
+impl<'a> Bits<'a> {
+    fn new(data: &'a [u8], position: usize) -> Bits<'a> {
+        Bits {
+            data,
+            position,
+            buffer: 0,
+            count: 0,
+            padding: 0
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.count <= 56 {
+            let byte = match self.data.get(self.position) {
+                Some(0xFF) if self.data.get(self.position + 1) == Some(&0) => {
+                    self.position += 2;
+                    0xFF
+                }
+                // a marker ends the data, corrupt files read as zeros past it
+                Some(0xFF) | None => {
+                    self.padding += 8;
+                    0
+                }
+                Some(byte) => {
+                    self.position += 1;
+                    *byte
+                }
+            };
+            self.buffer |= u64::from(byte) << (56 - self.count);
+            self.count += 8;
+        }
+    }
+
+    fn peek(&mut self, bits: u32) -> u32 {
+        if self.count < bits {
+            self.fill();
+        }
+        (self.buffer >> (64 - bits)) as u32
+    }
+
+    fn consume(&mut self, bits: u32) {
+        self.buffer <<= bits;
+        self.count -= bits;
+    }
+
+    fn bits(&mut self, bits: u32) -> u32 {
+        if bits == 0 {
+            return 0;
+        }
+        let value = self.peek(bits);
+        self.consume(bits);
+        value
+    }
+
+    fn decode(&mut self, table: &Huffman) -> Result<u8, JniErrors> {
+        let (symbol, length) = table.lookup[self.peek(LOOKUP_BITS) as usize];
+        if length != 0 {
+            self.consume(u32::from(length));
+            return Ok(symbol);
+        }
+        let mut code = 0;
+        for length in 1..=16 {
+            code = (code << 1) | self.bits(1) as i32;
+            if code <= table.max_code[length] {
+                return table
+                    .values
+                    .get((table.offset[length] + code) as usize)
+                    .copied()
+                    .ok_or_else(|| decode_error("Invalid Huffman code"));
+            }
+        }
+        Err(decode_error("Invalid Huffman code"))
+    }
+
+    /// The value of a coefficient of magnitude category `size`
+    fn receive_extend(&mut self, size: u8) -> Result<i32, JniErrors> {
+        if size > 16 {
+            return Err(decode_error(format!("Invalid coefficient size {size}")));
+        }
+        let size = u32::from(size);
+        let value = self.bits(size) as i32;
+        // values below half the range are negative
+        match size != 0 && value < 1 << (size - 1) {
+            true => Ok(value - (1 << size) + 1),
+            false => Ok(value)
+        }
+    }
+
+    /// Fail if the bits decoded so far ran past the end of the data
+    fn check_overrun(&self) -> Result<(), JniErrors> {
+        match self.padding > self.count {
+            true => Err(decode_error("JPEG data ends in the middle of a scan")),
+            false => Ok(())
+        }
+    }
+
+    /// Drop the bits left before a restart marker and skip it
+    fn restart(&mut self) -> Result<(), JniErrors> {
+        self.check_overrun()?;
+        self.buffer = 0;
+        self.count = 0;
+        self.padding = 0;
+        if let [0xFF, 0xD0..=0xD7, ..] = self.data[self.position.min(self.data.len())..] {
+            self.position += 2;
+        }
+        Ok(())
+    }
+}
+
+/// Position of the first marker at or after `position` that isn't a restart marker
+fn next_marker(data: &[u8], mut position: usize) -> usize {
+    while position + 1 < data.len() {
+        match data[position..position + 2] {
+            [0xFF, 0x00 | 0xD0..=0xD7] => position += 2,
+            [0xFF, _] => return position,
+            _ => position += 1
+        }
+    }
+    data.len()
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quant_table: usize,
+    /// Quantized DC coefficient of each block, rows of `stride` blocks
+    dc: Vec<i32>,
+    stride: usize,
+    decoded: bool
+}
+
+struct Frame {
+    width: usize,
+    height: usize,
+    progressive: bool,
+    components: Vec<Component>,
+    h_max: usize,
+    v_max: usize
+}
+
+impl Frame {
+    fn parse(segment: &[u8], progressive: bool, limits: &DecodeLimits) -> Result<Frame, JniErrors> {
+        let [precision, ..] = *segment else {
+            return Err(decode_error("Truncated frame header"));
+        };
+        if precision != 8 {
+            return Err(unsupported("Only 8 bit JPEGs can be decoded at a reduced size"));
+        }
+        let height = usize::from(be_u16(segment, 1)?);
+        let width = usize::from(be_u16(segment, 3)?);
+        if width == 0 || height == 0 {
+            return Err(unsupported("JPEGs declaring their height after the first scan can't be decoded at a reduced size"));
+        }
+        let count = usize::from(*segment.get(5).ok_or_else(|| decode_error("Truncated frame header"))?);
+        if count != 1 && count != 3 {
+            return Err(unsupported("Only JPEGs with one or three components can be decoded at a reduced size"));
+        }
+        let mut components = Vec::with_capacity(count);
+        for spec in segment.get(6..6 + count * 3).ok_or_else(|| decode_error("Truncated frame header"))?.chunks_exact(3) {
+            let (h, v) = (usize::from(spec[1] >> 4), usize::from(spec[1] & 15));
+            if !(1..=4).contains(&h) || !(1..=4).contains(&v) || spec[2] > 3 {
+                return Err(decode_error("Invalid component in frame header"));
+            }
+            components.push(Component {
+                id: spec[0],
+                // a single component is never subsampled
+                h: if count == 1 { 1 } else { h },
+                v: if count == 1 { 1 } else { v },
+                quant_table: usize::from(spec[2]),
+                dc: Vec::new(),
+                stride: 0,
+                decoded: false
+            });
+        }
+        let h_max = components.iter().map(|c| c.h).max().unwrap_or(1);
+        let v_max = components.iter().map(|c| c.v).max().unwrap_or(1);
+        let (mcus_x, mcus_y) = (width.div_ceil(8 * h_max), height.div_ceil(8 * v_max));
+
+        let blocks: usize = components.iter().map(|c| mcus_x * c.h * mcus_y * c.v).sum();
+        limits.check_memory((blocks * std::mem::size_of::<i32>()) as u64)?;
+
+        for component in &mut components {
+            component.stride = mcus_x * component.h;
+            component.dc = vec![0; component.stride * mcus_y * component.v];
+        }
+        Ok(Frame {
+            width,
+            height,
+            progressive,
+            components,
+            h_max,
+            v_max
+        })
+    }
+}
+
+#[derive(Default)]
+struct Tables {
+    dc: [Option<Huffman>; 4],
+    ac: [Option<Huffman>; 4],
+    /// The DC entry of each quantization table
+    quant: [Option<u16>; 4],
+    restart_interval: usize
+}
+
+impl Tables {
+    fn parse_huffman(&mut self, mut segment: &[u8]) -> Result<(), JniErrors> {
+        while let [class_id, ..] = *segment {
+            let counts = segment.get(1..17).ok_or_else(|| decode_error("Truncated Huffman table"))?;
+            let total: usize = counts.iter().map(|count| usize::from(*count)).sum();
+            let values = segment.get(17..17 + total).ok_or_else(|| decode_error("Truncated Huffman table"))?;
+            let table = Huffman::new(counts, values).ok_or_else(|| decode_error("Invalid Huffman table"))?;
+
+            let id = usize::from(class_id & 15);
+            let tables = match class_id >> 4 {
+                0 => &mut self.dc,
+                _ => &mut self.ac
+            };
+            *tables.get_mut(id).ok_or_else(|| decode_error("Invalid Huffman table id"))? = Some(table);
+            segment = &segment[17 + total..];
+        }
+        Ok(())
+    }
+
+    fn parse_quantization(&mut self, mut segment: &[u8]) -> Result<(), JniErrors> {
+        while let [precision_id, ..] = *segment {
+            // 8 or 16 bit entries, the first one scales the DC coefficient
+            let (dc, size) = match precision_id >> 4 {
+                0 => (segment.get(1).map(|value| u16::from(*value)), 65),
+                _ => (be_u16(segment, 1).ok(), 129)
+            };
+            let id = usize::from(precision_id & 15);
+            *self.quant.get_mut(id).ok_or_else(|| decode_error("Invalid quantization table id"))? =
+                Some(dc.ok_or_else(|| decode_error("Truncated quantization table"))?);
+            segment = segment.get(size..).unwrap_or_default();
+        }
+        Ok(())
+    }
+}
+
+/// Decode the DC coefficients of the scan whose header is `header`, the
+/// entropy coded data starts at `start`
+///
+/// Returns where the data ends
+fn decode_scan(data: &[u8], start: usize, header: &[u8], frame: &mut Frame, tables: &Tables) -> Result<usize, JniErrors> {
+    let count = usize::from(*header.first().ok_or_else(|| decode_error("Truncated scan header"))?);
+    let specs = header.get(1..1 + count * 2).ok_or_else(|| decode_error("Truncated scan header"))?;
+    let Some(&[spectral_start, spectral_end, approximation]) = header.get(1 + count * 2..4 + count * 2) else {
+        return Err(decode_error("Truncated scan header"));
+    };
+    // later scans of a progressive file only refine the DC coefficients or add AC ones
+    if frame.progressive && (spectral_start != 0 || approximation >> 4 != 0) {
+        return Ok(next_marker(data, start));
+    }
+    let decode_ac = !frame.progressive && spectral_end != 0;
+    let shift = u32::from(approximation & 15);
+
+    let mut scan = Vec::with_capacity(count);
+    for spec in specs.chunks_exact(2) {
+        let index = frame
+            .components
+            .iter()
+            .position(|component| component.id == spec[0])
+            .ok_or_else(|| decode_error("Scan refers to an unknown component"))?;
+        let dc_table = tables.dc[usize::from(spec[1] >> 4) & 3].as_ref().ok_or_else(|| decode_error("Missing DC Huffman table"))?;
+        let ac_table = match decode_ac {
+            true => Some(tables.ac[usize::from(spec[1] & 15) & 3].as_ref().ok_or_else(|| decode_error("Missing AC Huffman table"))?),
+            false => None
+        };
+        scan.push((index, dc_table, ac_table));
+    }
+    // a lone component is stored block after block rather than in MCUs
+    let (mcus_x, mcus_y) = match scan[..] {
+        [(index, ..)] => {
+            let component = &frame.components[index];
+            (
+                (frame.width * component.h).div_ceil(frame.h_max).div_ceil(8),
+                (frame.height * component.v).div_ceil(frame.v_max).div_ceil(8)
+            )
+        }
+        _ => (frame.width.div_ceil(8 * frame.h_max), frame.height.div_ceil(8 * frame.v_max))
+    };
+    let single = scan.len() == 1;
+
+    let mut bits = Bits::new(data, start);
+    let mut predictors = vec![0_i32; scan.len()];
+    let mut until_restart = tables.restart_interval;
+
+    for mcu_y in 0..mcus_y {
+        for mcu_x in 0..mcus_x {
+            if tables.restart_interval != 0 {
+                if until_restart == 0 {
+                    bits.restart()?;
+                    predictors.fill(0);
+                    until_restart = tables.restart_interval;
+                }
+                until_restart -= 1;
+            }
+            for ((index, dc_table, ac_table), predictor) in scan.iter().zip(&mut predictors) {
+                let component = &mut frame.components[*index];
+                let (blocks_x, blocks_y) = if single { (1, 1) } else { (component.h, component.v) };
+
+                for block_y in 0..blocks_y {
+                    for block_x in 0..blocks_x {
+                        let size = bits.decode(dc_table)?;
+                        *predictor = predictor.wrapping_add(bits.receive_extend(size)?);
+
+                        let (x, y) = (mcu_x * blocks_x + block_x, mcu_y * blocks_y + block_y);
+                        component.dc[y * component.stride + x] = predictor.wrapping_shl(shift);
+
+                        if let Some(ac_table) = ac_table {
+                            // skip over the AC coefficients
+                            let mut k = 1;
+                            while k < 64 {
+                                let symbol = bits.decode(ac_table)?;
+                                let (run, size) = (symbol >> 4, symbol & 15);
+                                if size == 0 && run != 15 {
+                                    break;
+                                }
+                                bits.bits(u32::from(size));
+                                k += usize::from(run) + 1;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    bits.check_overrun()?;
+    for (index, ..) in &scan {
+        frame.components[*index].decoded = true;
+    }
+    Ok(next_marker(data, bits.position))
+}
+
+/// The blocks of a component with `factor` of `max_factor` samples per MCU
+/// that surround the center of output pixel `position`, and the weight of the second
+///
+/// `extent` is the width or height of the image
+fn sample_position(position: usize, factor: usize, max_factor: usize, extent: usize) -> (usize, usize, f32) {
+    let blocks = (extent * factor).div_ceil(max_factor).div_ceil(8);
+    let center = ((position as f32 + 0.5) * factor as f32 / max_factor as f32 - 0.5).max(0.0);
+    let first = (center as usize).min(blocks - 1);
+    (first, (first + 1).min(blocks - 1), center - first as f32)
+}
+
+/// Decode a JPEG at an eighth of its width and height, rounded up
+///
+/// Each pixel is the average of an 8x8 block of the full image
+pub fn decode_jpeg_dc(data: &[u8], limits: &DecodeLimits) -> Result<Image, JniErrors> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return Err(decode_error("Missing start of image marker"));
+    }
+    let mut tables = Tables::default();
+    let mut frame: Option<Frame> = None;
+    // the transform flag of an Adobe segment, 0 marks RGB
+    let mut adobe_transform = None;
+    let mut position = 2;
+
+    loop {
+        let marker = match data.get(position..position + 2) {
+            Some([0xFF, 0xFF]) => {
+                position += 1;
+                continue;
+            }
+            Some([0xFF, marker]) => *marker,
+            _ => return Err(decode_error("Expected a marker")),
+        };
+        // markers without a payload
+        if matches!(marker, 0x01 | 0xD0..=0xD8) {
+            position += 2;
+            continue;
+        }
+        if marker == 0xD9 {
+            break;
+        }
+        let length = usize::from(be_u16(data, position + 2)?);
+        let segment = data
+            .get(position + 4..position + 2 + length.max(2))
+            .ok_or_else(|| decode_error("Truncated JPEG segment"))?;
+
+        match marker {
+            0xC0..=0xC2 => frame = Some(Frame::parse(segment, marker == 0xC2, limits)?),
+            0xC3 | 0xC5..=0xC7 | 0xC9..=0xCB | 0xCD..=0xCF => {
+                return Err(unsupported("Only Huffman coded sequential and progressive JPEGs can be decoded at a reduced size"))
+            }
+            0xC4 => tables.parse_huffman(segment)?,
+            0xDB => tables.parse_quantization(segment)?,
+            0xDD => tables.restart_interval = usize::from(be_u16(segment, 0)?),
+            0xEE if segment.starts_with(b"Adobe") => adobe_transform = segment.get(11).copied(),
+            0xDA => {
+                let frame = frame.as_mut().ok_or_else(|| decode_error("Scan before the frame header"))?;
+                position = decode_scan(data, position + 2 + length, segment, frame, &tables)?;
+
+                if frame.components.iter().all(|component| component.decoded) {
+                    break;
+                }
+                continue;
+            }
+            _ => ()
+        }
+        position += 2 + length;
+    }
+
+    let frame = frame.ok_or_else(|| decode_error("Missing frame header"))?;
+    if !frame.components.iter().all(|component| component.decoded) {
+        return Err(decode_error("JPEG ends before every component was decoded"));
+    }
+    let (width, height) = (frame.width.div_ceil(8), frame.height.div_ceil(8));
+
+    // the average of each block, the DC coefficient is eight times it after level shifting
+    let mut planes = Vec::with_capacity(frame.components.len());
+    for component in &frame.components {
+        let quant = tables.quant[component.quant_table].ok_or_else(|| decode_error("Missing quantization table"))?;
+        let value = |x: usize, y: usize| component.dc[y * component.stride + x] as f32 * f32::from(quant) / 8.0 + 128.0;
+
+        // subsampled blocks are interpolated between their centers, as a full decode upsamples them
+        let columns: Vec<_> = (0..width).map(|x| sample_position(x, component.h, frame.h_max, frame.width)).collect();
+        let mut plane = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let (top, bottom, y_weight) = sample_position(y, component.v, frame.v_max, frame.height);
+            for &(left, right, x_weight) in &columns {
+                let upper = value(left, top) + (value(right, top) - value(left, top)) * x_weight;
+                let lower = value(left, bottom) + (value(right, bottom) - value(left, bottom)) * x_weight;
+                plane.push(upper + (lower - upper) * y_weight);
+            }
+        }
+        planes.push(plane);
+    }
+
+    let to_u8 = |value: f32| value.round().clamp(0.0, 255.0) as u8;
+    let image = match &planes[..] {
+        [luma] => Image::from_u8(&luma.iter().map(|value| to_u8(*value)).collect::<Vec<u8>>(), width, height, ColorSpace::Luma),
+        [first, second, third] => {
+            let ids: Vec<u8> = frame.components.iter().map(|component| component.id).collect();
+            let rgb = adobe_transform == Some(0) || ids == b"RGB";
+
+            let mut pixels = Vec::with_capacity(width * height * 3);
+            for ((y, cb), cr) in first.iter().zip(second).zip(third) {
+                match rgb {
+                    true => pixels.extend([to_u8(*y), to_u8(*cb), to_u8(*cr)]),
+                    false => {
+                        let (cb, cr) = (cb - 128.0, cr - 128.0);
+                        pixels.extend([
+                            to_u8(y + 1.402 * cr),
+                            to_u8(y - 0.344_136 * cb - 0.714_136 * cr),
+                            to_u8(y + 1.772 * cb)
+                        ]);
+                    }
+                }
+            }
+            Image::from_u8(&pixels, width, height, ColorSpace::RGB)
+        }
+        _ => unreachable!("frames have one or three components")
+    };
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use jpeg_encoder::{ColorType, Encoder, SamplingFactor};
+    use zune_core::options::DecoderOptions;
+
+    use super::*;
+
+    const WIDTH: usize = 100;
+    const HEIGHT: usize = 75;
+
+    /// Gradients, so that averaging before or after upsampling and color conversion roughly agrees
+    fn pixels(channels: usize) -> Vec<u8> {
+        let mut pixels = Vec::with_capacity(WIDTH * HEIGHT * channels);
+        for y in 0..HEIGHT {
+            for x in 0..WIDTH {
+                let wave = 128.0 + 60.0 * ((x + y) as f32 / 40.0).sin();
+                pixels.extend([(x * 2) as u8, (y * 3) as u8, wave as u8].iter().take(channels));
+            }
+        }
+        pixels
+    }
+
+    fn encode(color: ColorType, sampling: SamplingFactor, progressive: bool, restart_interval: u16) -> Vec<u8> {
+        let channels = if color == ColorType::Luma { 1 } else { 3 };
+        let mut encoded = Vec::new();
+        let mut encoder = Encoder::new(&mut encoded, 90);
+        encoder.set_sampling_factor(sampling);
+        encoder.set_progressive(progressive);
+        encoder.set_restart_interval(restart_interval);
+        encoder.encode(&pixels(channels), WIDTH as u16, HEIGHT as u16, color).unwrap();
+        encoded
+    }
+
+    /// Decode `data` in full and average each 8x8 block, returns the pixels and their channels
+    fn shrunk_full_decode(data: &[u8]) -> (Vec<u8>, usize) {
+        let image = Image::read(data, DecoderOptions::default()).unwrap();
+        let channels = image.colorspace().num_components();
+        let full = image.flatten_frames::<u8>().swap_remove(0);
+
+        let mut shrunk = Vec::new();
+        for y in (0..HEIGHT).step_by(8) {
+            for x in (0..WIDTH).step_by(8) {
+                for channel in 0..channels {
+                    let (mut sum, mut count) = (0, 0);
+                    for full_y in y..(y + 8).min(HEIGHT) {
+                        for full_x in x..(x + 8).min(WIDTH) {
+                            sum += u32::from(full[(full_y * WIDTH + full_x) * channels + channel]);
+                            count += 1;
+                        }
+                    }
+                    shrunk.push((sum as f32 / count as f32).round() as u8);
+                }
+            }
+        }
+        (shrunk, channels)
+    }
+
+    fn assert_matches_full_decode(data: &[u8]) {
+        let image = decode_jpeg_dc(data, &DecodeLimits::default()).unwrap();
+        assert_eq!(image.dimensions(), (WIDTH.div_ceil(8), HEIGHT.div_ceil(8)));
+
+        let (expected, channels) = shrunk_full_decode(data);
+        assert_eq!(image.colorspace().num_components(), channels);
+        let pixels = image.flatten_frames::<u8>().swap_remove(0);
+        assert_eq!(pixels.len(), expected.len());
+
+        let errors: Vec<u32> = pixels.iter().zip(&expected).map(|(a, b)| u32::from(a.abs_diff(*b))).collect();
+        let max = errors.iter().copied().max().unwrap_or_default();
+        let mean = errors.iter().sum::<u32>() as f32 / errors.len() as f32;
+        // subsampled chroma is interpolated from fewer samples than a full decode has
+        assert!(max <= 16 && mean < 2.5, "differs from a full decode by up to {max}, {mean} on average");
+    }
+
+    /// Offset of the first `marker` segment
+    fn find_marker(data: &[u8], marker: u8) -> usize {
+        data.windows(2).position(|window| window == [0xFF, marker]).unwrap()
+    }
+
+    #[test]
+    fn matches_full_decode_of_444() {
+        assert_matches_full_decode(&encode(ColorType::Rgb, SamplingFactor::R_4_4_4, false, 0));
+    }
+
+    #[test]
+    fn matches_full_decode_of_420() {
+        assert_matches_full_decode(&encode(ColorType::Rgb, SamplingFactor::R_4_2_0, false, 0));
+    }
+
+    #[test]
+    fn matches_full_decode_with_restart_intervals() {
+        assert_matches_full_decode(&encode(ColorType::Rgb, SamplingFactor::R_4_2_0, false, 3));
+        assert_matches_full_decode(&encode(ColorType::Rgb, SamplingFactor::R_4_4_4, false, 1));
+        assert_matches_full_decode(&encode(ColorType::Luma, SamplingFactor::R_4_4_4, false, 5));
+    }
+
+    #[test]
+    fn matches_full_decode_of_grayscale() {
+        let data = encode(ColorType::Luma, SamplingFactor::R_4_4_4, false, 0);
+        assert_eq!(decode_jpeg_dc(&data, &DecodeLimits::default()).unwrap().colorspace(), ColorSpace::Luma);
+        assert_matches_full_decode(&data);
+    }
+
+    #[test]
+    fn matches_full_decode_of_progressive() {
+        // only the first DC scans are read, the AC scans after them are skipped
+        assert_matches_full_decode(&encode(ColorType::Rgb, SamplingFactor::R_4_2_0, true, 0));
+        assert_matches_full_decode(&encode(ColorType::Rgb, SamplingFactor::R_4_4_4, true, 0));
+        assert_matches_full_decode(&encode(ColorType::Luma, SamplingFactor::R_4_4_4, true, 0));
+    }
+
+    #[test]
+    fn leaves_other_processes_to_the_full_decoder() {
+        let data = encode(ColorType::Rgb, SamplingFactor::R_4_2_0, false, 0);
+        let frame = find_marker(&data, 0xC0);
+
+        // lossless, arithmetic coded and 12 bit files fall back to a full decode
+        for (offset, byte) in [(frame + 1, 0xC3), (frame + 1, 0xC9), (frame + 4, 12)] {
+            let mut changed = data.clone();
+            changed[offset] = byte;
+            let result = decode_jpeg_dc(&changed, &DecodeLimits::default());
+            assert!(matches!(result, Err(JniErrors::Unsupported { .. })), "{:?}", result.err());
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_files() {
+        let baseline = encode(ColorType::Rgb, SamplingFactor::R_4_2_0, false, 3);
+        // the end of image marker itself isn't needed
+        for length in 0..baseline.len() - 2 {
+            assert!(decode_jpeg_dc(&baseline[..length], &DecodeLimits::default()).is_err(), "accepted {length} bytes");
+        }
+
+        // progressive files only need their DC scans, those whose spectral selection starts at 0
+        let progressive = encode(ColorType::Rgb, SamplingFactor::R_4_2_0, true, 0);
+        let last_dc_scan = (0..progressive.len() - 1)
+            .filter(|&offset| progressive[offset..offset + 2] == [0xFF, 0xDA])
+            .rfind(|&scan| progressive[scan + 5 + usize::from(progressive[scan + 4]) * 2] == 0)
+            .unwrap();
+        let dc_end = next_marker(&progressive, last_dc_scan + 2);
+        for length in 0..dc_end {
+            assert!(decode_jpeg_dc(&progressive[..length], &DecodeLimits::default()).is_err(), "accepted {length} bytes");
+        }
+        assert!(decode_jpeg_dc(&progressive[..dc_end], &DecodeLimits::default()).is_ok());
+    }
+
+    #[test]
+    fn rejects_corrupt_files() {
+        let data = encode(ColorType::Rgb, SamplingFactor::R_4_2_0, false, 3);
+        assert!(decode_jpeg_dc(b"not a jpeg", &DecodeLimits::default()).is_err());
+
+        // a scan naming a component the frame doesn't have
+        let mut changed = data.clone();
+        changed[find_marker(&data, 0xDA) + 5] = 9;
+        assert!(decode_jpeg_dc(&changed, &DecodeLimits::default()).is_err());
+
+        // more one bit codes than there are one bit codes
+        let mut changed = data.clone();
+        changed[find_marker(&data, 0xC4) + 5] = 3;
+        assert!(decode_jpeg_dc(&changed, &DecodeLimits::default()).is_err());
+
+        let limits = DecodeLimits {
+            memory_budget: 64,
+            ..DecodeLimits::default()
+        };
+        assert!(matches!(decode_jpeg_dc(&data, &limits), Err(JniErrors::LimitExceeded { .. })));
+
+        // whatever a damaged byte decodes to, it mustn't panic
+        for progressive in [false, true] {
+            let data = encode(ColorType::Rgb, SamplingFactor::R_4_2_0, progressive, 2);
+            for offset in 0..data.len() {
+                for flip in [0x01, 0x80, 0xFF] {
+                    let mut changed = data.clone();
+                    changed[offset] ^= flip;
+                    let _ = decode_jpeg_dc(&changed, &DecodeLimits::default());
+                }
+            }
+        }
+    }
+}
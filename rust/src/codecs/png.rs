@@ -0,0 +1,241 @@
+//! PNG encoding via the `png` crate
+//!
+//! zune-png's encoder has no knobs for compression or filtering,
+//! this one exposes both. Images with several frames are written as
+//! animated PNGs, each frame storing only the region that changed.
+use std::borrow::Cow;
+
+use png::text_metadata::ITXtChunk;
+use png::{BitDepth as PngDepth, BlendOp, ColorType, DeflateCompression, DisposeOp, Filter, Info};
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::{ImageErrors, ImgEncodeErrors};
+use zune_image::image::Image;
+use zune_image::traits::EncoderTrait;
+
+use crate::animation::{changed_region, merge_identical, Animation, Region};
+use crate::xmp::PNG_XMP_KEYWORD;
+
+/// Row filter applied before compression
+///
+/// Discriminants match the order of `ZilPngFilter` in Kotlin
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum PngFilter {
+    /// Pick the best filter for each row
+    #[default]
+    Adaptive,
+    NoFilter,
+    Sub,
+    Up,
+    Avg,
+    Paeth
+}
+
+impl PngFilter {
+    pub fn from_ordinal(ordinal: i32) -> Option<PngFilter> {
+        match ordinal {
+            0 => Some(PngFilter::Adaptive),
+            1 => Some(PngFilter::NoFilter),
+            2 => Some(PngFilter::Sub),
+            3 => Some(PngFilter::Up),
+            4 => Some(PngFilter::Avg),
+            5 => Some(PngFilter::Paeth),
+            _ => None
+        }
+    }
+
+    fn to_png(self) -> Filter {
+        match self {
+            PngFilter::Adaptive => Filter::Adaptive,
+            PngFilter::NoFilter => Filter::NoFilter,
+            PngFilter::Sub => Filter::Sub,
+            PngFilter::Up => Filter::Up,
+            PngFilter::Avg => Filter::Avg,
+            PngFilter::Paeth => Filter::Paeth
+        }
+    }
+}
+
+pub struct PngEncoder {
+    /// zlib level, 0 stores the data uncompressed
+    compression: u8,
+    filter: PngFilter,
+    /// Frame delays and loop count, used when the image has several frames
+    animation: Animation,
+    icc: Option<Vec<u8>>,
+    exif: Option<Vec<u8>>,
+    xmp: Option<Vec<u8>>
+}
+
+impl PngEncoder {
+    pub fn new(compression: u8, filter: PngFilter) -> PngEncoder {
+        PngEncoder {
+            compression: compression.min(9),
+            filter,
+            animation: Animation::default(),
+            icc: None,
+            exif: None,
+            xmp: None
+        }
+    }
+
+    /// Write every frame of the image, timed by `animation`
+    pub fn animated(mut self, animation: Animation) -> PngEncoder {
+        self.animation = animation;
+        self
+    }
+
+    /// Embed an ICC profile describing the pixels
+    pub fn icc_profile(mut self, profile: Option<Vec<u8>>) -> PngEncoder {
+        self.icc = profile;
+        self
+    }
+
+    /// Embed EXIF, stored as a TIFF structure
+    pub fn exif(mut self, exif: Option<Vec<u8>>) -> PngEncoder {
+        self.exif = exif;
+        self
+    }
+
+    /// Embed an XMP packet, stored uncompressed so other tools can find it
+    pub fn xmp(mut self, xmp: Option<Vec<u8>>) -> PngEncoder {
+        self.xmp = xmp;
+        self
+    }
+}
+
+impl EncoderTrait for PngEncoder {
+    fn name(&self) -> &'static str {
+        "png"
+    }
+
+    fn encode_inner(&mut self, image: &Image) -> Result<Vec<u8>, ImageErrors> {
+        let colorspace = image.colorspace();
+        let color_type = match colorspace {
+            ColorSpace::Luma => ColorType::Grayscale,
+            ColorSpace::LumaA => ColorType::GrayscaleAlpha,
+            ColorSpace::RGB => ColorType::Rgb,
+            ColorSpace::RGBA => ColorType::Rgba,
+            _ => {
+                return Err(ImgEncodeErrors::UnsupportedColorspace(colorspace, self.supported_colorspaces()).into())
+            }
+        };
+        let (width, height) = image.dimensions();
+        let (width, height) = match (u32::try_from(width), u32::try_from(height)) {
+            (Ok(w), Ok(h)) => (w, h),
+            _ => {
+                let msg = format!("Too large image dimensions {width} x {height}");
+                return Err(ImgEncodeErrors::ImageEncodeErrors(msg).into());
+            }
+        };
+
+        // png stores 16 bit samples big endian
+        let (depth, frames) = match image.depth() {
+            BitDepth::Sixteen => {
+                let frames = image.flatten_frames::<u16>();
+                let bytes = frames
+                    .iter()
+                    .map(|pixels| pixels.iter().flat_map(|x| x.to_be_bytes()).collect())
+                    .collect();
+                (PngDepth::Sixteen, bytes)
+            }
+            _ => (PngDepth::Eight, image.flatten_frames::<u8>())
+        };
+
+        let to_err = |e: png::EncodingError| ImgEncodeErrors::ImageEncodeErrors(e.to_string());
+
+        let mut info = Info::with_size(width, height);
+        info.icc_profile = self.icc.as_deref().map(Cow::Borrowed);
+        info.exif_metadata = self.exif.as_deref().map(Cow::Borrowed);
+        if let Some(xmp) = &self.xmp {
+            info.utf8_text.push(ITXtChunk::new(PNG_XMP_KEYWORD, String::from_utf8_lossy(xmp)));
+        }
+
+        let mut encoded = Vec::new();
+        let mut encoder = png::Encoder::with_info(&mut encoded, info).map_err(to_err)?;
+        encoder.set_color(color_type);
+        encoder.set_depth(depth);
+        encoder.set_deflate_compression(match self.compression {
+            0 => DeflateCompression::NoCompression,
+            level => DeflateCompression::Level(level)
+        });
+        encoder.set_filter(self.filter.to_png());
+
+        let kept = merge_identical(&frames, &self.animation);
+
+        if kept.len() > 1 {
+            encoder.set_animated(kept.len() as u32, self.animation.loop_count).map_err(to_err)?;
+        }
+        let mut writer = encoder.write_header().map_err(to_err)?;
+
+        if kept.len() == 1 {
+            writer.write_image_data(&frames[0]).map_err(to_err)?;
+            writer.finish().map_err(to_err)?;
+            return Ok(encoded);
+        }
+        let (width, height) = (width as usize, height as usize);
+        let pixel_size = color_type.samples() * if depth == PngDepth::Sixteen { 2 } else { 1 };
+
+        for (position, (index, delay)) in kept.iter().enumerate() {
+            let pixels = &frames[*index];
+            // the first frame is also the default image and has to cover it
+            let region = match position {
+                0 => Region::full(width, height),
+                _ => changed_region(&frames[kept[position - 1].0], pixels, width, pixel_size)
+                    .unwrap_or(Region::full(width, height))
+            };
+            // fall back to hundredths of a second for delays over a minute
+            let (numerator, denominator) = match u16::try_from(*delay) {
+                Ok(delay) => (delay, 1000),
+                Err(_) => (u16::try_from(delay / 10).unwrap_or(u16::MAX), 100)
+            };
+            writer.set_frame_delay(numerator, denominator).map_err(to_err)?;
+            // the region replaces what was there, transparent pixels included,
+            // and stays for the next frame to draw over
+            writer.set_blend_op(BlendOp::Source).map_err(to_err)?;
+            writer.set_dispose_op(DisposeOp::None).map_err(to_err)?;
+            writer.reset_frame_position().map_err(to_err)?;
+            writer.set_frame_dimension(region.width as u32, region.height as u32).map_err(to_err)?;
+            writer.set_frame_position(region.left as u32, region.top as u32).map_err(to_err)?;
+
+            writer.write_image_data(&region.crop(pixels, width, pixel_size)).map_err(to_err)?;
+        }
+        writer.finish().map_err(to_err)?;
+
+        Ok(encoded)
+    }
+
+    fn supported_colorspaces(&self) -> &'static [ColorSpace] {
+        &[
+            ColorSpace::Luma,
+            ColorSpace::LumaA,
+            ColorSpace::RGB,
+            ColorSpace::RGBA
+        ]
+    }
+
+    fn format(&self) -> ImageFormat {
+        ImageFormat::PNG
+    }
+
+    fn supported_bit_depth(&self) -> &'static [BitDepth] {
+        &[BitDepth::Eight, BitDepth::Sixteen]
+    }
+
+    fn default_depth(&self, depth: BitDepth) -> BitDepth {
+        match depth {
+            BitDepth::Float32 | BitDepth::Sixteen => BitDepth::Sixteen,
+            _ => BitDepth::Eight
+        }
+    }
+
+    fn default_colorspace(&self, colorspace: ColorSpace) -> ColorSpace {
+        // don't lose transparency when converting e.g. BGRA or ARGB
+        if colorspace.has_alpha() {
+            ColorSpace::RGBA
+        } else {
+            ColorSpace::RGB
+        }
+    }
+}
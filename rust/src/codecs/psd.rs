@@ -0,0 +1,596 @@
+//! Photoshop layer extraction
+//!
+//! zune only decodes the flattened composite stored at the end of a PSD,
+//! the layers themselves live in the layer and mask information section
+//! which is parsed here.
+//!
+//! Layers are listed in the order Photoshop's layers panel shows them,
+//! top-most first. Group markers are not layers of their own, instead
+//! each layer carries the path of the groups it is nested in.
+//!
+//! A layer decodes to an image covering its bounds, which may extend past
+//! the canvas. Its opacity, blend mode and masks are reported rather than
+//! applied, so the pixels are exactly the ones Photoshop stored.
+use jni::objects::{JClass, JObject, JString, JValue};
+use jni::sys::{jint, jobjectArray, jsize};
+use jni::JNIEnv;
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::ImageErrors;
+use zune_image::image::Image;
+use zune_inflate::{DeflateDecoder, DeflateOptions};
+
+use crate::decoder::DecodeLimits;
+use crate::errors::{jni_guard, JniErrors};
+use crate::format::Format;
+use crate::get_string;
+use crate::probe::ImageInfo;
+
+const PSD_LAYER_CLASS: &str = "ZilPsdLayer";
+
+/// Color modes from the file header
+const MODE_GRAYSCALE: u16 = 1;
+const MODE_RGB: u16 = 3;
+
+/// Channel id of the transparency mask
+const CHANNEL_ALPHA: i16 = -1;
+
+/// Additional layer information keys whose length is 8 bytes in PSB files
+const LONG_KEYS: [&[u8; 4]; 13] = [
+    b"LMsk", b"Lr16", b"Lr32", b"Layr", b"Mt16", b"Mt32", b"Mtrn", b"Alph", b"FMsk", b"lnk2", b"FEid", b"FXid",
+    b"PxSD"
+];
+
+/// Properties of a single layer
+#[derive(Debug, Clone)]
+pub struct PsdLayer {
+    pub name: String,
+    /// Names of the groups the layer is nested in, outermost first, joined by `/`
+    pub group: String,
+    /// Position of the layer's top left corner on the canvas, may be negative
+    pub left: i32,
+    pub top: i32,
+    pub width: usize,
+    pub height: usize,
+    /// 0 is fully transparent, 255 is opaque
+    pub opacity: u8,
+    /// Blend mode, e.g. `normal` or `multiply`
+    pub blend_mode: String,
+    pub visible: bool
+}
+
+fn decode_error<S: Into<String>>(msg: S) -> JniErrors {
+    JniErrors::from(ImageErrors::ImageDecodeErrors(msg.into())).with_format(ImageFormat::PSD)
+}
+
+fn unsupported(reason: String) -> JniErrors {
+    JniErrors::Unsupported {
+        reason,
+        format: Some(Format::from(ImageFormat::PSD).to_string())
+    }
+}
+
+/// Big endian reader over a section of the file
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data, position: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], JniErrors> {
+        if length > self.remaining() {
+            return Err(decode_error("Truncated PSD file"));
+        }
+        let bytes = &self.data[self.position..self.position + length];
+        self.position += length;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], JniErrors> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, JniErrors> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, JniErrors> {
+        Ok(u16::from_be_bytes(self.array()?))
+    }
+
+    fn i16(&mut self) -> Result<i16, JniErrors> {
+        Ok(i16::from_be_bytes(self.array()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, JniErrors> {
+        Ok(u32::from_be_bytes(self.array()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, JniErrors> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+
+    /// A section length, 8 bytes wide in PSB files when `long` is set
+    fn length(&mut self, long: bool) -> Result<usize, JniErrors> {
+        let length = if long { u64::from_be_bytes(self.array()?) } else { u64::from(self.u32()?) };
+        usize::try_from(length).map_err(|_| decode_error("PSD section too large"))
+    }
+
+    /// A section prefixed with its length
+    fn section(&mut self, long: bool) -> Result<&'a [u8], JniErrors> {
+        let length = self.length(long)?;
+        self.bytes(length)
+    }
+}
+
+struct Header {
+    /// Large document format, which widens some lengths to 8 bytes
+    psb: bool,
+    depth: u16,
+    mode: u16
+}
+
+/// Where a layer channel's data lies in the file
+struct Channel {
+    id: i16,
+    data: std::ops::Range<usize>
+}
+
+/// A layer record, including group markers
+struct Record {
+    layer: PsdLayer,
+    /// Section divider type, 1 and 2 open a group, 3 closes one
+    divider: u32,
+    channels: Vec<Channel>
+}
+
+fn blend_mode_name(key: &[u8; 4]) -> String {
+    let name = match key {
+        b"pass" => "passThrough",
+        b"norm" => "normal",
+        b"diss" => "dissolve",
+        b"dark" => "darken",
+        b"mul " => "multiply",
+        b"idiv" => "colorBurn",
+        b"lbrn" => "linearBurn",
+        b"dkCl" => "darkerColor",
+        b"lite" => "lighten",
+        b"scrn" => "screen",
+        b"div " => "colorDodge",
+        b"lddg" => "linearDodge",
+        b"lgCl" => "lighterColor",
+        b"over" => "overlay",
+        b"sLit" => "softLight",
+        b"hLit" => "hardLight",
+        b"vLit" => "vividLight",
+        b"lLit" => "linearLight",
+        b"pLit" => "pinLight",
+        b"hMix" => "hardMix",
+        b"diff" => "difference",
+        b"smud" => "exclusion",
+        b"fsub" => "subtract",
+        b"fdiv" => "divide",
+        b"hue " => "hue",
+        b"sat " => "saturation",
+        b"colr" => "color",
+        b"lum " => "luminosity",
+        _ => return String::from_utf8_lossy(key).trim().to_string()
+    };
+    name.to_string()
+}
+
+/// Parse one layer record, returning it along with the id and length of each of its channels
+fn read_record(reader: &mut Reader, header: &Header) -> Result<(Record, Vec<(i16, usize)>), JniErrors> {
+    let top = reader.i32()?;
+    let left = reader.i32()?;
+    let bottom = reader.i32()?;
+    let right = reader.i32()?;
+
+    let mut channels = Vec::new();
+    for _ in 0..reader.u16()? {
+        let id = reader.i16()?;
+        let length = reader.length(header.psb)?;
+        channels.push((id, length));
+    }
+    if &reader.array::<4>()? != b"8BIM" {
+        return Err(decode_error("Invalid PSD layer record signature"));
+    }
+    let blend_mode = blend_mode_name(&reader.array()?);
+    let opacity = reader.u8()?;
+    let _clipping = reader.u8()?;
+    let flags = reader.u8()?;
+    let _filler = reader.u8()?;
+
+    let mut extra = Reader::new(reader.section(false)?);
+    let _mask = extra.section(false)?;
+    let _blending_ranges = extra.section(false)?;
+
+    // pascal string padded to a multiple of 4 bytes, including the length byte
+    let name_length = usize::from(extra.u8()?);
+    let mut name: String = extra.bytes(name_length)?.iter().map(|b| char::from(*b)).collect();
+    extra.bytes((4 - (name_length + 1) % 4) % 4)?;
+
+    let mut divider = 0;
+    while extra.remaining() >= 12 {
+        let signature = extra.array::<4>()?;
+        if &signature != b"8BIM" && &signature != b"8B64" {
+            break;
+        }
+        let key = extra.array::<4>()?;
+        let mut data = Reader::new(extra.section(header.psb && LONG_KEYS.contains(&&key))?);
+
+        match &key {
+            // the unicode name, the pascal one is truncated and in the system encoding
+            b"luni" => {
+                let length = data.u32()? as usize;
+                let units: Vec<u16> = (0..length).map(|_| data.u16()).collect::<Result<_, _>>()?;
+                name = String::from_utf16_lossy(&units).trim_end_matches('\0').to_string();
+            }
+            b"lsct" | b"lsdk" => divider = data.u32()?,
+            _ => ()
+        }
+    }
+
+    let layer = PsdLayer {
+        name,
+        group: String::new(),
+        left,
+        top,
+        width: right.saturating_sub(left).max(0) as usize,
+        height: bottom.saturating_sub(top).max(0) as usize,
+        opacity,
+        blend_mode,
+        // bit 1 is set for hidden layers
+        visible: flags & 2 == 0
+    };
+    let record = Record {
+        layer,
+        divider,
+        channels: Vec::new()
+    };
+    Ok((record, channels))
+}
+
+/// Parse the layer info section, which starts at `offset` in the file
+fn read_layer_info(info: &[u8], offset: usize, header: &Header) -> Result<Vec<Record>, JniErrors> {
+    let mut reader = Reader::new(info);
+
+    // a negative count means the first alpha channel holds the composite's transparency
+    let count = reader.i16()?.unsigned_abs();
+
+    let mut records = Vec::with_capacity(usize::from(count));
+    let mut lengths = Vec::with_capacity(usize::from(count));
+    for _ in 0..count {
+        let (record, channels) = read_record(&mut reader, header)?;
+        records.push(record);
+        lengths.push(channels);
+    }
+    // channel data follows all the records, in the same order
+    for (record, channels) in records.iter_mut().zip(lengths) {
+        for (id, length) in channels {
+            let start = offset + reader.position;
+            reader.bytes(length)?;
+            record.channels.push(Channel {
+                id,
+                data: start..start + length
+            });
+        }
+    }
+    Ok(records)
+}
+
+fn read_header(reader: &mut Reader) -> Result<Header, JniErrors> {
+    if &reader.array::<4>()? != b"8BPS" {
+        return Err(decode_error("Not a PSD file"));
+    }
+    let psb = match reader.u16()? {
+        1 => false,
+        2 => true,
+        version => return Err(unsupported(format!("Unknown PSD version {version}")))
+    };
+    reader.bytes(6)?;
+    let _channels = reader.u16()?;
+    let _height = reader.u32()?;
+    let _width = reader.u32()?;
+    let depth = reader.u16()?;
+    let mode = reader.u16()?;
+
+    Ok(Header { psb, depth, mode })
+}
+
+/// Parse the headers and every layer record, in file order (bottom-most first)
+fn read_records(data: &[u8]) -> Result<(Header, Vec<Record>), JniErrors> {
+    let mut reader = Reader::new(data);
+    let header = read_header(&mut reader)?;
+
+    let _color_mode_data = reader.section(false)?;
+    let _image_resources = reader.section(false)?;
+
+    let section_start = reader.position + if header.psb { 8 } else { 4 };
+    let mut section = Reader::new(reader.section(header.psb)?);
+
+    let info_start = section_start + section.position + if header.psb { 8 } else { 4 };
+    let info = section.section(header.psb)?;
+    if !info.is_empty() {
+        let records = read_layer_info(info, info_start, &header)?;
+        return Ok((header, records));
+    }
+    // 16 and 32 bit files store their layers in a block following the global mask
+    let _global_mask = section.section(false)?;
+
+    while section.remaining() >= 12 {
+        let signature = section.array::<4>()?;
+        if &signature != b"8BIM" && &signature != b"8B64" {
+            break;
+        }
+        let key = section.array::<4>()?;
+        let long = header.psb && LONG_KEYS.contains(&&key);
+        let block_start = section_start + section.position + if long { 8 } else { 4 };
+        let block = section.section(long)?;
+
+        if matches!(&key, b"Layr" | b"Lr16" | b"Lr32") {
+            let records = read_layer_info(block, block_start, &header)?;
+            return Ok((header, records));
+        }
+        // blocks are padded to a multiple of 4 bytes
+        section.bytes(((4 - block.len() % 4) % 4).min(section.remaining()))?;
+    }
+    Ok((header, Vec::new()))
+}
+
+/// Pixel layers in layers panel order, with the channels holding their pixels
+fn read_layers(data: &[u8]) -> Result<(Header, Vec<Record>), JniErrors> {
+    let (header, records) = read_records(data)?;
+
+    let mut groups: Vec<String> = Vec::new();
+    let mut layers = Vec::new();
+
+    // walking top down, a group's folder record comes before its contents
+    // and the bounding divider after them
+    for mut record in records.into_iter().rev() {
+        match record.divider {
+            1 | 2 => groups.push(record.layer.name),
+            3 => {
+                groups.pop();
+            }
+            _ => {
+                record.layer.group = groups.join("/");
+                layers.push(record);
+            }
+        }
+    }
+    Ok((header, layers))
+}
+
+/// List the layers of a PSD, top-most first
+pub fn psd_layers(data: &[u8]) -> Result<Vec<PsdLayer>, JniErrors> {
+    Ok(read_layers(data)?.1.into_iter().map(|r| r.layer).collect())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_psdLayersNative(mut env: JNIEnv, _class: JClass, filename: JString) -> jobjectArray {
+    jni_guard(&mut env, "psdLayersNative", |env| {
+        let path = get_string(env, &filename)?;
+        let data = std::fs::read(&path).map_err(|e| JniErrors::from(e).with_path(&path))?;
+        let layers = psd_layers(&data).map_err(|e| e.with_path(&path))?;
+
+        let array = env.new_object_array(layers.len() as jsize, PSD_LAYER_CLASS, JObject::null())?;
+        for (index, layer) in layers.iter().enumerate() {
+            let name = JObject::from(env.new_string(&layer.name)?);
+            let group = JObject::from(env.new_string(&layer.group)?);
+            let blend_mode = JObject::from(env.new_string(&layer.blend_mode)?);
+
+            let object = env.new_object(
+                PSD_LAYER_CLASS,
+                "(Ljava/lang/String;Ljava/lang/String;IIJJILjava/lang/String;Z)V",
+                &[
+                    JValue::Object(&name),
+                    JValue::Object(&group),
+                    JValue::Int(layer.left),
+                    JValue::Int(layer.top),
+                    JValue::Long(layer.width as _),
+                    JValue::Long(layer.height as _),
+                    JValue::Int(jint::from(layer.opacity)),
+                    JValue::Object(&blend_mode),
+                    JValue::Bool(layer.visible.into())
+                ]
+            )?;
+            env.set_object_array_element(&array, index as jsize, object)?;
+        }
+        Ok(array)
+    })
+    .into_raw()
+}
+
+/// Undo PackBits compression of a single row
+fn unpack_bits(packed: &[u8], row: &mut [u8]) -> Result<(), JniErrors> {
+    let mut reader = Reader::new(packed);
+    let mut written = 0;
+
+    while written < row.len() && reader.remaining() > 0 {
+        let header = reader.u8()? as i8;
+        match header {
+            -128 => (),
+            0.. => {
+                let length = header as usize + 1;
+                let bytes = reader.bytes(length)?;
+                let end = (written + length).min(row.len());
+                row[written..end].copy_from_slice(&bytes[..end - written]);
+                written = end;
+            }
+            _ => {
+                let length = 1 - header as isize;
+                let value = reader.u8()?;
+                let end = (written + length as usize).min(row.len());
+                row[written..end].fill(value);
+                written = end;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Undo the per row delta encoding of ZIP with prediction
+fn unpredict(plane: &mut [u8], width: usize, bytes_per_sample: usize) {
+    let row_bytes = width * bytes_per_sample;
+
+    for row in plane.chunks_exact_mut(row_bytes) {
+        match bytes_per_sample {
+            1 => {
+                for x in 1..row.len() {
+                    row[x] = row[x].wrapping_add(row[x - 1]);
+                }
+            }
+            2 => {
+                for x in 1..width {
+                    let previous = u16::from_be_bytes([row[x * 2 - 2], row[x * 2 - 1]]);
+                    let current = u16::from_be_bytes([row[x * 2], row[x * 2 + 1]]);
+                    row[x * 2..x * 2 + 2].copy_from_slice(&current.wrapping_add(previous).to_be_bytes());
+                }
+            }
+            // floats are split into byte planes, the delta runs across the whole row
+            _ => {
+                for x in 1..row.len() {
+                    row[x] = row[x].wrapping_add(row[x - 1]);
+                }
+                let planes = row.to_vec();
+                for x in 0..width {
+                    for b in 0..4 {
+                        row[x * 4 + b] = planes[b * width + x];
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Decompress a channel into a plane of big endian samples
+fn decode_channel(data: &[u8], width: usize, height: usize, bytes_per_sample: usize, psb: bool) -> Result<Vec<u8>, JniErrors> {
+    let mut reader = Reader::new(data);
+    let row_bytes = width * bytes_per_sample;
+    let mut plane = vec![0; row_bytes * height];
+
+    match reader.u16()? {
+        0 => plane.copy_from_slice(reader.bytes(row_bytes * height)?),
+        1 => {
+            let counts: Vec<usize> = (0..height)
+                .map(|_| if psb { reader.u32().map(|c| c as usize) } else { reader.u16().map(usize::from) })
+                .collect::<Result<_, _>>()?;
+
+            for (row, count) in plane.chunks_exact_mut(row_bytes).zip(counts) {
+                unpack_bits(reader.bytes(count)?, row)?;
+            }
+        }
+        compression @ (2 | 3) => {
+            // only the plane is used, a stream inflating past it is refused rather than held in memory
+            let options = DeflateOptions::default()
+                .set_limit(row_bytes * height)
+                .set_size_hint(row_bytes * height);
+            let inflated = DeflateDecoder::new_with_options(reader.bytes(reader.remaining())?, options)
+                .decode_zlib()
+                .map_err(|e| decode_error(format!("{e:?}")))?;
+            if inflated.len() != row_bytes * height {
+                return Err(decode_error(format!(
+                    "PSD channel data inflates to {} bytes rather than {}",
+                    inflated.len(),
+                    row_bytes * height
+                )));
+            }
+            plane.copy_from_slice(&inflated);
+
+            if compression == 3 {
+                unpredict(&mut plane, width, bytes_per_sample);
+            }
+        }
+        compression => return Err(unsupported(format!("Unknown PSD channel compression {compression}")))
+    }
+    Ok(plane)
+}
+
+/// Decode the layer at `index` in [`psd_layers`] to an RGBA or LumaA image covering its bounds
+pub fn decode_psd_layer(data: &[u8], index: usize, limits: &DecodeLimits) -> Result<Image, JniErrors> {
+    let (header, mut layers) = read_layers(data)?;
+
+    if index >= layers.len() {
+        return Err(JniErrors::InvalidArgument(format!(
+            "Layer index {index} out of range, the PSD has {} layers",
+            layers.len()
+        )));
+    }
+    let record = layers.swap_remove(index);
+    let (width, height) = (record.layer.width, record.layer.height);
+
+    if width == 0 || height == 0 {
+        return Err(JniErrors::InvalidArgument(format!(
+            "PSD layer \"{}\" has no pixels",
+            record.layer.name
+        )));
+    }
+    let (colorspace, ids): (ColorSpace, &[i16]) = match header.mode {
+        MODE_RGB => (ColorSpace::RGBA, &[0, 1, 2, CHANNEL_ALPHA]),
+        MODE_GRAYSCALE => (ColorSpace::LumaA, &[0, CHANNEL_ALPHA]),
+        mode => return Err(unsupported(format!("PSD layers in color mode {mode} are not supported")))
+    };
+    let depth = match header.depth {
+        8 => BitDepth::Eight,
+        16 => BitDepth::Sixteen,
+        32 => BitDepth::Float32,
+        depth => return Err(unsupported(format!("{depth} bit PSD layers are not supported")))
+    };
+    limits.check_info(&ImageInfo {
+        width,
+        height,
+        format: ImageFormat::PSD.into(),
+        depth,
+        colorspace
+    })?;
+
+    let bytes_per_sample = depth.size_of();
+    let components = ids.len();
+    let mut pixels = vec![0_u8; width * height * components * bytes_per_sample];
+
+    for (c, id) in ids.iter().enumerate() {
+        let plane = match record.channels.iter().find(|channel| channel.id == *id) {
+            Some(channel) => {
+                let channel_data = data.get(channel.data.clone()).ok_or_else(|| decode_error("Truncated PSD file"))?;
+                decode_channel(channel_data, width, height, bytes_per_sample, header.psb)?
+            }
+            // layers without a transparency mask are opaque
+            None if *id == CHANNEL_ALPHA => match depth {
+                BitDepth::Float32 => 1.0_f32.to_be_bytes().repeat(width * height),
+                _ => vec![0xFF; width * height * bytes_per_sample]
+            },
+            None => return Err(decode_error(format!("PSD layer is missing channel {id}")))
+        };
+        let destination = pixels.chunks_exact_mut(bytes_per_sample).skip(c).step_by(components);
+
+        for (dst, src) in destination.zip(plane.chunks_exact(bytes_per_sample)) {
+            dst.copy_from_slice(src);
+        }
+    }
+
+    let image = match depth {
+        BitDepth::Eight => Image::from_u8(&pixels, width, height, colorspace),
+        BitDepth::Sixteen => {
+            let samples: Vec<u16> = pixels.chunks_exact(2).map(|s| u16::from_be_bytes([s[0], s[1]])).collect();
+            Image::from_u16(&samples, width, height, colorspace)
+        }
+        _ => {
+            let samples: Vec<f32> = pixels
+                .chunks_exact(4)
+                .map(|s| f32::from_be_bytes([s[0], s[1], s[2], s[3]]))
+                .collect();
+            Image::from_f32(&samples, width, height, colorspace)
+        }
+    };
+    Ok(image)
+}
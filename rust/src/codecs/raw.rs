@@ -0,0 +1,449 @@
+//! Camera RAW decoding (DNG, CR2, NEF, ARW and the other formats `rawloader` reads)
+//!
+//! `rawloader` only unpacks the sensor data, developing it into an RGB
+//! image is done here:
+//!
+//! 1. Crop to the usable sensor area
+//! 2. Scale each photosite from the black..white level range to 0..1
+//! 3. Apply the white balance recorded by the camera
+//! 4. Demosaic with bilinear interpolation
+//! 5. Convert camera RGB to linear sRGB with the camera's color matrix
+//! 6. Apply the sRGB transfer curve
+//!
+//! The result is a 16 bit or float RGB image, floats are in 0..1 like
+//! every other float image zune produces.
+use jni::objects::{JClass, JObject, JString, JValue};
+use jni::sys::{jint, jobject, jsize};
+use jni::JNIEnv;
+use rawloader::{Orientation, RawImage, RawImageData, CFA};
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_image::errors::ImageErrors;
+use zune_image::image::Image;
+
+use crate::decoder::DecodeLimits;
+use crate::errors::{jni_guard, JniErrors};
+use crate::format::Format;
+use crate::get_string;
+use crate::probe::ImageInfo;
+
+/// CIE XYZ from linear sRGB (D65)
+const SRGB_TO_XYZ: [[f32; 3]; 3] = [
+    [0.412_453, 0.357_580, 0.180_423],
+    [0.212_671, 0.715_160, 0.072_169],
+    [0.019_334, 0.119_193, 0.950_227]
+];
+
+const RAW_METADATA_CLASS: &str = "ZilRawMetadata";
+
+/// RAW specific properties, read from the file's headers
+#[derive(Debug, Clone)]
+pub struct RawMetadata {
+    /// Camera make and model as written in the file
+    pub make: String,
+    pub model: String,
+    /// Make and model cleaned up to be consistent across formats
+    pub clean_make: String,
+    pub clean_model: String,
+    /// Dimensions of the whole sensor, before cropping
+    pub sensor_width: usize,
+    pub sensor_height: usize,
+    /// Usable sensor area as top, right, bottom and left margins
+    pub crops: [usize; 4],
+    /// Color filter array pattern, e.g. `RGGB`, empty for non mosaiced images
+    pub cfa_pattern: String,
+    /// Per channel black and white levels, in RGBE order
+    pub black_levels: [u16; 4],
+    pub white_levels: [u16; 4],
+    /// As shot white balance multipliers in RGBE order, normalized to green
+    pub wb_coeffs: [f32; 4],
+    /// XYZ to camera color matrix, four rows of three in row-major order
+    pub xyz_to_cam: [f32; 12],
+    /// EXIF orientation, 1-8, or 0 if unknown
+    pub orientation: u16
+}
+
+fn decode_error<S: Into<String>>(msg: S) -> JniErrors {
+    JniErrors::from(ImageErrors::ImageDecodeErrors(msg.into())).with_format(Format::Raw)
+}
+
+fn orientation_to_exif(orientation: Orientation) -> u16 {
+    match orientation {
+        Orientation::Normal => 1,
+        Orientation::HorizontalFlip => 2,
+        Orientation::Rotate180 => 3,
+        Orientation::VerticalFlip => 4,
+        Orientation::Transpose => 5,
+        Orientation::Rotate90 => 6,
+        Orientation::Transverse => 7,
+        Orientation::Rotate270 => 8,
+        Orientation::Unknown => 0
+    }
+}
+
+/// Parse a RAW file's headers without unpacking the sensor data
+fn read_headers(data: &[u8]) -> Result<RawImage, JniErrors> {
+    // the "dummy" decode only parses metadata and skips the pixel data
+    rawloader::decode_dummy(&mut &data[..]).map_err(|e| decode_error(e.to_string()))
+}
+
+/// Whether `data` is a RAW file `rawloader` can develop
+///
+/// Most RAW formats are TIFF files, so this is what separates them from
+/// plain TIFF images.
+pub fn is_camera_raw(data: &[u8]) -> bool {
+    read_headers(data).is_ok_and(|raw| raw.cpp == 1 || raw.cpp == 3)
+}
+
+/// White balance multipliers normalized to green, falling back to
+/// daylight when the camera didn't record any
+fn white_balance(raw: &RawImage) -> [f32; 4] {
+    let coeffs = raw.wb_coeffs;
+
+    let coeffs = if coeffs[..3].iter().all(|c| c.is_finite() && *c > 0.0) {
+        coeffs
+    } else {
+        raw.neutralwb()
+    };
+    let green = coeffs[1];
+    // the fourth channel only exists in four color sensors
+    let fourth = if coeffs[3].is_finite() && coeffs[3] > 0.0 { coeffs[3] } else { green };
+
+    [coeffs[0] / green, 1.0, coeffs[2] / green, fourth / green]
+}
+
+/// Read RAW specific metadata from a file's headers
+pub fn raw_metadata(data: &[u8]) -> Result<RawMetadata, JniErrors> {
+    let raw = read_headers(data)?;
+
+    let mut xyz_to_cam = [0.0; 12];
+    for (dst, src) in xyz_to_cam.iter_mut().zip(raw.xyz_to_cam.iter().flatten()) {
+        *dst = *src;
+    }
+
+    Ok(RawMetadata {
+        make: raw.make.clone(),
+        model: raw.model.clone(),
+        clean_make: raw.clean_make.clone(),
+        clean_model: raw.clean_model.clone(),
+        sensor_width: raw.width,
+        sensor_height: raw.height,
+        crops: raw.crops,
+        cfa_pattern: if raw.cfa.is_valid() { raw.cfa.name.clone() } else { String::new() },
+        black_levels: raw.blacklevels,
+        white_levels: raw.whitelevels,
+        wb_coeffs: white_balance(&raw),
+        xyz_to_cam,
+        orientation: orientation_to_exif(raw.orientation)
+    })
+}
+
+/// Dimensions after removing the masked and unusable sensor borders
+fn cropped_dimensions(raw: &RawImage) -> (usize, usize) {
+    let [top, right, bottom, left] = raw.crops;
+
+    (
+        raw.width.saturating_sub(left + right),
+        raw.height.saturating_sub(top + bottom)
+    )
+}
+
+/// Parse the headers of a RAW file
+pub fn probe_raw(data: &[u8]) -> Result<ImageInfo, JniErrors> {
+    let raw = read_headers(data)?;
+    let (width, height) = cropped_dimensions(&raw);
+
+    Ok(ImageInfo {
+        width,
+        height,
+        format: Format::Raw,
+        depth: BitDepth::Sixteen,
+        colorspace: ColorSpace::RGB
+    })
+}
+
+/// The camera to linear sRGB matrix, white balance is applied separately
+///
+/// Like dcraw the sRGB to camera matrix is normalized so that white
+/// balanced camera white maps to sRGB (D65) white.
+///
+/// Returns `None` if the camera has no color matrix, in which case
+/// camera RGB is used as is
+fn camera_to_srgb(raw: &RawImage) -> Option<[[f32; 4]; 3]> {
+    if raw.xyz_to_cam.iter().flatten().all(|x| *x == 0.0) {
+        return None;
+    }
+    let mut srgb_to_cam = [[0.0; 3]; 4];
+    for (i, row) in srgb_to_cam.iter_mut().enumerate() {
+        for (j, value) in row.iter_mut().enumerate() {
+            *value = (0..3).map(|k| raw.xyz_to_cam[i][k] * SRGB_TO_XYZ[k][j]).sum();
+        }
+    }
+    Some(RawImage::normalized_pseudoinverse(srgb_to_cam))
+}
+
+/// Scale sensor values to 0..1 and white balance them
+///
+/// Returns one value per photosite (or three for already demosaiced
+/// images) covering the cropped area
+fn normalize(raw: &RawImage, cfa: &CFA, width: usize, height: usize) -> Vec<f32> {
+    let [top, _, _, left] = raw.crops;
+    let wb = white_balance(raw);
+    let cpp = raw.cpp;
+
+    let scale: Vec<(f32, f32)> = (0..4)
+        .map(|c| {
+            let black = f32::from(raw.blacklevels[c]);
+            let white = f32::from(raw.whitelevels[c]).max(black + 1.0);
+            (black, 1.0 / (white - black))
+        })
+        .collect();
+
+    let sample = |index: usize| -> f32 {
+        match &raw.data {
+            RawImageData::Integer(data) => f32::from(data[index]),
+            RawImageData::Float(data) => data[index]
+        }
+    };
+
+    let mut out = Vec::with_capacity(width * height * cpp);
+    for y in 0..height {
+        let row = (y + top) * raw.width + left;
+
+        for x in 0..width {
+            for c in 0..cpp {
+                let channel = if cpp == 1 { cfa.color_at(y, x) } else { c };
+                let (black, scale) = scale[channel];
+
+                let value = (sample((row + x) * cpp + c) - black) * scale * wb[channel];
+                // clipping keeps blown highlights white rather than tinted
+                out.push(value.clamp(0.0, 1.0));
+            }
+        }
+    }
+    out
+}
+
+/// Bilinear demosaic, averaging each color over the 3x3 neighbourhood
+///
+/// This works for any repeating pattern, including X-Trans, the fourth
+/// color of four color sensors is treated as green.
+fn demosaic(mosaic: &[f32], cfa: &CFA, width: usize, height: usize) -> Vec<f32> {
+    let color = |y: usize, x: usize| match cfa.color_at(y, x) {
+        3 => 1,
+        c => c
+    };
+    let mut out = vec![0.0; width * height * 3];
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut sums = [0.0_f32; 3];
+            let mut counts = [0_u32; 3];
+
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    let c = color(ny, nx);
+                    sums[c] += mosaic[ny * width + nx];
+                    counts[c] += 1;
+                }
+            }
+            let own = color(y, x);
+            let pixel = &mut out[(y * width + x) * 3..][..3];
+
+            for c in 0..3 {
+                pixel[c] = if c == own {
+                    mosaic[y * width + x]
+                } else if counts[c] != 0 {
+                    sums[c] / counts[c] as f32
+                } else {
+                    0.0
+                };
+            }
+        }
+    }
+    out
+}
+
+/// The sRGB transfer function
+fn srgb_gamma(linear: f32) -> f32 {
+    if linear <= 0.003_130_8 {
+        linear * 12.92
+    } else {
+        1.055 * linear.powf(1.0 / 2.4) - 0.055
+    }
+}
+
+/// Develop a RAW file into an sRGB image
+///
+/// The image is 16 bit unless `float` is set, in which case it's 32 bit float
+pub fn decode_raw(data: &[u8], float: bool, limits: &DecodeLimits) -> Result<Image, JniErrors> {
+    let raw = rawloader::decode(&mut &data[..]).map_err(|e| decode_error(e.to_string()))?;
+
+    if raw.cpp != 1 && raw.cpp != 3 {
+        return Err(JniErrors::Unsupported {
+            reason: format!("RAW images with {} components per pixel are not supported", raw.cpp),
+            format: Some(Format::Raw.to_string())
+        });
+    }
+    let (width, height) = cropped_dimensions(&raw);
+    if width == 0 || height == 0 {
+        return Err(decode_error("RAW crop leaves no usable pixels"));
+    }
+    // developing needs a float copy of the sensor data and one of the output
+    limits.check_memory((width * height * 3 * 4 * 2) as u64)?;
+
+    let cfa = raw.cropped_cfa();
+    let normalized = normalize(&raw, &cfa, width, height);
+
+    let mut pixels = if raw.cpp == 3 {
+        normalized
+    } else if raw.is_monochrome() {
+        normalized.iter().flat_map(|v| [*v; 3]).collect()
+    } else {
+        demosaic(&normalized, &cfa, width, height)
+    };
+
+    let matrix = camera_to_srgb(&raw);
+    for pixel in pixels.chunks_exact_mut(3) {
+        let rgb = match &matrix {
+            Some(m) => {
+                // treat the fourth channel as a second green
+                let cam = [pixel[0], pixel[1], pixel[2], pixel[1]];
+                [0, 1, 2].map(|i| (0..4).map(|j| m[i][j] * cam[j]).sum::<f32>())
+            }
+            None => [pixel[0], pixel[1], pixel[2]]
+        };
+        for (dst, src) in pixel.iter_mut().zip(rgb) {
+            *dst = srgb_gamma(src.clamp(0.0, 1.0));
+        }
+    }
+
+    if float {
+        return Ok(Image::from_f32(&pixels, width, height, ColorSpace::RGB));
+    }
+    let pixels: Vec<u16> = pixels.iter().map(|v| (v * 65535.0).round() as u16).collect();
+
+    Ok(Image::from_u16(&pixels, width, height, ColorSpace::RGB))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_rawMetadataNative(mut env: JNIEnv, _class: JClass, filename: JString) -> jobject {
+    jni_guard(&mut env, "rawMetadataNative", |env| {
+        let path = get_string(env, &filename)?;
+        // RAW headers are spread across the file, so read all of it
+        let data = std::fs::read(&path).map_err(|e| JniErrors::from(e).with_path(&path))?;
+        let metadata = raw_metadata(&data).map_err(|e| e.with_path(&path))?;
+
+        let make = JObject::from(env.new_string(&metadata.make)?);
+        let model = JObject::from(env.new_string(&metadata.model)?);
+        let clean_make = JObject::from(env.new_string(&metadata.clean_make)?);
+        let clean_model = JObject::from(env.new_string(&metadata.clean_model)?);
+        let cfa_pattern = JObject::from(env.new_string(&metadata.cfa_pattern)?);
+
+        let crops = metadata.crops.map(|x| x as jint);
+        let black_levels = metadata.black_levels.map(jint::from);
+        let white_levels = metadata.white_levels.map(jint::from);
+
+        let int_array = |values: &[jint]| -> Result<JObject, JniErrors> {
+            let array = env.new_int_array(values.len() as jsize)?;
+            env.set_int_array_region(&array, 0, values)?;
+            Ok(array.into())
+        };
+        let crops = int_array(&crops)?;
+        let black_levels = int_array(&black_levels)?;
+        let white_levels = int_array(&white_levels)?;
+
+        let float_array = |values: &[f32]| -> Result<JObject, JniErrors> {
+            let array = env.new_float_array(values.len() as jsize)?;
+            env.set_float_array_region(&array, 0, values)?;
+            Ok(array.into())
+        };
+        let wb_coeffs = float_array(&metadata.wb_coeffs)?;
+        let xyz_to_cam = float_array(&metadata.xyz_to_cam)?;
+
+        let object = env.new_object(
+            RAW_METADATA_CLASS,
+            "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;JJ[ILjava/lang/String;[I[I[F[FI)V",
+            &[
+                JValue::Object(&make),
+                JValue::Object(&model),
+                JValue::Object(&clean_make),
+                JValue::Object(&clean_model),
+                JValue::Long(metadata.sensor_width as _),
+                JValue::Long(metadata.sensor_height as _),
+                JValue::Object(&crops),
+                JValue::Object(&cfa_pattern),
+                JValue::Object(&black_levels),
+                JValue::Object(&white_levels),
+                JValue::Object(&wb_coeffs),
+                JValue::Object(&xyz_to_cam),
+                JValue::Int(jint::from(metadata.orientation))
+            ]
+        )?;
+        Ok(object)
+    })
+    .into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use zune_core::bit_depth::BitDepth;
+    use zune_core::colorspace::ColorSpace;
+
+    use super::*;
+
+    /// An 8x6 RGGB sensor with a one photosite border outside its active area,
+    /// black level 64, white level 4095 and an as shot neutral of 0.5, 1, 0.8
+    const DNG: &[u8] = include_bytes!("../../tests/data/rggb_8x6.dng");
+
+    #[test]
+    fn detects_dng() {
+        assert_eq!(Format::guess(DNG), Format::Raw);
+
+        let info = probe_raw(DNG).unwrap();
+        assert_eq!((info.width, info.height), (6, 4));
+        assert_eq!(info.depth, BitDepth::Sixteen);
+    }
+
+    #[test]
+    fn develops_to_16_bit() {
+        let image = decode_raw(DNG, false, &DecodeLimits::default()).unwrap();
+        assert_eq!(image.dimensions(), (6, 4));
+        assert_eq!(image.depth(), BitDepth::Sixteen);
+        assert_eq!(image.colorspace(), ColorSpace::RGB);
+    }
+
+    #[test]
+    fn develops_to_float() {
+        let image = decode_raw(DNG, true, &DecodeLimits::default()).unwrap();
+        assert_eq!(image.dimensions(), (6, 4));
+        assert_eq!(image.depth(), BitDepth::Float32);
+
+        let pixels = image.flatten_frames::<f32>().swap_remove(0);
+        assert_eq!(pixels.len(), 6 * 4 * 3);
+        assert!(pixels.iter().all(|v| (0.0..=1.0).contains(v)));
+    }
+
+    #[test]
+    fn reports_metadata() {
+        let metadata = raw_metadata(DNG).unwrap();
+        assert_eq!((metadata.make.as_str(), metadata.model.as_str()), ("Pixly", "Test Sensor"));
+        assert_eq!((metadata.sensor_width, metadata.sensor_height), (8, 6));
+        assert_eq!(metadata.crops, [1, 1, 1, 1]);
+        assert_eq!(metadata.cfa_pattern, "RGGB");
+        assert_eq!(metadata.black_levels, [64; 4]);
+        assert_eq!(metadata.white_levels, [4095; 4]);
+        assert_eq!(metadata.wb_coeffs, [2.0, 1.0, 1.25, 1.0]);
+        assert_eq!(metadata.xyz_to_cam, [1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0]);
+        assert_eq!(metadata.orientation, 1);
+    }
+
+    #[test]
+    fn enforces_memory_budget() {
+        let limits = DecodeLimits {
+            memory_budget: 64,
+            ..DecodeLimits::default()
+        };
+        assert!(matches!(decode_raw(DNG, false, &limits), Err(JniErrors::LimitExceeded { .. })));
+    }
+}
@@ -0,0 +1,117 @@
+//! SVG rasterization via resvg
+//!
+//! SVGs have no pixel size of their own, the caller picks one with
+//! [`SvgOptions`], by default the document's size at 96 DPI is used.
+use std::sync::{Arc, OnceLock};
+
+use resvg::tiny_skia::{Pixmap, Transform};
+use resvg::usvg::{fontdb, Options, Tree};
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_image::errors::ImageErrors;
+use zune_image::image::Image;
+
+use crate::decoder::{DecodeLimits, SvgOptions};
+use crate::errors::JniErrors;
+use crate::format::Format;
+use crate::probe::ImageInfo;
+
+/// The DPI SVG user units are defined at
+const CSS_DPI: f32 = 96.0;
+
+fn decode_error<S: Into<String>>(msg: S) -> JniErrors {
+    JniErrors::from(ImageErrors::ImageDecodeErrors(msg.into())).with_format(Format::Svg)
+}
+
+/// System fonts for rendering text, loading them is slow so it's done once
+fn fonts() -> Arc<fontdb::Database> {
+    static FONTS: OnceLock<Arc<fontdb::Database>> = OnceLock::new();
+
+    FONTS
+        .get_or_init(|| {
+            let mut fonts = fontdb::Database::new();
+            fonts.load_system_fonts();
+            Arc::new(fonts)
+        })
+        .clone()
+}
+
+fn parse(data: &[u8]) -> Result<Tree, JniErrors> {
+    let options = Options {
+        fontdb: fonts(),
+        ..Options::default()
+    };
+    Tree::from_data(data, &options).map_err(|e| decode_error(e.to_string()))
+}
+
+/// Pixel size of the rasterized image and the transform fitting the document into it
+fn output_size(tree: &Tree, options: &SvgOptions) -> (usize, usize, Transform) {
+    let (doc_width, doc_height) = (tree.size().width(), tree.size().height());
+    let scale = options.dpi / CSS_DPI;
+
+    let (width, height) = match (options.width, options.height) {
+        (0, 0) => (doc_width * scale, doc_height * scale),
+        (width, 0) => (width as f32, doc_height * width as f32 / doc_width),
+        (0, height) => (doc_width * height as f32 / doc_height, height as f32),
+        (width, height) => (width as f32, height as f32)
+    };
+    let (width, height) = (width.round().max(1.0), height.round().max(1.0));
+
+    // keep the aspect ratio, centering the document if both sides were given
+    let scale = (width / doc_width).min(height / doc_height);
+    let dx = (width - doc_width * scale) / 2.0;
+    let dy = (height - doc_height * scale) / 2.0;
+
+    let transform = Transform::from_scale(scale, scale).post_translate(dx, dy);
+
+    (width as usize, height as usize, transform)
+}
+
+fn info(width: usize, height: usize) -> ImageInfo {
+    ImageInfo {
+        width,
+        height,
+        format: Format::Svg,
+        depth: BitDepth::Eight,
+        colorspace: ColorSpace::RGBA
+    }
+}
+
+/// Parse an SVG, reporting its size at the default DPI
+pub fn probe_svg(data: &[u8]) -> Result<ImageInfo, JniErrors> {
+    let (width, height, _) = output_size(&parse(data)?, &SvgOptions::default());
+
+    Ok(info(width, height))
+}
+
+/// Rasterize an SVG (or gzip compressed SVGZ) to an RGBA image sized by `options`
+pub fn decode_svg(data: &[u8], options: &SvgOptions, limits: &DecodeLimits) -> Result<Image, JniErrors> {
+    render(&parse(data)?, options, limits)
+}
+
+/// Rasterize an SVG with its longest side `max_edge` pixels long, enlarging small documents
+pub fn decode_svg_to_fit(data: &[u8], max_edge: usize, limits: &DecodeLimits) -> Result<Image, JniErrors> {
+    let tree = parse(data)?;
+    let options = match tree.size().width() >= tree.size().height() {
+        true => SvgOptions { width: max_edge, ..SvgOptions::default() },
+        false => SvgOptions { height: max_edge, ..SvgOptions::default() }
+    };
+    render(&tree, &options, limits)
+}
+
+fn render(tree: &Tree, options: &SvgOptions, limits: &DecodeLimits) -> Result<Image, JniErrors> {
+    let (width, height, transform) = output_size(tree, options);
+
+    // the size comes from the options rather than the headers, check it here
+    limits.check_info(&info(width, height))?;
+
+    let mut pixmap = u32::try_from(width)
+        .ok()
+        .zip(u32::try_from(height).ok())
+        .and_then(|(w, h)| Pixmap::new(w, h))
+        .ok_or_else(|| decode_error(format!("Cannot rasterize SVG at {width} x {height}")))?;
+
+    resvg::render(tree, transform, &mut pixmap.as_mut());
+
+    Ok(Image::from_u8(&pixmap.take_demultiplied(), width, height, ColorSpace::RGBA))
+}
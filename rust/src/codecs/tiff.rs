@@ -0,0 +1,155 @@
+//! TIFF decoding via the `tiff` crate
+//!
+//! Every page sharing the first page's size and layout becomes a frame of
+//! the image, pages that differ (usually reduced resolution previews)
+//! are skipped since all frames of an image must match.
+use std::io::{Cursor, Read, Seek};
+
+use tiff::decoder::{Decoder, DecodingResult, Limits};
+use tiff::ColorType;
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_image::errors::ImageErrors;
+use zune_image::frame::Frame;
+use zune_image::image::Image;
+
+use crate::decoder::DecodeLimits;
+use crate::errors::JniErrors;
+use crate::format::Format;
+use crate::probe::ImageInfo;
+
+fn decode_error(err: tiff::TiffError) -> JniErrors {
+    JniErrors::from(ImageErrors::ImageDecodeErrors(err.to_string())).with_format(Format::Tiff)
+}
+
+/// The colorspace a page of `color_type` decodes to
+fn colorspace(color_type: ColorType) -> Result<ColorSpace, JniErrors> {
+    let colorspace = match color_type {
+        ColorType::Gray(_) => ColorSpace::Luma,
+        ColorType::GrayA(_) => ColorSpace::LumaA,
+        // gray with an extra sample, the extra sample is alpha
+        ColorType::Multiband { num_samples: 2, .. } => ColorSpace::LumaA,
+        ColorType::RGB(_) => ColorSpace::RGB,
+        ColorType::RGBA(_) => ColorSpace::RGBA,
+        ColorType::CMYK(_) => ColorSpace::CMYK,
+        ColorType::YCbCr(_) => ColorSpace::YCbCr,
+        _ => {
+            return Err(JniErrors::Unsupported {
+                reason: format!("Unsupported TIFF color type {color_type:?}"),
+                format: Some(Format::Tiff.to_string())
+            })
+        }
+    };
+    Ok(colorspace)
+}
+
+/// The depth a page of `color_type` decodes to, low bit depth grayscale is expanded to 8 bits
+fn depth(color_type: ColorType) -> BitDepth {
+    match color_type.bit_depth() {
+        1..=8 => BitDepth::Eight,
+        16 => BitDepth::Sixteen,
+        _ => BitDepth::Float32
+    }
+}
+
+/// Expand packed 1, 2 or 4 bit grayscale samples to 8 bits
+///
+/// Each row starts on a byte boundary
+fn expand_gray(packed: &[u8], width: usize, bits: u8) -> Vec<u8> {
+    let per_byte = usize::from(8 / bits);
+    let row_bytes = width.div_ceil(per_byte);
+    let max = (1_u16 << bits) - 1;
+    let mask = max as u8;
+
+    let mut out = Vec::with_capacity(packed.len() * per_byte);
+    for row in packed.chunks_exact(row_bytes) {
+        for x in 0..width {
+            let byte = row[x / per_byte];
+            let shift = 8 - bits as usize * (x % per_byte + 1);
+            let value = u16::from((byte >> shift) & mask);
+            out.push((value * 255 / max) as u8);
+        }
+    }
+    out
+}
+
+/// Decode the page the decoder currently points at into a frame
+fn read_frame(decoder: &mut Decoder<Cursor<&[u8]>>, width: usize, color_type: ColorType) -> Result<Frame, JniErrors> {
+    let colorspace = colorspace(color_type)?;
+
+    let frame = match decoder.read_image().map_err(decode_error)? {
+        DecodingResult::U8(pixels) if color_type.bit_depth() < 8 => match color_type {
+            ColorType::Gray(bits @ (1 | 2 | 4)) => Frame::from_u8(&expand_gray(&pixels, width, bits), colorspace, 1, 1),
+            _ => {
+                return Err(JniErrors::Unsupported {
+                    reason: format!("Unsupported TIFF sample depth for {color_type:?}, only 1, 2 and 4 bit grayscale is expanded"),
+                    format: Some(Format::Tiff.to_string())
+                })
+            }
+        },
+        DecodingResult::U8(pixels) => Frame::from_u8(&pixels, colorspace, 1, 1),
+        DecodingResult::U16(pixels) => Frame::from_u16(&pixels, colorspace, 1, 1),
+        DecodingResult::F32(pixels) => Frame::from_f32(&pixels, colorspace, 1, 1),
+        DecodingResult::F64(pixels) => {
+            let pixels: Vec<f32> = pixels.iter().map(|x| *x as f32).collect();
+            Frame::from_f32(&pixels, colorspace, 1, 1)
+        }
+        _ => {
+            return Err(JniErrors::Unsupported {
+                reason: format!("Unsupported TIFF sample format for {color_type:?}"),
+                format: Some(Format::Tiff.to_string())
+            })
+        }
+    };
+    Ok(frame)
+}
+
+/// Parse the headers of the first page
+///
+/// Only the first directory and the values it points to are read from `reader`
+pub fn probe_tiff<R: Read + Seek>(reader: R) -> Result<ImageInfo, JniErrors> {
+    let mut decoder = Decoder::new(reader).map_err(decode_error)?;
+    let (width, height) = decoder.dimensions().map_err(decode_error)?;
+    let color_type = decoder.colortype().map_err(decode_error)?;
+
+    Ok(ImageInfo {
+        width: width as usize,
+        height: height as usize,
+        format: Format::Tiff,
+        depth: depth(color_type),
+        colorspace: colorspace(color_type)?
+    })
+}
+
+/// Decode all pages matching the first one, 8 and 16 bit integer and
+/// floating point samples are supported
+pub fn decode_tiff(data: &[u8], limits: &DecodeLimits) -> Result<Image, JniErrors> {
+    // our own limits were checked against the headers already
+    let mut decoder = Decoder::new(Cursor::new(data))
+        .map_err(decode_error)?
+        .with_limits(Limits::unlimited());
+
+    let (width, height) = decoder.dimensions().map_err(decode_error)?;
+    let color_type = decoder.colortype().map_err(decode_error)?;
+    let (width, height) = (width as usize, height as usize);
+
+    let colorspace = colorspace(color_type)?;
+    let depth = depth(color_type);
+    let frame_size = (width * height * colorspace.num_components() * depth.size_of()) as u64;
+
+    let mut frames = vec![read_frame(&mut decoder, width, color_type)?];
+
+    while decoder.more_images() {
+        decoder.next_image().map_err(decode_error)?;
+
+        let page_dims = decoder.dimensions().map_err(decode_error)?;
+        let page_type = decoder.colortype().map_err(decode_error)?;
+
+        if page_dims != (width as u32, height as u32) || page_type != color_type {
+            continue;
+        }
+        limits.check_memory(frame_size * (frames.len() as u64 + 1))?;
+        frames.push(read_frame(&mut decoder, width, color_type)?);
+    }
+    Ok(Image::new_frames(frames, depth, width, height, colorspace))
+}
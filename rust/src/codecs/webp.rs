@@ -0,0 +1,305 @@
+//! WebP encoding and decoding via libwebp
+use webp::{AnimDecoder, AnimEncoder, AnimFrame, BitstreamFeatures, PixelLayout, WebPConfig};
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::{ImageErrors, ImgEncodeErrors};
+use zune_image::frame::Frame;
+use zune_image::image::Image;
+use zune_image::traits::EncoderTrait;
+
+use crate::animation::Animation;
+use crate::containers;
+use crate::decoder::DecodeLimits;
+use crate::errors::JniErrors;
+use crate::format::Format;
+use crate::probe::ImageInfo;
+
+/// `VP8X` flags
+const VP8X_ICC: u8 = 0x20;
+const VP8X_ALPHA: u8 = 0x10;
+const VP8X_EXIF: u8 = 0x08;
+const VP8X_XMP: u8 = 0x04;
+
+pub struct WebPEncoder {
+    lossless: bool,
+    /// 0-100, for lossless files this trades speed for size
+    quality: f32,
+    /// 0 (fastest) - 6 (smallest)
+    method: i32,
+    /// Frame delays and loop count, used when the image has several frames
+    animation: Animation,
+    icc: Option<Vec<u8>>,
+    exif: Option<Vec<u8>>,
+    xmp: Option<Vec<u8>>
+}
+
+impl WebPEncoder {
+    pub fn new(lossless: bool, quality: u8, method: u8) -> WebPEncoder {
+        WebPEncoder {
+            lossless,
+            quality: f32::from(quality.min(100)),
+            method: i32::from(method.min(6)),
+            animation: Animation::default(),
+            icc: None,
+            exif: None,
+            xmp: None
+        }
+    }
+
+    /// Write every frame of the image, timed by `animation`
+    pub fn animated(mut self, animation: Animation) -> WebPEncoder {
+        self.animation = animation;
+        self
+    }
+
+    /// Embed an ICC profile describing the pixels
+    pub fn icc_profile(mut self, profile: Option<Vec<u8>>) -> WebPEncoder {
+        self.icc = profile;
+        self
+    }
+
+    /// Embed EXIF, stored as a TIFF structure
+    pub fn exif(mut self, exif: Option<Vec<u8>>) -> WebPEncoder {
+        self.exif = exif;
+        self
+    }
+
+    /// Embed an XMP packet
+    pub fn xmp(mut self, xmp: Option<Vec<u8>>) -> WebPEncoder {
+        self.xmp = xmp;
+        self
+    }
+}
+
+impl EncoderTrait for WebPEncoder {
+    fn name(&self) -> &'static str {
+        "libwebp"
+    }
+
+    fn encode_inner(&mut self, image: &Image) -> Result<Vec<u8>, ImageErrors> {
+        let colorspace = image.colorspace();
+        let layout = match colorspace {
+            ColorSpace::RGB => PixelLayout::Rgb,
+            ColorSpace::RGBA => PixelLayout::Rgba,
+            _ => {
+                return Err(ImgEncodeErrors::UnsupportedColorspace(colorspace, self.supported_colorspaces()).into())
+            }
+        };
+        let (width, height) = image.dimensions();
+        let frames = image.flatten_frames::<u8>();
+
+        let mut config = WebPConfig::new()
+            .map_err(|_| ImgEncodeErrors::GenericStatic("Could not initialize the WebP encoder"))?;
+        config.lossless = i32::from(self.lossless);
+        config.quality = self.quality;
+        config.method = self.method;
+        // keep lossy files' alpha lossless, it's usually a mask
+        config.alpha_compression = i32::from(!self.lossless);
+
+        if frames.len() == 1 {
+            let encoded = webp::Encoder::new(&frames[0], layout, width as u32, height as u32)
+                .encode_advanced(&config)
+                .map_err(|e| ImgEncodeErrors::ImageEncodeErrors(format!("WebP encoding failed: {e:?}")))?;
+
+            return Ok(self.with_metadata(encoded.to_vec(), width, height, colorspace.has_alpha()));
+        }
+        let mut encoder = AnimEncoder::new(width as u32, height as u32, &config);
+        encoder.set_loop_count(i32::try_from(self.animation.loop_count).unwrap_or(i32::MAX));
+
+        // timestamps mark the start of each frame
+        let mut timestamp = 0_i32;
+        for (index, pixels) in frames.iter().enumerate() {
+            encoder.add_frame(AnimFrame::new(pixels, layout, width as u32, height as u32, timestamp, None));
+            timestamp = timestamp.saturating_add(i32::try_from(self.animation.delay(index)).unwrap_or(i32::MAX));
+        }
+        let encoded = encoder
+            .try_encode()
+            .map_err(|e| ImgEncodeErrors::ImageEncodeErrors(format!("WebP encoding failed: {e:?}")))?;
+
+        let mut encoded = encoded.to_vec();
+        set_last_frame_duration(&mut encoded, self.animation.delay(frames.len() - 1));
+        Ok(self.with_metadata(encoded, width, height, colorspace.has_alpha()))
+    }
+
+    fn supported_colorspaces(&self) -> &'static [ColorSpace] {
+        &[ColorSpace::RGB, ColorSpace::RGBA]
+    }
+
+    fn format(&self) -> ImageFormat {
+        // zune has no id for WebP
+        ImageFormat::Unknown
+    }
+
+    fn supported_bit_depth(&self) -> &'static [BitDepth] {
+        &[BitDepth::Eight]
+    }
+
+    fn default_depth(&self, _: BitDepth) -> BitDepth {
+        BitDepth::Eight
+    }
+
+    fn default_colorspace(&self, colorspace: ColorSpace) -> ColorSpace {
+        if colorspace.has_alpha() {
+            ColorSpace::RGBA
+        } else {
+            ColorSpace::RGB
+        }
+    }
+}
+
+impl WebPEncoder {
+    /// Add `ICCP`, `EXIF` and `XMP ` chunks to an encoded file, turning a simple file into an extended one if needed
+    ///
+    /// libwebp's encoder has no way to take metadata, that's left to its muxer
+    fn with_metadata(&self, data: Vec<u8>, width: usize, height: usize, alpha: bool) -> Vec<u8> {
+        if self.icc.is_none() && self.exif.is_none() && self.xmp.is_none() {
+            return data;
+        }
+        let flags = if self.icc.is_some() { VP8X_ICC } else { 0 }
+            | if self.exif.is_some() { VP8X_EXIF } else { 0 }
+            | if self.xmp.is_some() { VP8X_XMP } else { 0 };
+        let extra = [&self.icc, &self.exif, &self.xmp].iter().map(|chunk| chunk.as_ref().map_or(0, Vec::len)).sum::<usize>();
+
+        // skip the RIFF header
+        let chunks = &data[12..];
+        let mut output = Vec::with_capacity(data.len() + extra + 48);
+        output.extend_from_slice(b"RIFF\0\0\0\0WEBP");
+
+        let rest = if chunks.starts_with(b"VP8X") {
+            let mut header = chunks[..18].to_vec();
+            header[8] |= flags;
+            output.extend_from_slice(&header);
+            &chunks[18..]
+        } else {
+            let flags = flags | if alpha { VP8X_ALPHA } else { 0 };
+            output.extend_from_slice(b"VP8X");
+            output.extend_from_slice(&10_u32.to_le_bytes());
+            output.extend_from_slice(&[flags, 0, 0, 0]);
+            // canvas size minus one, 24 bits each
+            output.extend_from_slice(&(width as u32 - 1).to_le_bytes()[..3]);
+            output.extend_from_slice(&(height as u32 - 1).to_le_bytes()[..3]);
+            chunks
+        };
+        // the profile precedes the image data, EXIF and XMP follow it
+        if let Some(profile) = &self.icc {
+            push_chunk(&mut output, b"ICCP", profile);
+        }
+        output.extend_from_slice(rest);
+        if let Some(exif) = &self.exif {
+            push_chunk(&mut output, b"EXIF", exif);
+        }
+        if let Some(xmp) = &self.xmp {
+            push_chunk(&mut output, b"XMP ", xmp);
+        }
+
+        let riff_size = (output.len() - 8) as u32;
+        output[4..8].copy_from_slice(&riff_size.to_le_bytes());
+        output
+    }
+}
+
+fn push_chunk(output: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    output.extend_from_slice(fourcc);
+    output.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    output.extend_from_slice(data);
+    // chunks are padded to an even length
+    if data.len() % 2 == 1 {
+        output.push(0);
+    }
+}
+
+/// Overwrite the duration of the last `ANMF` chunk of an animated WebP
+///
+/// The `webp` crate ends animations at timestamp zero, which makes libwebp
+/// guess the last frame's duration rather than use the one we gave it
+fn set_last_frame_duration(data: &mut [u8], duration: u32) {
+    let last = containers::riff_chunks(data)
+        .filter(|(fourcc, payload)| *fourcc == b"ANMF" && payload.len() >= 16)
+        .last()
+        // where the payload starts within data
+        .map(|(_, payload)| payload.as_ptr() as usize - data.as_ptr() as usize);
+
+    // the duration is 24 bits following the frame's offset and size
+    if let Some(payload) = last {
+        data[payload + 12..payload + 15].copy_from_slice(&duration.min(0xFF_FFFF).to_le_bytes()[..3]);
+    }
+}
+
+fn decode_error<S: Into<String>>(msg: S) -> JniErrors {
+    JniErrors::from(ImageErrors::ImageDecodeErrors(msg.into())).with_format(Format::WebP)
+}
+
+fn features(data: &[u8]) -> Result<BitstreamFeatures, JniErrors> {
+    BitstreamFeatures::new(data).ok_or_else(|| decode_error("Invalid WebP headers"))
+}
+
+/// Parse the WebP headers
+pub fn probe_webp(data: &[u8]) -> Result<ImageInfo, JniErrors> {
+    let features = features(data)?;
+
+    Ok(ImageInfo {
+        width: features.width() as usize,
+        height: features.height() as usize,
+        format: Format::WebP,
+        depth: BitDepth::Eight,
+        // animations are always decoded with alpha
+        colorspace: if features.has_alpha() || features.has_animation() {
+            ColorSpace::RGBA
+        } else {
+            ColorSpace::RGB
+        }
+    })
+}
+
+/// Decode a still or animated WebP, along with the timing of animations
+///
+/// libwebp decodes every frame of an animation to a full canvas in one go,
+/// so the memory budget is checked against the number of frames first.
+pub fn decode_webp(data: &[u8], limits: &DecodeLimits) -> Result<(Image, Animation), JniErrors> {
+    let features = features(data)?;
+    if !features.has_animation() {
+        let decoded = webp::Decoder::new(data)
+            .decode()
+            .ok_or_else(|| decode_error("Could not decode WebP image"))?;
+
+        let colorspace = if decoded.is_alpha() { ColorSpace::RGBA } else { ColorSpace::RGB };
+        let (width, height) = (decoded.width() as usize, decoded.height() as usize);
+
+        return Ok((Image::from_u8(&decoded, width, height, colorspace), Animation::default()));
+    }
+    let frame_count = containers::riff_chunks(data).filter(|(id, _)| *id == b"ANMF").count() as u64;
+    let canvas = u64::from(features.width()) * u64::from(features.height()) * 4;
+    limits.check_memory(frame_count.saturating_mul(canvas))?;
+
+    let animation = AnimDecoder::new(data).decode().map_err(decode_error)?;
+
+    let first = animation
+        .get_frame(0)
+        .ok_or_else(|| decode_error("WebP animation contains no frames"))?;
+    let (width, height) = (first.width() as usize, first.height() as usize);
+    // every frame shares the layout libwebp was asked to decode to
+    let colorspace = match first.get_layout() {
+        PixelLayout::Rgb => ColorSpace::RGB,
+        PixelLayout::Rgba => ColorSpace::RGBA
+    };
+
+    let mut frames = Vec::with_capacity(animation.len());
+    let mut delays = Vec::with_capacity(animation.len());
+    // timestamps mark the end of each frame
+    let mut previous_end = 0;
+
+    for frame in &animation {
+        let delay = (frame.get_time_ms() - previous_end).max(0) as usize;
+        previous_end = frame.get_time_ms();
+
+        frames.push(Frame::from_u8(frame.get_image(), colorspace, delay, 1000));
+        delays.push(delay as u32);
+    }
+    let timing = Animation {
+        delays,
+        loop_count: animation.loop_count
+    };
+    Ok((Image::new_frames(frames, BitDepth::Eight, width, height, colorspace), timing))
+}
+
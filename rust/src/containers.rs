@@ -0,0 +1,95 @@
+//! Walking the segments, chunks and boxes image files are made of
+//!
+//! Used to find the metadata zune doesn't report and to add it back to
+//! the files our encoders write. Each iterator stops quietly at the first
+//! entry that runs past the end of the data.
+
+/// Signature box starting a JPEG-XL container
+pub const JXL_SIGNATURE_BOX: &[u8] = b"\0\0\0\x0CJXL \r\n\x87\n";
+
+/// Marker and payload of each JPEG segment before the image data
+pub fn jpeg_segments(data: &[u8]) -> impl Iterator<Item = (u8, &[u8])> {
+    // skip the start of image marker
+    let mut position = 2;
+
+    std::iter::from_fn(move || loop {
+        if data.get(position) != Some(&0xFF) {
+            return None;
+        }
+        let marker = *data.get(position + 1)?;
+        match marker {
+            // fill bytes
+            0xFF => position += 1,
+            // start of scan, what follows is entropy coded
+            0xDA => return None,
+            _ => {
+                let length = usize::from(u16::from_be_bytes(data.get(position + 2..position + 4)?.try_into().ok()?));
+                let payload = data.get(position + 4..position + 2 + length.max(2))?;
+                position += 2 + length;
+                return Some((marker, payload));
+            }
+        }
+    })
+}
+
+/// Type and data of each PNG chunk
+pub fn png_chunks(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    // skip the signature
+    let mut position = 8;
+
+    std::iter::from_fn(move || {
+        let header = data.get(position..position + 8)?;
+        let length = u32::from_be_bytes(header[..4].try_into().ok()?) as usize;
+        let chunk = data.get(position + 8..(position + 8).checked_add(length)?)?;
+        // length, type, data and crc
+        position += 12 + length;
+        Some((&header[4..8], chunk))
+    })
+}
+
+/// FourCC and payload of each chunk of a RIFF file such as WebP
+pub fn riff_chunks(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    // skip the RIFF header
+    let mut position = 12;
+
+    std::iter::from_fn(move || {
+        let header = data.get(position..position + 8)?;
+        let length = u32::from_le_bytes(header[4..8].try_into().ok()?) as usize;
+        let chunk = data.get(position + 8..(position + 8).checked_add(length)?)?;
+        // chunks are padded to an even length
+        position += 8 + length + (length & 1);
+        Some((&header[..4], chunk))
+    })
+}
+
+/// Type and payload of each box of an ISO BMFF file such as a JPEG-XL container
+pub fn bmff_boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut position = 0;
+
+    std::iter::from_fn(move || {
+        let header = data.get(position..position + 8)?;
+        let (payload_start, size) = match u32::from_be_bytes(header[..4].try_into().ok()?) {
+            // the box runs to the end of the file
+            0 => (position + 8, data.len() - position),
+            // a 64 bit size follows the type
+            1 => {
+                let size = u64::from_be_bytes(data.get(position + 8..position + 16)?.try_into().ok()?);
+                (position + 16, usize::try_from(size).ok()?)
+            }
+            size => (position + 8, size as usize)
+        };
+        let end = position.checked_add(size)?;
+        let payload = data.get(payload_start..end)?;
+        position = end;
+        Some((&header[4..8], payload))
+    })
+}
+
+/// A box of an ISO BMFF file
+pub fn bmff_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut output = Vec::with_capacity(payload.len() + 8);
+    output.extend_from_slice(&(payload.len() as u32 + 8).to_be_bytes());
+    output.extend_from_slice(kind);
+    output.extend_from_slice(payload);
+    output
+}
@@ -0,0 +1,459 @@
+//! Decoding images from files and in-memory sources
+//!
+//! All load paths funnel into [`decode_bytes`], the format is always detected
+//! from the encoded bytes themselves rather than from a file extension.
+//!
+//! Decoding is configured by [`DecodeOptions`], whose [`DecodeLimits`] are
+//! checked against the image headers before any pixel memory is allocated.
+use jni::objects::{JByteArray, JByteBuffer, JClass, JObject, JString};
+use jni::sys::{jint, jlong};
+use jni::JNIEnv;
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_core::options::DecoderOptions;
+use zune_image::codecs::ImageFormat;
+use zune_image::image::Image;
+
+use crate::animation::Animation;
+use crate::errors::{jni_guard, JniErrors};
+use crate::format::Format;
+use crate::handles::Entry;
+use crate::icc::{self, WorkingSpace};
+use crate::iptc::Iptc;
+use crate::metadata::{self, Exif};
+use crate::xmp::Xmp;
+use crate::{codecs, get_string, handles, probe};
+
+/// Limits applied when decoding untrusted images
+///
+/// A value of zero means the limit is not applied, for width and height
+/// the decoder defaults are used instead.
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DecodeLimits {
+    pub max_width: usize,
+    pub max_height: usize,
+    pub max_pixels: u64,
+    /// Maximum bytes the decoded pixels (and the encoded file) may occupy
+    pub memory_budget: u64,
+    /// Treat recoverable errors (e.g. bad checksums, truncated data) as fatal
+    pub strict: bool
+}
+
+/// How images are decoded
+#[derive(Debug, Copy, Clone, Default)]
+pub struct DecodeOptions {
+    pub limits: DecodeLimits,
+    /// Develop camera RAW files to 32 bit float rather than 16 bit
+    pub raw_float: bool,
+    /// Color space RGB pixels are converted to
+    pub working_space: WorkingSpace,
+    /// Turn the pixels upright according to the EXIF orientation
+    pub apply_orientation: bool
+}
+
+impl DecodeOptions {
+    /// Read options from a Kotlin `ZilDecodeOptions` object
+    pub fn from_java(env: &mut JNIEnv, options: &JObject) -> Result<DecodeOptions, JniErrors> {
+        let limits = DecodeLimits::from_java(env, options)?;
+        let raw_float = env.get_field(options, "rawFloat", "Z")?.z()?;
+
+        let space = env.get_field(options, "workingSpace", "LZilWorkingSpace;")?.l()?;
+        if space.is_null() {
+            return Err(JniErrors::InvalidArgument("workingSpace should not be null".to_string()));
+        }
+        let ordinal = env.call_method(&space, "ordinal", "()I", &[])?.i()?;
+        let working_space = WorkingSpace::from_ordinal(ordinal)
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown working space {ordinal}")))?;
+
+        Ok(DecodeOptions {
+            limits,
+            raw_float,
+            working_space,
+            apply_orientation: env.get_field(options, "applyOrientation", "Z")?.z()?
+        })
+    }
+}
+
+impl DecodeLimits {
+    /// Read limits from a Kotlin `ZilDecodeOptions` object
+    pub fn from_java(env: &mut JNIEnv, options: &JObject) -> Result<DecodeLimits, JniErrors> {
+        // negative values make no sense, treat them as "no limit"
+        let mut get_long = |name: &str| -> Result<u64, JniErrors> {
+            Ok(env.get_field(options, name, "J")?.j()?.max(0) as u64)
+        };
+        let max_width = get_long("maxWidth")? as usize;
+        let max_height = get_long("maxHeight")? as usize;
+        let max_pixels = get_long("maxPixels")?;
+        let memory_budget = get_long("memoryBudget")?;
+        let strict = env.get_field(options, "strict", "Z")?.z()?;
+
+        Ok(DecodeLimits {
+            max_width,
+            max_height,
+            max_pixels,
+            memory_budget,
+            strict
+        })
+    }
+
+    fn decoder_options(&self) -> DecoderOptions {
+        let mut options = DecoderOptions::default().set_strict_mode(self.strict);
+
+        if self.max_width != 0 {
+            options = options.set_max_width(self.max_width);
+        }
+        if self.max_height != 0 {
+            options = options.set_max_height(self.max_height);
+        }
+        if self.memory_budget != 0 {
+            // bounds how much a png's compressed stream may inflate to
+            options = options.inflate_set_limit(self.memory_budget.min(usize::MAX as u64) as usize);
+        }
+        options
+    }
+
+    fn check(limit: &'static str, value: u64, max: u64) -> Result<(), JniErrors> {
+        if max != 0 && value > max {
+            return Err(JniErrors::LimitExceeded { limit, value, max });
+        }
+        Ok(())
+    }
+
+    /// Ensure decoded pixels occupying `required` bytes fit the memory budget
+    ///
+    /// Used by decoders producing several frames, where the headers only describe the first
+    pub fn check_memory(&self, required: u64) -> Result<(), JniErrors> {
+        DecodeLimits::check("memoryBudget", required, self.memory_budget)
+    }
+
+    /// Ensure an encoded file of `size` bytes can be read into memory
+    pub fn check_file_size(&self, size: u64) -> Result<(), JniErrors> {
+        DecodeLimits::check("memoryBudget", size, self.memory_budget)
+    }
+
+    /// Ensure an image described by `info` fits the limits
+    pub fn check_info(&self, info: &probe::ImageInfo) -> Result<(), JniErrors> {
+        let (width, height) = (info.width as u64, info.height as u64);
+
+        DecodeLimits::check("maxWidth", width, self.max_width as u64)?;
+        DecodeLimits::check("maxHeight", height, self.max_height as u64)?;
+        DecodeLimits::check("maxPixels", width.saturating_mul(height), self.max_pixels)?;
+
+        let components = match info.colorspace {
+            ColorSpace::Unknown => 4,
+            colorspace => colorspace.num_components() as u64
+        };
+        // if the headers don't declare a depth, assume the widest one
+        let depth = match info.depth {
+            BitDepth::Unknown => BitDepth::Float32.size_of() as u64,
+            depth => depth.size_of() as u64
+        };
+        let required = width
+            .saturating_mul(height)
+            .saturating_mul(components)
+            .saturating_mul(depth);
+
+        DecodeLimits::check("memoryBudget", required, self.memory_budget)
+    }
+
+    fn is_unlimited(&self) -> bool {
+        self.max_width == 0 && self.max_height == 0 && self.max_pixels == 0 && self.memory_budget == 0
+    }
+}
+
+/// Size SVG documents are rasterized at
+///
+/// With neither width nor height set the document's own size is scaled
+/// by `dpi / 96`, with one of them set the other follows the aspect ratio.
+#[derive(Debug, Copy, Clone)]
+pub struct SvgOptions {
+    pub width: usize,
+    pub height: usize,
+    pub dpi: f32
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            width: 0,
+            height: 0,
+            dpi: 96.0
+        }
+    }
+}
+
+impl SvgOptions {
+    /// Read options from a Kotlin `ZilSvgOptions` object
+    pub fn from_java(env: &mut JNIEnv, options: &JObject) -> Result<SvgOptions, JniErrors> {
+        let mut get_size = |name: &str| -> Result<usize, JniErrors> {
+            let value = env.get_field(options, name, "J")?.j()?;
+            usize::try_from(value).map_err(|_| JniErrors::InvalidArgument(format!("{name} cannot be negative, found {value}")))
+        };
+        let width = get_size("width")?;
+        let height = get_size("height")?;
+        let dpi = env.get_field(options, "dpi", "F")?.f()?;
+
+        if !dpi.is_finite() || dpi <= 0.0 {
+            return Err(JniErrors::InvalidArgument(format!("dpi should be positive, found {dpi}")));
+        }
+        Ok(SvgOptions { width, height, dpi })
+    }
+}
+
+/// Decode an encoded image held in memory, along with the timing of its
+/// frames and its ICC profile
+///
+/// RGB pixels are converted to the working space in `options`, `path` is
+/// only used to give errors more context
+pub fn decode_bytes(data: &[u8], path: Option<&str>, options: &DecodeOptions) -> Result<Entry, JniErrors> {
+    let with_path = |err: JniErrors| match path {
+        Some(path) => err.with_path(path),
+        None => err
+    };
+    if data.is_empty() {
+        return Err(JniErrors::InvalidArgument("Cannot decode an empty buffer".to_string()));
+    }
+    let format = Format::guess(data);
+    let limits = &options.limits;
+
+    if !limits.is_unlimited() {
+        // Check the headers before the decoder allocates anything, an
+        // image whose headers can't be parsed can't be checked either
+        let info = probe::probe_bytes(data).map_err(with_path)?;
+        limits.check_info(&info).map_err(with_path)?;
+    }
+
+    let still = |image: Result<Image, JniErrors>| image.map(|image| (image, Animation::default()));
+
+    let decoded = match format {
+        Format::WebP => codecs::decode_webp(data, limits),
+        Format::Gif => codecs::decode_gif(data, limits),
+        Format::Tiff => still(codecs::decode_tiff(data, limits)),
+        Format::Raw => still(codecs::decode_raw(data, options.raw_float, limits)),
+        Format::Exr => still(codecs::decode_exr(data, None, limits)),
+        Format::Svg => still(codecs::decode_svg(data, &SvgOptions::default(), limits)),
+        _ => Image::read(data, limits.decoder_options())
+            .map(|image| {
+                // zune decodes animated PNGs but keeps their timing to itself
+                let animation = match format {
+                    Format::Zune(ImageFormat::PNG) if image.is_animated() => Animation::from_png(data),
+                    _ => Animation::default()
+                };
+                (image, animation)
+            })
+            .map_err(|e| JniErrors::from(e).with_format(format))
+    };
+    let (image, animation) = decoded.map_err(with_path)?;
+
+    let mut entry = Entry::new(image, animation);
+    icc::color_manage(&mut entry, data, format, options.working_space, limits.strict).map_err(with_path)?;
+    entry.metadata.exif = Exif::extract(data, format);
+    entry.metadata.xmp = Xmp::extract(data, format);
+    if format == Format::Zune(ImageFormat::JPEG) {
+        entry.metadata.iptc = Iptc::extract_jpeg(data);
+    }
+    if options.apply_orientation {
+        metadata::apply_orientation(&mut entry).map_err(with_path)?;
+    }
+    Ok(entry)
+}
+
+/// Decode the image stored at `path`, see [`decode_bytes`]
+///
+/// Unlike `Image::open` this keeps track of the path and detected format,
+/// so that errors can report them back to Kotlin
+pub fn open_image(path: &str, options: &DecodeOptions) -> Result<Entry, JniErrors> {
+    let io_err = |e: std::io::Error| JniErrors::from(e).with_path(path);
+
+    let size = std::fs::metadata(path).map_err(io_err)?.len();
+    options.limits.check_file_size(size).map_err(|e| e.with_path(path))?;
+
+    let data = std::fs::read(path).map_err(io_err)?;
+
+    let mut entry = decode_bytes(&data, Some(path), options)?;
+    // formats that can't embed XMP keep it in a sidecar
+    if entry.metadata.xmp.is_none() {
+        entry.metadata.xmp = Xmp::read_sidecar(path);
+    }
+    Ok(entry)
+}
+
+/// Return the address of a direct byte buffer and its `position()..limit()` bounds
+fn direct_buffer_region(env: &mut JNIEnv, buffer: &JByteBuffer) -> Result<(*mut u8, usize, usize), JniErrors> {
+    let address = env
+        .get_direct_buffer_address(buffer)
+        .map_err(|_| JniErrors::InvalidArgument("ByteBuffer must be a direct buffer".to_string()))?;
+    let capacity = env.get_direct_buffer_capacity(buffer)?;
+
+    let position = env.call_method(buffer, "position", "()I", &[])?.i()? as usize;
+    let limit = env.call_method(buffer, "limit", "()I", &[])?.i()? as usize;
+
+    if position > limit || limit > capacity {
+        return Err(JniErrors::InvalidArgument(format!(
+            "Buffer position {position} and limit {limit} exceed capacity {capacity}"
+        )));
+    }
+    Ok((address, position, limit))
+}
+
+/// Return the readable region of a direct byte buffer, i.e. `position()..limit()`
+///
+/// # Safety
+/// The returned slice borrows the buffer's native memory, it must not outlive `buffer`
+/// and the buffer must not be modified from Java while the slice is alive
+pub unsafe fn direct_buffer_slice<'a>(env: &mut JNIEnv, buffer: &'a JByteBuffer) -> Result<&'a [u8], JniErrors> {
+    let (address, position, limit) = direct_buffer_region(env, buffer)?;
+    Ok(std::slice::from_raw_parts(address.add(position), limit - position))
+}
+
+/// Return the writable region of a direct byte buffer, i.e. `position()..limit()`
+///
+/// # Safety
+/// Same as [`direct_buffer_slice`], additionally Java must not read the buffer
+/// while the slice is alive
+pub unsafe fn direct_buffer_slice_mut<'a>(
+    env: &mut JNIEnv, buffer: &'a mut JByteBuffer
+) -> Result<&'a mut [u8], JniErrors> {
+    let (address, position, limit) = direct_buffer_region(env, buffer)?;
+    Ok(std::slice::from_raw_parts_mut(address.add(position), limit - position))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_loadImageFromBytesNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, bytes: JByteArray) {
+    jni_guard(&mut env, "loadImageFromBytesNative", |env| {
+        let data = env.convert_byte_array(&bytes)?;
+        let entry = decode_bytes(&data, None, &DecodeOptions::default())?;
+        handles::replace(image_ptr, entry)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_loadImageFromBufferNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, buffer: JByteBuffer) {
+    jni_guard(&mut env, "loadImageFromBufferNative", |env| {
+        // decode straight from the buffer's memory, no copy
+        let data = unsafe { direct_buffer_slice(env, &buffer)? };
+        let entry = decode_bytes(data, None, &DecodeOptions::default())?;
+        handles::replace(image_ptr, entry)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_loadImageWithOptionsNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, filename: JString, options: JObject) {
+    jni_guard(&mut env, "loadImageWithOptionsNative", |env| {
+        let options = DecodeOptions::from_java(env, &options)?;
+        let path = get_string(env, &filename)?;
+        let entry = open_image(&path, &options)?;
+        handles::replace(image_ptr, entry)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_loadExrLayerNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, filename: JString, layer: JString, options: JObject) {
+    jni_guard(&mut env, "loadExrLayerNative", |env| {
+        let limits = match options.is_null() {
+            true => DecodeLimits::default(),
+            false => DecodeLimits::from_java(env, &options)?
+        };
+        let path = get_string(env, &filename)?;
+        let layer = get_string(env, &layer)?;
+
+        let size = std::fs::metadata(&path).map_err(|e| JniErrors::from(e).with_path(&path))?.len();
+        limits.check_file_size(size).map_err(|e| e.with_path(&path))?;
+
+        let data = std::fs::read(&path).map_err(|e| JniErrors::from(e).with_path(&path))?;
+        let im = codecs::decode_exr(&data, Some(&layer), &limits).map_err(|e| e.with_path(&path))?;
+        handles::replace(image_ptr, Entry::new(im, Animation::default()))?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_loadPsdLayerNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, filename: JString, index: jint, options: JObject) {
+    jni_guard(&mut env, "loadPsdLayerNative", |env| {
+        let options = match options.is_null() {
+            true => DecodeOptions::default(),
+            false => DecodeOptions::from_java(env, &options)?
+        };
+        let limits = &options.limits;
+        let path = get_string(env, &filename)?;
+        let index = usize::try_from(index).map_err(|_| JniErrors::InvalidArgument(format!("Negative layer index {index}")))?;
+
+        let size = std::fs::metadata(&path).map_err(|e| JniErrors::from(e).with_path(&path))?.len();
+        limits.check_file_size(size).map_err(|e| e.with_path(&path))?;
+
+        let data = std::fs::read(&path).map_err(|e| JniErrors::from(e).with_path(&path))?;
+        let im = codecs::decode_psd_layer(&data, index, limits).map_err(|e| e.with_path(&path))?;
+
+        // layers share the document's profile
+        let mut entry = Entry::new(im, Animation::default());
+        icc::color_manage(&mut entry, &data, Format::Zune(ImageFormat::PSD), options.working_space, limits.strict)
+            .map_err(|e| e.with_path(&path))?;
+        handles::replace(image_ptr, entry)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_loadSvgNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, filename: JString, options: JObject, decode_options: JObject) {
+    jni_guard(&mut env, "loadSvgNative", |env| {
+        let options = SvgOptions::from_java(env, &options)?;
+        let limits = match decode_options.is_null() {
+            true => DecodeLimits::default(),
+            false => DecodeLimits::from_java(env, &decode_options)?
+        };
+        let path = get_string(env, &filename)?;
+
+        let size = std::fs::metadata(&path).map_err(|e| JniErrors::from(e).with_path(&path))?.len();
+        limits.check_file_size(size).map_err(|e| e.with_path(&path))?;
+
+        let data = std::fs::read(&path).map_err(|e| JniErrors::from(e).with_path(&path))?;
+        let im = codecs::decode_svg(&data, &options, &limits).map_err(|e| e.with_path(&path))?;
+        handles::replace(image_ptr, Entry::new(im, Animation::default()))?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_loadSvgFromBytesNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, bytes: JByteArray, options: JObject, decode_options: JObject) {
+    jni_guard(&mut env, "loadSvgFromBytesNative", |env| {
+        let options = SvgOptions::from_java(env, &options)?;
+        let limits = match decode_options.is_null() {
+            true => DecodeLimits::default(),
+            false => DecodeLimits::from_java(env, &decode_options)?
+        };
+        limits.check_file_size(env.get_array_length(&bytes)? as u64)?;
+
+        let data = env.convert_byte_array(&bytes)?;
+        let im = codecs::decode_svg(&data, &options, &limits)?;
+        handles::replace(image_ptr, Entry::new(im, Animation::default()))?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_loadImageFromBytesWithOptionsNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, bytes: JByteArray, options: JObject) {
+    jni_guard(&mut env, "loadImageFromBytesWithOptionsNative", |env| {
+        let options = DecodeOptions::from_java(env, &options)?;
+        // the encoded bytes count against the budget too
+        options.limits.check_file_size(env.get_array_length(&bytes)? as u64)?;
+
+        let data = env.convert_byte_array(&bytes)?;
+        let entry = decode_bytes(&data, None, &options)?;
+        handles::replace(image_ptr, entry)?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_loadImageFromBufferWithOptionsNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, buffer: JByteBuffer, options: JObject) {
+    jni_guard(&mut env, "loadImageFromBufferWithOptionsNative", |env| {
+        let options = DecodeOptions::from_java(env, &options)?;
+        let data = unsafe { direct_buffer_slice(env, &buffer)? };
+        let entry = decode_bytes(data, None, &options)?;
+        handles::replace(image_ptr, entry)?;
+        Ok(())
+    })
+}
@@ -0,0 +1,576 @@
+//! Encoding images with caller supplied options
+//!
+//! Every save path goes through [`encode`], options Kotlin doesn't
+//! set fall back to [`EncodeOptions::default`], which matches the defaults
+//! of `ZilEncodeOptions`.
+//!
+//! GIF, WebP and PNG keep every frame of an animation, other formats
+//! store the active frame.
+//!
+//! With `keepMetadata` set, JPEG, PNG, WebP and JPEG-XL files carry the
+//! EXIF and XMP of the image they were loaded from, JPEG files its IPTC
+//! records too. Other formats get their XMP written to a sidecar.
+use std::borrow::Cow;
+use std::path::Path;
+
+use jni::objects::{JByteBuffer, JClass, JIntArray, JLongArray, JObject, JString};
+use jni::sys::{jbyteArray, jlong};
+use jni::JNIEnv;
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_core::options::EncoderOptions;
+use zune_image::codecs::ImageFormat;
+use zune_image::core_filters::colorspace::ColorspaceConv;
+use zune_image::core_filters::depth::Depth;
+use zune_image::errors::{ImageErrors, ImgEncodeErrors};
+use zune_image::frame::Frame;
+use zune_image::image::Image;
+use zune_image::traits::{EncoderTrait, OperationsTrait};
+
+use crate::animation::{self, Animation};
+use crate::codecs::{
+    BmpEncoder, ExrEncoder, GifEncoder, IcoEncoder, JpegEncoder, PngEncoder, PngFilter, WebPEncoder, MAX_EXIF_LENGTH, MAX_ICON_SIZE,
+    MAX_XMP_LENGTH
+};
+use crate::decoder::direct_buffer_slice_mut;
+use crate::containers;
+use crate::errors::{jni_guard, JniErrors};
+use crate::format::Format;
+use crate::handles::Entry;
+use crate::icc;
+use crate::metadata::{ExifGroup, Metadata};
+use crate::xmp;
+use crate::{get_string, handles, im_long_to_format};
+
+/// Offset of the colorspace byte in a QOI header
+const QOI_COLORSPACE_OFFSET: usize = 13;
+
+/// Options for the encoders, mirrors `ZilEncodeOptions` in Kotlin
+///
+/// Each format only looks at the options prefixed with its name,
+/// except `quality` which is used by JPEG and WebP.
+#[derive(Debug, Clone)]
+pub struct EncodeOptions {
+    /// JPEG and WebP quality, 1-100
+    pub quality: u8,
+    /// Write a progressive JPEG
+    pub progressive: bool,
+    /// zlib compression level, 0 (none) - 9 (smallest)
+    pub png_compression: u8,
+    pub png_filter: PngFilter,
+    /// How hard the JPEG-XL encoder looks for a smaller file, 0-127
+    pub jxl_effort: u8,
+    pub jxl_lossless: bool,
+    /// Mark QOI pixels as linear rather than sRGB
+    pub qoi_linear: bool,
+    /// 3 or 4, zero keeps alpha only if the image has it
+    pub qoi_channels: u8,
+    /// 24 or 32, zero picks 32 only if the image has alpha
+    pub bmp_bits_per_pixel: u16,
+    pub webp_lossless: bool,
+    /// libwebp method, 0 (fastest) - 6 (smallest)
+    pub webp_method: u8,
+    /// Palette quantizer speed, 1 (best) - 30 (fastest)
+    pub gif_speed: u8,
+    /// Edge lengths of the entries in an icon, 1-256
+    pub ico_sizes: Vec<u32>,
+    /// Store EXR channels as 16 bit half floats rather than 32 bit floats
+    pub exr_half: bool,
+    /// Write the image's EXIF, XMP and IPTC to formats that can store them,
+    /// off unless asked for since they may hold locations and serial numbers
+    pub keep_metadata: bool
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptions {
+            quality: 80,
+            progressive: false,
+            png_compression: 6,
+            png_filter: PngFilter::Adaptive,
+            jxl_effort: 4,
+            jxl_lossless: true,
+            qoi_linear: false,
+            qoi_channels: 0,
+            bmp_bits_per_pixel: 0,
+            webp_lossless: false,
+            webp_method: 4,
+            gif_speed: 10,
+            ico_sizes: vec![16, 32, 48, 256],
+            exr_half: false,
+            keep_metadata: false
+        }
+    }
+}
+
+impl EncodeOptions {
+    /// Read options from a Kotlin `ZilEncodeOptions` object
+    pub fn from_java(env: &mut JNIEnv, options: &JObject) -> Result<EncodeOptions, JniErrors> {
+        let mut get_int = |name: &'static str, min: i32, max: i32| -> Result<i32, JniErrors> {
+            let value = env.get_field(options, name, "I")?.i()?;
+            if !(min..=max).contains(&value) {
+                return Err(JniErrors::InvalidArgument(format!(
+                    "{name} should be between {min} and {max}, found {value}"
+                )));
+            }
+            Ok(value)
+        };
+        let quality = get_int("quality", 1, 100)? as u8;
+        let png_compression = get_int("pngCompression", 0, 9)? as u8;
+        let jxl_effort = get_int("jxlEffort", 0, 127)? as u8;
+        let qoi_channels = get_int("qoiChannels", 0, 4)? as u8;
+        let bmp_bits_per_pixel = get_int("bmpBitsPerPixel", 0, 32)? as u16;
+        let webp_method = get_int("webpMethod", 0, 6)? as u8;
+        let gif_speed = get_int("gifSpeed", 1, 30)? as u8;
+
+        if !matches!(qoi_channels, 0 | 3 | 4) {
+            return Err(JniErrors::InvalidArgument(format!("qoiChannels should be 0, 3 or 4, found {qoi_channels}")));
+        }
+        if !matches!(bmp_bits_per_pixel, 0 | 24 | 32) {
+            return Err(JniErrors::InvalidArgument(format!(
+                "bmpBitsPerPixel should be 0, 24 or 32, found {bmp_bits_per_pixel}"
+            )));
+        }
+
+        let filter = env.get_field(options, "pngFilter", "LZilPngFilter;")?.l()?;
+        if filter.is_null() {
+            return Err(JniErrors::InvalidArgument("pngFilter should not be null".to_string()));
+        }
+        let ordinal = env.call_method(&filter, "ordinal", "()I", &[])?.i()?;
+        let png_filter = PngFilter::from_ordinal(ordinal)
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown png filter {ordinal}")))?;
+
+        let sizes = JIntArray::from(env.get_field(options, "icoSizes", "[I")?.l()?);
+        if sizes.is_null() {
+            return Err(JniErrors::InvalidArgument("icoSizes should not be null".to_string()));
+        }
+        let mut ico_sizes = vec![0; env.get_array_length(&sizes)? as usize];
+        env.get_int_array_region(&sizes, 0, &mut ico_sizes)?;
+
+        if ico_sizes.is_empty() || ico_sizes.iter().any(|size| !(1..=MAX_ICON_SIZE as i32).contains(size)) {
+            return Err(JniErrors::InvalidArgument(format!(
+                "icoSizes should hold sizes between 1 and {MAX_ICON_SIZE}, found {ico_sizes:?}"
+            )));
+        }
+
+        Ok(EncodeOptions {
+            quality,
+            progressive: env.get_field(options, "progressive", "Z")?.z()?,
+            png_compression,
+            png_filter,
+            jxl_effort,
+            jxl_lossless: env.get_field(options, "jxlLossless", "Z")?.z()?,
+            qoi_linear: env.get_field(options, "qoiLinear", "Z")?.z()?,
+            qoi_channels,
+            bmp_bits_per_pixel,
+            webp_lossless: env.get_field(options, "webpLossless", "Z")?.z()?,
+            webp_method,
+            gif_speed,
+            ico_sizes: ico_sizes.iter().map(|size| *size as u32).collect(),
+            exr_half: env.get_field(options, "exrHalf", "Z")?.z()?,
+            keep_metadata: env.get_field(options, "keepMetadata", "Z")?.z()?
+        })
+    }
+
+    /// Read options from a nullable `ZilEncodeOptions`, null gives the defaults
+    pub fn from_java_or_default(env: &mut JNIEnv, options: &JObject) -> Result<EncodeOptions, JniErrors> {
+        if options.is_null() {
+            return Ok(EncodeOptions::default());
+        }
+        EncodeOptions::from_java(env, options)
+    }
+
+    /// The subset of options zune's own encoders understand
+    fn encoder_options(&self) -> EncoderOptions {
+        EncoderOptions::default()
+            .set_quality(self.quality)
+            .set_effort(self.jxl_effort)
+    }
+}
+
+/// Convert `image` to `colorspace` if it isn't in it already
+fn to_colorspace(image: &Image, colorspace: ColorSpace) -> Result<Cow<'_, Image>, ImageErrors> {
+    if image.colorspace() == colorspace {
+        return Ok(Cow::Borrowed(image));
+    }
+    let mut image = image.clone();
+    ColorspaceConv::new(colorspace).execute(&mut image)?;
+    Ok(Cow::Owned(image))
+}
+
+fn encode_qoi(image: &Image, options: &EncodeOptions) -> Result<Vec<u8>, ImageErrors> {
+    let keep_alpha = match options.qoi_channels {
+        3 => false,
+        4 => true,
+        _ => image.colorspace().has_alpha()
+    };
+    let colorspace = if keep_alpha { ColorSpace::RGBA } else { ColorSpace::RGB };
+    let image = to_colorspace(image, colorspace)?;
+
+    let mut encoder = ImageFormat::QOI
+        .get_encoder_with_options(options.encoder_options())
+        .ok_or(ImgEncodeErrors::NoEncoderForFormat(ImageFormat::QOI))?;
+    let mut encoded = encoder.encode(&image)?;
+
+    // zune-image gives us no way to set this, it's a single byte in the header
+    if options.qoi_linear {
+        if let Some(byte) = encoded.get_mut(QOI_COLORSPACE_OFFSET) {
+            *byte = 1;
+        }
+    }
+    Ok(encoded)
+}
+
+/// Metadata to embed in an encoded file, serialized the way the formats store it
+#[derive(Default)]
+struct Embedded<'a> {
+    icc: Option<&'a [u8]>,
+    /// EXIF as a TIFF structure
+    exif: Option<Vec<u8>>,
+    /// An XMP packet
+    xmp: Option<Vec<u8>>,
+    /// A JPEG `APP13` segment holding IPTC records
+    iptc: Option<Vec<u8>>
+}
+
+/// Add `Exif` and `xml ` boxes to a JPEG-XL codestream, wrapping it in the container format
+///
+/// zune writes bare codestreams, which have no place for metadata
+fn jxl_with_metadata(codestream: Vec<u8>, embedded: &Embedded) -> Vec<u8> {
+    if !codestream.starts_with(&[0xFF, 0x0A]) || (embedded.exif.is_none() && embedded.xmp.is_none()) {
+        return codestream;
+    }
+    let mut output = containers::JXL_SIGNATURE_BOX.to_vec();
+    output.extend(containers::bmff_box(b"ftyp", b"jxl \0\0\0\0jxl "));
+    if let Some(exif) = &embedded.exif {
+        // the payload starts with the offset of the TIFF header within the rest of the box
+        output.extend(containers::bmff_box(b"Exif", &[&[0; 4], exif.as_slice()].concat()));
+    }
+    if let Some(xmp) = &embedded.xmp {
+        output.extend(containers::bmff_box(b"xml ", xmp));
+    }
+    output.extend(containers::bmff_box(b"jxlc", &codestream));
+    output
+}
+
+fn encode_zune(image: &Image, animation: &Animation, embedded: &Embedded, format: ImageFormat, options: &EncodeOptions) -> Result<Vec<u8>, JniErrors> {
+    let icc = embedded.icc.map(<[u8]>::to_vec);
+    let encoded = match format {
+        ImageFormat::JPEG => JpegEncoder::new(options.encoder_options(), options.progressive)
+            .icc_profile(icc)
+            .exif(embedded.exif.clone())
+            .xmp(embedded.xmp.clone())
+            .iptc(embedded.iptc.clone())
+            .encode(image),
+        ImageFormat::PNG => PngEncoder::new(options.png_compression, options.png_filter)
+            .animated(animation.clone())
+            .icc_profile(icc)
+            .exif(embedded.exif.clone())
+            .xmp(embedded.xmp.clone())
+            .encode(image),
+        ImageFormat::BMP => {
+            let bits_per_pixel = match options.bmp_bits_per_pixel {
+                0 if image.colorspace().has_alpha() => 32,
+                0 => 24,
+                bits => bits
+            };
+            BmpEncoder::new(bits_per_pixel).encode(image)
+        }
+        ImageFormat::QOI => encode_qoi(image, options),
+        ImageFormat::JPEG_XL if !options.jxl_lossless => {
+            return Err(JniErrors::Unsupported {
+                reason: "The JPEG-XL encoder only supports lossless encoding".to_string(),
+                format: Some(format!("{format:?}"))
+            });
+        }
+        _ => match format.get_encoder_with_options(options.encoder_options()) {
+            Some(mut encoder) => encoder.encode(image),
+            None => Err(ImgEncodeErrors::NoEncoderForFormat(format).into())
+        }
+    };
+    match format {
+        ImageFormat::JPEG_XL => Ok(jxl_with_metadata(encoded?, embedded)),
+        _ => Ok(encoded?)
+    }
+}
+
+fn encode_inner(image: &Image, animation: &Animation, embedded: &Embedded, format: Format, options: &EncodeOptions) -> Result<Vec<u8>, JniErrors> {
+    let encoded = match format {
+        Format::Zune(format) => return encode_zune(image, animation, embedded, format, options),
+        Format::WebP => WebPEncoder::new(options.webp_lossless, options.quality, options.webp_method)
+            .animated(animation.clone())
+            .icc_profile(embedded.icc.map(<[u8]>::to_vec))
+            .exif(embedded.exif.clone())
+            .xmp(embedded.xmp.clone())
+            .encode(image),
+        Format::Gif => GifEncoder::new(options.gif_speed).animated(animation.clone()).encode(image),
+        Format::Ico => IcoEncoder::new(&options.ico_sizes).encode(image),
+        Format::Exr => ExrEncoder::new(options.exr_half).encode(image),
+        Format::Tiff | Format::Raw | Format::Svg => {
+            return Err(JniErrors::Unsupported {
+                reason: format!("{format} images can only be decoded"),
+                format: Some(format.to_string())
+            })
+        }
+    };
+    Ok(encoded?)
+}
+
+/// Whether `format` can store every frame of an animation
+fn keeps_frames(format: Format) -> bool {
+    matches!(format, Format::Gif | Format::WebP | Format::Zune(ImageFormat::PNG))
+}
+
+/// Encode `image` as `format`, formats that can store animations write every frame timed by `animation`
+///
+/// JPEG, PNG and WebP files embed the ICC profile of the pixels, unless
+/// it was made for other channels than the image has. When asked to keep
+/// metadata, the EXIF is updated to describe the encoded pixels and
+/// embedded in formats that can store it, along with XMP and IPTC
+pub fn encode(image: &Image, animation: &Animation, metadata: &Metadata, format: Format, options: &EncodeOptions) -> Result<Vec<u8>, JniErrors> {
+    let mut embedded = Embedded {
+        icc: metadata.icc.as_deref().filter(|profile| icc::matches(profile, image.colorspace())),
+        ..Embedded::default()
+    };
+    if options.keep_metadata {
+        if let Some(exif) = &metadata.exif {
+            let (width, height) = image.dimensions();
+            let mut exif = exif.for_image(width, height);
+            let mut tiff = exif.to_tiff()?;
+            // JPEG segments hold less than 64K, camera specific data is what usually overflows them
+            if format == Format::Zune(ImageFormat::JPEG) && tiff.len() > MAX_EXIF_LENGTH {
+                exif.remove_group(ExifGroup::MakerNote);
+                tiff = exif.to_tiff()?;
+            }
+            embedded.exif = Some(tiff);
+        }
+        embedded.xmp = match format {
+            Format::Zune(ImageFormat::JPEG) => metadata.xmp.as_ref().and_then(|xmp| xmp.to_packet_within(MAX_XMP_LENGTH)),
+            _ => metadata.xmp.as_ref().map(xmp::Xmp::to_packet)
+        };
+        embedded.iptc = metadata.iptc.as_ref().map(|iptc| iptc.to_jpeg_segment());
+    }
+    encode_inner(image, animation, &embedded, format, options).map_err(|e| e.with_format(format))
+}
+
+/// Encode the image of a handle, formats that can't store animations get its active frame
+pub fn encode_entry(entry: &Entry, format: Format, options: &EncodeOptions) -> Result<Vec<u8>, JniErrors> {
+    if entry.image.frames_len() > 1 && !keeps_frames(format) {
+        let frame = animation::frame_image(&entry.image, entry.active_frame)?;
+        return encode(&frame, &Animation::default(), &entry.metadata, format, options);
+    }
+    encode(&entry.image, &entry.animation, &entry.metadata, format, options)
+}
+
+/// Encode the image behind `image_ptr` to a format id from Kotlin
+fn encode_handle(env: &mut JNIEnv, image_ptr: jlong, format: jlong, options: &JObject) -> Result<Vec<u8>, JniErrors> {
+    let format = im_long_to_format(format)
+        .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown image format {format}")))?;
+    let options = EncodeOptions::from_java_or_default(env, options)?;
+
+    handles::with_entry(image_ptr, |entry| encode_entry(entry, format, &options))?
+}
+
+/// Gather the active frame of each handle into one animation, as 8 bit RGBA,
+/// along with the ICC profile the frames share
+///
+/// Every frame must have the dimensions of the first one
+fn frames_from_handles(image_ptrs: &[jlong]) -> Result<(Image, Option<Vec<u8>>), JniErrors> {
+    let mut frames = Vec::with_capacity(image_ptrs.len());
+    let mut profiles = Vec::with_capacity(image_ptrs.len());
+    let mut dimensions = None;
+
+    for (index, image_ptr) in image_ptrs.iter().enumerate() {
+        let (mut image, icc) = handles::with_entry(*image_ptr, |entry| {
+            animation::frame_image(&entry.image, entry.active_frame).map(|image| (image, entry.metadata.icc.clone()))
+        })??;
+        profiles.push(icc);
+
+        let (width, height) = image.dimensions();
+        let (expected_width, expected_height) = *dimensions.get_or_insert((width, height));
+
+        if (width, height) != (expected_width, expected_height) {
+            return Err(JniErrors::InvalidArgument(format!(
+                "Frame {index} is {width}x{height}, every frame should be {expected_width}x{expected_height}"
+            )));
+        }
+        Depth::new(BitDepth::Eight).execute(&mut image)?;
+        ColorspaceConv::new(ColorSpace::RGBA).execute(&mut image)?;
+        frames.push(std::mem::replace(&mut image.frames_mut()[0], Frame::new(vec![])));
+    }
+    let (width, height) = dimensions.ok_or_else(|| JniErrors::InvalidArgument("An animation needs at least one frame".to_string()))?;
+
+    let image = Image::new_frames(frames, BitDepth::Eight, width, height, ColorSpace::RGBA);
+    // frames in different color spaces can't be described by one profile
+    let icc = match profiles.iter().all(|icc| *icc == profiles[0]) {
+        true => profiles.swap_remove(0),
+        false => None
+    };
+    Ok((image, icc))
+}
+
+/// Encode the handles in `image_ptrs` as the frames of an animation
+fn encode_animation(env: &mut JNIEnv, image_ptrs: &JLongArray, delays: &JIntArray, loop_count: jlong, format: jlong, options: &JObject) -> Result<Vec<u8>, JniErrors> {
+    let format = im_long_to_format(format)
+        .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown image format {format}")))?;
+    if !keeps_frames(format) {
+        return Err(JniErrors::Unsupported {
+            reason: "Animations can only be written as GIF, PNG or WebP".to_string(),
+            format: Some(format.to_string())
+        });
+    }
+    let options = EncodeOptions::from_java_or_default(env, options)?;
+
+    let mut ptrs = vec![0; env.get_array_length(image_ptrs)? as usize];
+    env.get_long_array_region(image_ptrs, 0, &mut ptrs)?;
+    let mut values = vec![0; env.get_array_length(delays)? as usize];
+    env.get_int_array_region(delays, 0, &mut values)?;
+
+    if values.len() != ptrs.len() || values.iter().any(|delay| *delay < 0) {
+        return Err(JniErrors::InvalidArgument(format!(
+            "Expected {} non-negative frame delays, found {values:?}",
+            ptrs.len()
+        )));
+    }
+    let loop_count = u16::try_from(loop_count)
+        .map_err(|_| JniErrors::InvalidArgument(format!("Loop count should be between 0 and 65535, found {loop_count}")))?;
+
+    let (image, icc) = frames_from_handles(&ptrs)?;
+    let animation = Animation {
+        delays: values.iter().map(|delay| *delay as u32).collect(),
+        loop_count: u32::from(loop_count)
+    };
+    // the frames may come from several files, so no single EXIF describes them
+    let metadata = Metadata {
+        icc,
+        ..Metadata::default()
+    };
+    encode(&image, &animation, &metadata, format, &options)
+}
+
+/// Find the format to encode to from a file extension
+pub fn format_for_extension(extension: &str) -> Option<Format> {
+    if let Some((format, _)) = ImageFormat::get_encoder_for_extension(extension) {
+        return Some(format.into());
+    }
+    // formats zune has no encoder for but we do
+    match extension {
+        "bmp" => Some(ImageFormat::BMP.into()),
+        "webp" => Some(Format::WebP),
+        "gif" => Some(Format::Gif),
+        "ico" => Some(Format::Ico),
+        "exr" => Some(Format::Exr),
+        _ => None
+    }
+}
+
+/// Encode the image of a handle to `path` using `format`
+///
+/// When keeping metadata, XMP the format can't embed is merged into a sidecar next to the file
+pub fn save_entry(entry: &Entry, path: &str, format: Format, options: &EncodeOptions) -> Result<(), JniErrors> {
+    let contents = encode_entry(entry, format, options).map_err(|e| e.with_path(path))?;
+
+    std::fs::write(path, contents).map_err(|e| JniErrors::from(e).with_path(path))?;
+    match &entry.metadata.xmp {
+        Some(xmp) if options.keep_metadata && !xmp::embeds_xmp(format) => xmp.merge_into_sidecar(path),
+        _ => Ok(())
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_saveNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, filename: JString) {
+    jni_guard(&mut env, "saveNative", |env| {
+        let input_str = get_string(env, &filename)?;
+        let extension = Path::new(&input_str)
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("No extension for file {input_str}")))?;
+
+        let format = format_for_extension(&extension.to_ascii_lowercase())
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("No encoder for extension {extension}")))?;
+
+        let options = EncodeOptions::default();
+        handles::with_entry(image_ptr, |entry| save_entry(entry, &input_str, format, &options))??;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_saveToNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, filename: JString, format: jlong) {
+    jni_guard(&mut env, "saveToNative", |env| {
+        let input_str = get_string(env, &filename)?;
+        let format = im_long_to_format(format)
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown image format {format}")))?;
+
+        let options = EncodeOptions::default();
+        handles::with_entry(image_ptr, |entry| save_entry(entry, &input_str, format, &options))??;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_saveToWithOptionsNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, filename: JString, format: jlong, options: JObject) {
+    jni_guard(&mut env, "saveToWithOptionsNative", |env| {
+        let input_str = get_string(env, &filename)?;
+        let format = im_long_to_format(format)
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown image format {format}")))?;
+        let options = EncodeOptions::from_java(env, &options)?;
+
+        handles::with_entry(image_ptr, |entry| save_entry(entry, &input_str, format, &options))??;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_encodeNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, format: jlong, options: JObject) -> jbyteArray {
+    jni_guard(&mut env, "encodeNative", |env| {
+        let encoded = encode_handle(env, image_ptr, format, &options)?;
+        Ok(env.byte_array_from_slice(&encoded)?)
+    })
+    .into_raw()
+}
+
+/// Encode into the `position()..limit()` region of a direct buffer, returning
+/// the number of bytes written
+///
+/// If the encoded image doesn't fit nothing is written and an
+/// `InvalidArgument` error stating the required size is raised
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_encodeToBufferNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, format: jlong, options: JObject, mut buffer: JByteBuffer) -> jlong {
+    jni_guard(&mut env, "encodeToBufferNative", |env| {
+        let encoded = encode_handle(env, image_ptr, format, &options)?;
+
+        // safety: the slice is dropped before we return to the jvm
+        let output = unsafe { direct_buffer_slice_mut(env, &mut buffer)? };
+        if output.len() < encoded.len() {
+            return Err(JniErrors::InvalidArgument(format!(
+                "Buffer too small, the encoded image needs {} bytes but only {} are available",
+                encoded.len(),
+                output.len()
+            )));
+        }
+        output[..encoded.len()].copy_from_slice(&encoded);
+        Ok(encoded.len() as jlong)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_encodeAnimationNative(mut env: JNIEnv, _class: JClass, image_ptrs: JLongArray, delays: JIntArray, loop_count: jlong, format: jlong, options: JObject) -> jbyteArray {
+    jni_guard(&mut env, "encodeAnimationNative", |env| {
+        let encoded = encode_animation(env, &image_ptrs, &delays, loop_count, format, &options)?;
+        Ok(env.byte_array_from_slice(&encoded)?)
+    })
+    .into_raw()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_saveAnimationNative(mut env: JNIEnv, _class: JClass, filename: JString, image_ptrs: JLongArray, delays: JIntArray, loop_count: jlong, format: jlong, options: JObject) {
+    jni_guard(&mut env, "saveAnimationNative", |env| {
+        let input_str = get_string(env, &filename)?;
+        let contents = encode_animation(env, &image_ptrs, &delays, loop_count, format, &options).map_err(|e| e.with_path(&input_str))?;
+
+        std::fs::write(&input_str, contents).map_err(|e| JniErrors::from(e).with_path(&input_str))
+    })
+}
+
+
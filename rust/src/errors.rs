@@ -0,0 +1,317 @@
+//! Errors possible when servicing a call from the JVM
+//!
+//! Every export returns one of these to [`jni_guard`], which is the only
+//! place that converts them into Java exceptions.
+//!
+//! Each error maps to one of the exception classes declared in
+//! `ZilImageExceptions.kt`, the constructor signatures below must match
+//! the ones declared there.
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+
+use jni::objects::{JObject, JThrowable, JValue};
+use jni::JNIEnv;
+use zune_image::errors::{ImageErrors, ImgEncodeErrors};
+
+use crate::format::Format;
+use crate::handles::HandleErrors;
+
+const BASE_EXCEPTION: &str = "ZilImageException";
+const DECODE_EXCEPTION: &str = "ZilDecodeException";
+const ENCODE_EXCEPTION: &str = "ZilEncodeException";
+const UNSUPPORTED_EXCEPTION: &str = "ZilUnsupportedException";
+const IO_EXCEPTION: &str = "ZilIoException";
+const DIMENSIONS_EXCEPTION: &str = "ZilInvalidDimensionsException";
+const ARGUMENT_EXCEPTION: &str = "ZilInvalidArgumentException";
+const HANDLE_EXCEPTION: &str = "ZilInvalidHandleException";
+const PANIC_EXCEPTION: &str = "ZilNativePanicException";
+const LIMIT_EXCEPTION: &str = "ZilLimitExceededException";
+
+/// Extra information attached to image errors
+///
+/// This is what fills the `format` and `path` fields of the
+/// Java exceptions
+#[derive(Debug, Default, Clone)]
+pub struct ErrorContext {
+    pub format: Option<String>,
+    pub path: Option<String>
+}
+
+/// All errors that can be surfaced to Kotlin
+#[derive(Debug)]
+pub enum JniErrors {
+    /// The image handle could not be resolved
+    Handle(HandleErrors),
+    /// zune-image failed to decode, encode or process the image
+    Image(ImageErrors, ErrorContext),
+    /// An argument passed from Kotlin was invalid
+    InvalidArgument(String),
+    /// The request is valid but not something we can do, e.g. an encoder option
+    /// the format does not support
+    Unsupported {
+        reason: String,
+        format: Option<String>
+    },
+    /// The image exceeds one of the configured decode limits
+    LimitExceeded {
+        limit: &'static str,
+        value: u64,
+        max:   u64
+    },
+    /// A JNI call failed, a Java exception may already be pending
+    Jni(jni::errors::Error),
+    /// The native code panicked
+    Panic(String),
+    /// Generic errors which have more context
+    Generic(String)
+}
+
+impl JniErrors {
+    /// Attach the file path being read or written to this error
+    pub fn with_path<P: AsRef<str>>(mut self, path: P) -> Self {
+        if let JniErrors::Image(_, ctx) = &mut self {
+            ctx.path = Some(path.as_ref().to_string());
+        }
+        self
+    }
+
+    /// Attach the image format being decoded or encoded to this error
+    pub fn with_format<F: Into<Format>>(mut self, format: F) -> Self {
+        if let JniErrors::Image(_, ctx) = &mut self {
+            ctx.format = Some(format.into().to_string());
+        }
+        self
+    }
+}
+
+impl Display for JniErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            JniErrors::Handle(err) => write!(f, "{err}"),
+            // zune errors end with a new line, strip it
+            JniErrors::Image(err, _) => write!(f, "{}", err.to_string().trim_end()),
+            JniErrors::InvalidArgument(msg) => write!(f, "Invalid argument: {msg}"),
+            JniErrors::Unsupported { reason, .. } => write!(f, "{reason}"),
+            JniErrors::LimitExceeded { limit, value, max } => {
+                write!(f, "Image exceeds {limit}, {value} is larger than the allowed {max}")
+            }
+            JniErrors::Jni(err) => write!(f, "JNI error: {err}"),
+            JniErrors::Panic(msg) => write!(f, "panicked: {msg}"),
+            JniErrors::Generic(msg) => write!(f, "{msg}")
+        }
+    }
+}
+
+impl std::error::Error for JniErrors {}
+
+impl From<HandleErrors> for JniErrors {
+    fn from(value: HandleErrors) -> Self {
+        JniErrors::Handle(value)
+    }
+}
+
+impl From<ImageErrors> for JniErrors {
+    fn from(value: ImageErrors) -> Self {
+        JniErrors::Image(value, ErrorContext::default())
+    }
+}
+
+impl From<std::io::Error> for JniErrors {
+    fn from(value: std::io::Error) -> Self {
+        JniErrors::from(ImageErrors::IoError(value))
+    }
+}
+
+impl From<jni::errors::Error> for JniErrors {
+    fn from(value: jni::errors::Error) -> Self {
+        JniErrors::Jni(value)
+    }
+}
+
+impl From<String> for JniErrors {
+    fn from(value: String) -> Self {
+        JniErrors::Generic(value)
+    }
+}
+
+impl From<&str> for JniErrors {
+    fn from(value: &str) -> Self {
+        JniErrors::Generic(value.to_string())
+    }
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(msg) = payload.downcast_ref::<&str>() {
+        return msg.to_string();
+    }
+    if let Some(msg) = payload.downcast_ref::<String>() {
+        return msg.clone();
+    }
+    "unknown panic payload".to_string()
+}
+
+fn new_nullable_string<'local>(
+    env: &mut JNIEnv<'local>, value: &Option<String>
+) -> jni::errors::Result<JObject<'local>> {
+    match value {
+        Some(value) => Ok(env.new_string(value)?.into()),
+        None => Ok(JObject::null())
+    }
+}
+
+/// Create the Java exception object matching `err`
+fn new_exception<'local>(
+    env: &mut JNIEnv<'local>, operation: &str, err: &JniErrors
+) -> jni::errors::Result<JThrowable<'local>> {
+    let reason = JObject::from(env.new_string(err.to_string())?);
+    let op = JObject::from(env.new_string(operation)?);
+    let (reason, op) = (JValue::Object(&reason), JValue::Object(&op));
+
+    let exception = match err {
+        JniErrors::Handle(handle_err) => {
+            let handle = match handle_err {
+                HandleErrors::Null => 0,
+                HandleErrors::Invalid(handle) | HandleErrors::Stale(handle) => *handle
+            };
+            env.new_object(
+                HANDLE_EXCEPTION,
+                "(Ljava/lang/String;Ljava/lang/String;J)V",
+                &[reason, op, JValue::Long(handle)]
+            )?
+        }
+        JniErrors::Image(image_err, ctx) => {
+            let format = new_nullable_string(env, &ctx.format)?;
+            let path = new_nullable_string(env, &ctx.path)?;
+            let (format, path) = (JValue::Object(&format), JValue::Object(&path));
+
+            match image_err {
+                ImageErrors::ImageDecodeErrors(_) => env.new_object(
+                    DECODE_EXCEPTION,
+                    "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;)V",
+                    &[reason, op, format, path]
+                )?,
+                ImageErrors::EncodeErrors(ImgEncodeErrors::NoEncoderForFormat(no_format)) => {
+                    let no_format = JObject::from(env.new_string(format!("{no_format:?}"))?);
+                    env.new_object(
+                        UNSUPPORTED_EXCEPTION,
+                        "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;)V",
+                        &[reason, op, JValue::Object(&no_format)]
+                    )?
+                }
+                ImageErrors::EncodeErrors(_) | ImageErrors::NoImageForEncoding => env.new_object(
+                    ENCODE_EXCEPTION,
+                    "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;)V",
+                    &[reason, op, format, path]
+                )?,
+                ImageErrors::ImageDecoderNotIncluded(_)
+                | ImageErrors::ImageDecoderNotImplemented(_)
+                | ImageErrors::UnsupportedColorspace(..)
+                | ImageErrors::ImageOperationNotImplemented(..) => env.new_object(
+                    UNSUPPORTED_EXCEPTION,
+                    "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;)V",
+                    &[reason, op, format]
+                )?,
+                ImageErrors::IoError(_) => env.new_object(
+                    IO_EXCEPTION,
+                    "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;)V",
+                    &[reason, op, path]
+                )?,
+                ImageErrors::DimensionsMisMatch(expected, found) => env.new_object(
+                    DIMENSIONS_EXCEPTION,
+                    "(Ljava/lang/String;Ljava/lang/String;JJ)V",
+                    &[reason, op, JValue::Long(*expected as _), JValue::Long(*found as _)]
+                )?,
+                _ => env.new_object(
+                    BASE_EXCEPTION,
+                    "(Ljava/lang/String;Ljava/lang/String;)V",
+                    &[reason, op]
+                )?
+            }
+        }
+        JniErrors::InvalidArgument(_) => env.new_object(
+            ARGUMENT_EXCEPTION,
+            "(Ljava/lang/String;Ljava/lang/String;)V",
+            &[reason, op]
+        )?,
+        JniErrors::Unsupported { format, .. } => {
+            let format = new_nullable_string(env, format)?;
+            env.new_object(
+                UNSUPPORTED_EXCEPTION,
+                "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;)V",
+                &[reason, op, JValue::Object(&format)]
+            )?
+        }
+        JniErrors::LimitExceeded { limit, value, max } => {
+            let limit = JObject::from(env.new_string(limit)?);
+            env.new_object(
+                LIMIT_EXCEPTION,
+                "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;JJ)V",
+                &[
+                    reason,
+                    op,
+                    JValue::Object(&limit),
+                    JValue::Long(*value as _),
+                    JValue::Long(*max as _)
+                ]
+            )?
+        }
+        JniErrors::Panic(_) => env.new_object(
+            PANIC_EXCEPTION,
+            "(Ljava/lang/String;Ljava/lang/String;)V",
+            &[reason, op]
+        )?,
+        JniErrors::Jni(_) | JniErrors::Generic(_) => env.new_object(
+            BASE_EXCEPTION,
+            "(Ljava/lang/String;Ljava/lang/String;)V",
+            &[reason, op]
+        )?
+    };
+    Ok(JThrowable::from(exception))
+}
+
+/// Throw `err` as a Java exception of the matching `ZilImageException` subclass
+///
+/// If the JVM already has an exception pending (e.g. a failed JNI call threw one)
+/// that exception is kept, since it carries more information than ours.
+pub fn throw_error(env: &mut JNIEnv, operation: &str, err: &JniErrors) {
+    if env.exception_check().unwrap_or(false) {
+        return;
+    }
+    match new_exception(env, operation, err) {
+        Ok(exception) => {
+            let _ = env.throw(exception);
+        }
+        Err(_) => {
+            // most likely the exception classes aren't on the class path,
+            // fall back to something that always exists
+            let _ = env.exception_clear();
+            let msg = format!("{operation}: {err}");
+            let _ = env.throw_new("java/lang/RuntimeException", msg);
+        }
+    }
+}
+
+/// Run the body of an exported function, converting errors and panics into Java exceptions
+///
+/// Unwinding across an `extern "system"` boundary aborts the JVM, so every
+/// `Java_ZilImageJni_*` export should route its body through this.
+///
+/// On failure an exception is raised in `env` and `R::default()` is returned,
+/// the JVM ignores the return value while an exception is pending.
+pub fn jni_guard<'local, R, F>(env: &mut JNIEnv<'local>, operation: &str, func: F) -> R
+where
+    R: Default,
+    F: FnOnce(&mut JNIEnv<'local>) -> Result<R, JniErrors>
+{
+    let result = catch_unwind(AssertUnwindSafe(|| func(env)))
+        .unwrap_or_else(|payload| Err(JniErrors::Panic(panic_message(payload.as_ref()))));
+
+    match result {
+        Ok(value) => value,
+        Err(err) => {
+            throw_error(env, operation, &err);
+            R::default()
+        }
+    }
+}
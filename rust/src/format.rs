@@ -0,0 +1,88 @@
+//! Image formats known to the bindings
+//!
+//! zune's [`ImageFormat`] is non-exhaustive and only covers the formats
+//! zune itself can handle, formats we ship our own codecs for get
+//! their own variants here.
+use std::fmt::{Display, Formatter};
+
+use zune_image::codecs::ImageFormat;
+
+use crate::codecs;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum Format {
+    /// A format handled by zune-image
+    Zune(ImageFormat),
+    WebP,
+    Gif,
+    Ico,
+    Tiff,
+    Exr,
+    /// Scalable vector graphics, rasterized on load
+    Svg,
+    /// Camera RAW files, most of which are TIFF based
+    Raw
+}
+
+impl Format {
+    /// Detect the format of an encoded image from its first bytes
+    ///
+    /// RAW files have no common signature, so TIFF files and unrecognised
+    /// data have their headers parsed to tell whether they are RAW files.
+    ///
+    /// Returns `Format::Zune(ImageFormat::Unknown)` if no format matches
+    pub fn guess(data: &[u8]) -> Format {
+        if let Some((format, _)) = ImageFormat::guess_format(data) {
+            return Format::Zune(format);
+        }
+        let format = match data {
+            [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] => Format::WebP,
+            [b'G', b'I', b'F', b'8', b'7' | b'9', b'a', ..] => Format::Gif,
+            [b'I', b'I', 42, 0, ..] | [b'M', b'M', 0, 42, ..] => Format::Tiff,
+            [0x76, 0x2f, 0x31, 0x01, ..] => Format::Exr,
+            // none of the other formats are gzip compressed, so this is an SVGZ
+            [0x1f, 0x8b, ..] => Format::Svg,
+            _ if is_svg(data) => Format::Svg,
+            _ => Format::Zune(ImageFormat::Unknown)
+        };
+        match format {
+            Format::Tiff | Format::Zune(ImageFormat::Unknown) if codecs::is_camera_raw(data) => Format::Raw,
+            format => format
+        }
+    }
+}
+
+/// Whether `data` looks like an SVG document, i.e. is XML with an `<svg` element near the start
+fn is_svg(data: &[u8]) -> bool {
+    let data = data.strip_prefix(b"\xEF\xBB\xBF").unwrap_or(data);
+    let start = data.iter().position(|c| !c.is_ascii_whitespace()).unwrap_or(data.len());
+    let data = &data[start..];
+
+    if !data.starts_with(b"<") {
+        return false;
+    }
+    // skip past any XML declaration, comments or doctype
+    let prefix = &data[..data.len().min(4096)];
+    prefix.windows(4).any(|w| w == b"<svg")
+}
+
+impl From<ImageFormat> for Format {
+    fn from(value: ImageFormat) -> Self {
+        Format::Zune(value)
+    }
+}
+
+impl Display for Format {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Format::Zune(format) => write!(f, "{format:?}"),
+            Format::WebP => write!(f, "WebP"),
+            Format::Gif => write!(f, "GIF"),
+            Format::Ico => write!(f, "ICO"),
+            Format::Tiff => write!(f, "TIFF"),
+            Format::Exr => write!(f, "EXR"),
+            Format::Svg => write!(f, "SVG"),
+            Format::Raw => write!(f, "RAW")
+        }
+    }
+}
@@ -0,0 +1,268 @@
+//! Generational handle table for images shared with the JVM
+//!
+//! Kotlin never sees a raw `*mut Image`, instead it receives an opaque
+//! `jlong` which packs a slot index in the lower 32 bits and the generation
+//! of that slot in the upper 32 bits.
+//!
+//! Every time a slot is freed its generation is bumped, so a handle that
+//! outlived its image (or one that was destroyed twice) no longer matches
+//! and is rejected instead of pointing into freed memory.
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex, PoisonError, RwLock};
+
+use jni::sys::jlong;
+use zune_image::image::Image;
+
+use crate::animation::{Animation, FilterScope};
+use crate::metadata::Metadata;
+
+/// Errors that may occur when resolving a handle
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum HandleErrors {
+    /// The handle was zero, i.e. no image was ever created for it
+    Null,
+    /// The handle does not belong to any slot we handed out
+    Invalid(jlong),
+    /// The handle was valid at some point but the image has since been destroyed
+    Stale(jlong)
+}
+
+impl Display for HandleErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            HandleErrors::Null => write!(f, "Image handle is null, did you create an image?"),
+            HandleErrors::Invalid(handle) => write!(f, "Invalid image handle {handle:#x}"),
+            HandleErrors::Stale(handle) => {
+                write!(f, "Image handle {handle:#x} refers to an image that was already destroyed")
+            }
+        }
+    }
+}
+
+impl std::error::Error for HandleErrors {}
+
+/// An image along with the frame state zune doesn't track for us
+#[derive(Clone)]
+pub struct Entry {
+    pub image: Image,
+    pub animation: Animation,
+    /// The frame pixel exports read and single frame filters modify
+    pub active_frame: usize,
+    pub filter_scope: FilterScope,
+    /// ICC profile and EXIF, written back on save
+    pub metadata: Metadata
+}
+
+impl Entry {
+    pub fn new(image: Image, animation: Animation) -> Entry {
+        Entry {
+            image,
+            animation,
+            active_frame: 0,
+            filter_scope: FilterScope::default(),
+            metadata: Metadata::default()
+        }
+    }
+}
+
+struct Slot {
+    generation: u32,
+    image: Option<Arc<Mutex<Entry>>>
+}
+
+/// The table holding all live images
+///
+/// Lookups only hold the table lock long enough to clone the `Arc` of an entry,
+/// the image itself has its own lock so filters on different images can run
+/// concurrently.
+struct ImageHandles {
+    slots: Vec<Slot>,
+    free: Vec<u32>
+}
+
+impl ImageHandles {
+    const fn new() -> ImageHandles {
+        ImageHandles {
+            slots: Vec::new(),
+            free: Vec::new()
+        }
+    }
+
+    fn insert(&mut self, entry: Entry) -> jlong {
+        let image = Some(Arc::new(Mutex::new(entry)));
+
+        if let Some(index) = self.free.pop() {
+            let slot = &mut self.slots[index as usize];
+            slot.image = image;
+            return encode(index, slot.generation);
+        }
+        let index = u32::try_from(self.slots.len()).expect("Too many live images");
+        self.slots.push(Slot { generation: 1, image });
+        encode(index, 1)
+    }
+
+    fn slot(&self, handle: jlong) -> Result<(u32, &Slot), HandleErrors> {
+        let (index, generation) = decode(handle)?;
+        let slot = self
+            .slots
+            .get(index as usize)
+            .ok_or(HandleErrors::Invalid(handle))?;
+
+        if slot.generation != generation || slot.image.is_none() {
+            return Err(HandleErrors::Stale(handle));
+        }
+        Ok((index, slot))
+    }
+
+    fn get(&self, handle: jlong) -> Result<Arc<Mutex<Entry>>, HandleErrors> {
+        let (_, slot) = self.slot(handle)?;
+        // slot() guarantees the image is present
+        Ok(slot.image.clone().unwrap())
+    }
+
+    fn remove(&mut self, handle: jlong) -> Result<(), HandleErrors> {
+        let (index, _) = self.slot(handle)?;
+        let slot = &mut self.slots[index as usize];
+
+        // bump the generation, any copy of this handle is now stale
+        slot.generation = slot.generation.wrapping_add(1).max(1);
+        slot.image = None;
+        self.free.push(index);
+        Ok(())
+    }
+}
+
+static HANDLES: RwLock<ImageHandles> = RwLock::new(ImageHandles::new());
+
+fn encode(index: u32, generation: u32) -> jlong {
+    // index is offset by one so that no valid handle is ever zero
+    (((generation as u64) << 32) | (u64::from(index) + 1)) as jlong
+}
+
+fn decode(handle: jlong) -> Result<(u32, u32), HandleErrors> {
+    if handle == 0 {
+        return Err(HandleErrors::Null);
+    }
+    let handle_u = handle as u64;
+    let index = (handle_u & 0xFFFF_FFFF) as u32;
+    let generation = (handle_u >> 32) as u32;
+
+    if index == 0 || generation == 0 {
+        return Err(HandleErrors::Invalid(handle));
+    }
+    Ok((index - 1, generation))
+}
+
+/// Store an image in the table, returning the handle Kotlin should use to refer to it
+pub fn insert(image: Image) -> jlong {
+    insert_entry(Entry::new(image, Animation::default()))
+}
+
+/// Store an image and its frame state in the table
+pub fn insert_entry(entry: Entry) -> jlong {
+    HANDLES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(entry)
+}
+
+/// Destroy the image referred to by `handle`
+///
+/// Destroying an already destroyed handle is an error rather than a double free
+pub fn remove(handle: jlong) -> Result<(), HandleErrors> {
+    HANDLES
+        .write()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(handle)
+}
+
+/// Return a shared reference to the image behind `handle`
+///
+/// The image stays alive for as long as the returned `Arc` is held, even
+/// if Kotlin destroys the handle in the meantime.
+pub fn get(handle: jlong) -> Result<Arc<Mutex<Entry>>, HandleErrors> {
+    HANDLES
+        .read()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(handle)
+}
+
+/// Run `func` with exclusive access to the image behind `handle`
+pub fn with_image<R, F>(handle: jlong, func: F) -> Result<R, HandleErrors>
+where
+    F: FnOnce(&mut Image) -> R
+{
+    with_entry(handle, |entry| func(&mut entry.image))
+}
+
+/// Run `func` with exclusive access to the image and frame state behind `handle`
+pub fn with_entry<R, F>(handle: jlong, func: F) -> Result<R, HandleErrors>
+where
+    F: FnOnce(&mut Entry) -> R
+{
+    let entry = get(handle)?;
+    let mut entry = entry.lock().unwrap_or_else(PoisonError::into_inner);
+    Ok(func(&mut entry))
+}
+
+/// Replace the image and frame state behind `handle` with a newly decoded one
+pub fn replace(handle: jlong, new_entry: Entry) -> Result<(), HandleErrors> {
+    with_entry(handle, |entry| *entry = new_entry)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry() -> Entry {
+        Entry::new(Image::fill(0_u8, zune_core::colorspace::ColorSpace::Luma, 1, 1), Animation::default())
+    }
+
+    #[test]
+    fn rejects_removed_handles() {
+        let mut handles = ImageHandles::new();
+        let handle = handles.insert(entry());
+        assert!(handles.get(handle).is_ok());
+
+        handles.remove(handle).unwrap();
+        assert_eq!(handles.get(handle).err(), Some(HandleErrors::Stale(handle)));
+    }
+
+    #[test]
+    fn reused_slots_get_a_new_generation() {
+        let mut handles = ImageHandles::new();
+        let old = handles.insert(entry());
+        handles.remove(old).unwrap();
+
+        let new = handles.insert(entry());
+        assert_eq!(decode(new).unwrap().0, decode(old).unwrap().0);
+        assert_ne!(new, old);
+        assert!(handles.get(new).is_ok());
+        assert_eq!(handles.get(old).err(), Some(HandleErrors::Stale(old)));
+        // removing through the old handle must not free the new image
+        assert_eq!(handles.remove(old), Err(HandleErrors::Stale(old)));
+        assert!(handles.get(new).is_ok());
+    }
+
+    #[test]
+    fn rejects_double_remove() {
+        let mut handles = ImageHandles::new();
+        let handle = handles.insert(entry());
+        handles.remove(handle).unwrap();
+        assert_eq!(handles.remove(handle), Err(HandleErrors::Stale(handle)));
+        assert_eq!(handles.free, [decode(handle).unwrap().0]);
+    }
+
+    #[test]
+    fn rejects_garbage_handles() {
+        let mut handles = ImageHandles::new();
+        let handle = handles.insert(entry());
+
+        assert_eq!(handles.get(0).err(), Some(HandleErrors::Null));
+        for garbage in [-1, i64::MIN, 1, 1 << 32, handle + 1, handle + (1 << 32), 0x1234_5678_9ABC] {
+            assert!(handles.get(garbage).is_err(), "{garbage:#x} was accepted");
+            assert!(handles.remove(garbage).is_err(), "{garbage:#x} was removed");
+        }
+        assert!(handles.get(handle).is_ok());
+    }
+}
@@ -0,0 +1,362 @@
+//! ICC profiles, reading them from files and converting pixels between them
+//!
+//! zune doesn't keep the profiles embedded in the files it decodes, so
+//! they are read from the encoded bytes here and stored next to the image
+//! in its handle.
+//!
+//! On load RGB pixels are converted to a [`WorkingSpace`], images without
+//! a profile are assumed to be sRGB, except linear HDR and EXR images which
+//! are left alone. The profile of the converted pixels is the one embedded
+//! again on save, images in sRGB are saved without one.
+use jni::objects::{JClass, JObject, JValue};
+use jni::sys::{jboolean, jbyteArray, jlong, jobject};
+use jni::JNIEnv;
+use moxcms::{ColorProfile, Layout, ProfileText, TransformExecutor, TransformOptions};
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::ImageErrors;
+use zune_image::frame::Frame;
+use zune_image::image::Image;
+
+use crate::containers;
+use crate::errors::{jni_guard, JniErrors};
+use crate::format::Format;
+use crate::handles::{self, Entry};
+
+const ICC_PROFILE_CLASS: &str = "ZilIccProfile";
+
+/// Identifier JPEG APP2 segments holding a profile start with
+const JPEG_ICC_MARKER: &[u8] = b"ICC_PROFILE\0";
+
+/// Photoshop image resource holding the profile
+const PSD_ICC_RESOURCE: u16 = 0x040F;
+
+/// TIFF tag holding the profile
+const TIFF_ICC_TAG: u16 = 34675;
+
+/// Color space RGB pixels are converted to on load
+///
+/// Discriminants match the order of `ZilWorkingSpace` in Kotlin
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum WorkingSpace {
+    /// Keep the pixels as decoded, along with the profile they came with
+    Original,
+    #[default]
+    Srgb,
+    DisplayP3,
+    AdobeRgb,
+    ProPhotoRgb
+}
+
+impl WorkingSpace {
+    pub fn from_ordinal(ordinal: i32) -> Option<WorkingSpace> {
+        match ordinal {
+            0 => Some(WorkingSpace::Original),
+            1 => Some(WorkingSpace::Srgb),
+            2 => Some(WorkingSpace::DisplayP3),
+            3 => Some(WorkingSpace::AdobeRgb),
+            4 => Some(WorkingSpace::ProPhotoRgb),
+            _ => None
+        }
+    }
+
+    fn profile(self) -> Option<ColorProfile> {
+        match self {
+            WorkingSpace::Original => None,
+            WorkingSpace::Srgb => Some(ColorProfile::new_srgb()),
+            WorkingSpace::DisplayP3 => Some(ColorProfile::new_display_p3()),
+            WorkingSpace::AdobeRgb => Some(ColorProfile::new_adobe_rgb()),
+            WorkingSpace::ProPhotoRgb => Some(ColorProfile::new_pro_photo_rgb())
+        }
+    }
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// Join the APP2 segments a JPEG splits its profile across
+fn from_jpeg(data: &[u8]) -> Option<Vec<u8>> {
+    let mut chunks: Vec<(u8, &[u8])> = containers::jpeg_segments(data)
+        .filter(|(marker, _)| *marker == 0xE2)
+        .filter_map(|(_, segment)| segment.strip_prefix(JPEG_ICC_MARKER))
+        // sequence number and chunk count precede the data
+        .filter(|chunk| chunk.len() > 2)
+        .map(|chunk| (chunk[0], &chunk[2..]))
+        .collect();
+
+    if chunks.is_empty() {
+        return None;
+    }
+    chunks.sort_by_key(|(sequence, _)| *sequence);
+    Some(chunks.iter().flat_map(|(_, chunk)| chunk.iter().copied()).collect())
+}
+
+/// Inflate the profile in a PNG's `iCCP` chunk
+fn from_png(data: &[u8]) -> Option<Vec<u8>> {
+    let (_, chunk) = containers::png_chunks(data)
+        // the profile must come before the image data
+        .take_while(|(kind, _)| *kind != b"IDAT")
+        .find(|(kind, _)| *kind == b"iCCP")?;
+
+    // profile name, a null and the compression method precede the data
+    let name_end = chunk.iter().position(|c| *c == 0)?;
+    let compressed = chunk.get(name_end + 2..)?;
+    zune_inflate::DeflateDecoder::new(compressed).decode_zlib().ok()
+}
+
+/// Read the `ICCP` chunk of an extended WebP
+fn from_webp(data: &[u8]) -> Option<Vec<u8>> {
+    containers::riff_chunks(data)
+        .find(|(kind, _)| *kind == b"ICCP")
+        .map(|(_, chunk)| chunk.to_vec())
+}
+
+/// Read the profile tag from the first IFD of a TIFF
+fn from_tiff(data: &[u8]) -> Option<Vec<u8>> {
+    let little_endian = data.starts_with(b"II");
+    let u16_at = |offset: usize| -> Option<u16> {
+        let bytes = data.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if little_endian { u16::from_le_bytes(bytes) } else { u16::from_be_bytes(bytes) })
+    };
+    let u32_at = |offset: usize| -> Option<u32> {
+        let bytes = data.get(offset..offset + 4)?.try_into().ok()?;
+        Some(if little_endian { u32::from_le_bytes(bytes) } else { u32::from_be_bytes(bytes) })
+    };
+    let ifd = u32_at(4)? as usize;
+
+    for index in 0..usize::from(u16_at(ifd)?) {
+        let entry = ifd + 2 + index * 12;
+        if u16_at(entry)? == TIFF_ICC_TAG {
+            let count = u32_at(entry + 4)? as usize;
+            // values of up to four bytes are stored in the entry itself
+            let offset = if count <= 4 { entry + 8 } else { u32_at(entry + 8)? as usize };
+            return data.get(offset..offset.checked_add(count)?).map(<[u8]>::to_vec);
+        }
+    }
+    None
+}
+
+/// Find the profile among the image resources of a Photoshop document
+fn from_psd(data: &[u8]) -> Option<Vec<u8>> {
+    // the color mode data follows the 26 byte header
+    let color_mode_length = be_u32(data, 26)? as usize;
+    let resources_offset = 30_usize.checked_add(color_mode_length)?;
+    let resources_length = be_u32(data, resources_offset)? as usize;
+    let resources_start = resources_offset + 4;
+    // a truncated section may still hold the profile
+    let resources_end = resources_start.checked_add(resources_length)?.min(data.len());
+    let mut resources = data.get(resources_start..resources_end)?;
+
+    while resources.len() >= 12 && resources.starts_with(b"8BIM") {
+        let id = be_u16(resources, 4)?;
+        // a pascal string, padded to an even length
+        let name_length = usize::from(resources[6]);
+        let size_offset = 6 + ((name_length + 2) & !1);
+        let size = be_u32(resources, size_offset)? as usize;
+        let resource = resources.get(size_offset + 4..(size_offset + 4).checked_add(size)?)?;
+
+        if id == PSD_ICC_RESOURCE {
+            return Some(resource.to_vec());
+        }
+        resources = resources.get(size_offset + 4 + size + (size & 1)..)?;
+    }
+    None
+}
+
+/// Read the ICC profile embedded in an encoded image, if it has one
+pub fn extract(data: &[u8], format: Format) -> Option<Vec<u8>> {
+    let profile = match format {
+        Format::Zune(ImageFormat::JPEG) => from_jpeg(data),
+        Format::Zune(ImageFormat::PNG) => from_png(data),
+        Format::Zune(ImageFormat::PSD) => from_psd(data),
+        Format::WebP => from_webp(data),
+        Format::Tiff => from_tiff(data),
+        _ => None
+    };
+    profile.filter(|profile| profile.len() >= 128)
+}
+
+/// Whether a profile describes pixels with the channels of `colorspace`
+pub fn matches(profile: &[u8], colorspace: ColorSpace) -> bool {
+    match profile.get(16..20) {
+        Some(b"RGB ") => matches!(colorspace, ColorSpace::RGB | ColorSpace::RGBA),
+        Some(b"GRAY") => matches!(colorspace, ColorSpace::Luma | ColorSpace::LumaA),
+        _ => false
+    }
+}
+
+fn cms_error(err: moxcms::CmsError) -> JniErrors {
+    JniErrors::from(ImageErrors::GenericString(format!("ICC profile: {err}")))
+}
+
+fn transform_frames<T: Copy + Default>(
+    transform: &(dyn TransformExecutor<T> + Send + Sync), frames: Vec<Vec<T>>
+) -> Result<Vec<Vec<T>>, JniErrors> {
+    frames
+        .into_iter()
+        .map(|pixels| {
+            let mut converted = vec![T::default(); pixels.len()];
+            transform.transform(&pixels, &mut converted).map_err(cms_error)?;
+            Ok(converted)
+        })
+        .collect()
+}
+
+/// Convert the RGB pixels of `image` from the `from` profile to `to`
+fn convert(image: &mut Image, from: &ColorProfile, to: &ColorProfile) -> Result<(), JniErrors> {
+    let colorspace = image.colorspace();
+    let layout = if colorspace.has_alpha() { Layout::Rgba } else { Layout::Rgb };
+    let options = TransformOptions::default();
+
+    let frames: Vec<Frame> = match image.depth() {
+        BitDepth::Eight => {
+            let transform = from.create_transform_8bit(layout, to, layout, options).map_err(cms_error)?;
+            transform_frames(transform.as_ref(), image.flatten_frames::<u8>())?
+                .iter()
+                .map(|pixels| Frame::from_u8(pixels, colorspace, 0, 1))
+                .collect()
+        }
+        BitDepth::Sixteen => {
+            let transform = from.create_transform_16bit(layout, to, layout, options).map_err(cms_error)?;
+            transform_frames(transform.as_ref(), image.flatten_frames::<u16>())?
+                .iter()
+                .map(|pixels| Frame::from_u16(pixels, colorspace, 0, 1))
+                .collect()
+        }
+        BitDepth::Float32 => {
+            let transform = from.create_transform_f32(layout, to, layout, options).map_err(cms_error)?;
+            transform_frames(transform.as_ref(), image.flatten_frames::<f32>())?
+                .iter()
+                .map(|pixels| Frame::from_f32(pixels, colorspace, 0, 1))
+                .collect()
+        }
+        _ => return Ok(())
+    };
+    for (frame, converted) in image.frames_mut().iter_mut().zip(frames) {
+        *frame = converted;
+    }
+    Ok(())
+}
+
+/// Read the profile embedded in `data`, the file `entry` was decoded from,
+/// and convert the pixels to `space`
+///
+/// Profiles that can't be parsed are ignored unless `strict` is set
+pub fn color_manage(entry: &mut Entry, data: &[u8], format: Format, space: WorkingSpace, strict: bool) -> Result<(), JniErrors> {
+    let colorspace = entry.image.colorspace();
+    // e.g. the CMYK profile of a JPEG zune converted to RGB no longer applies
+    let embedded = extract(data, format).filter(|profile| matches(profile, colorspace));
+
+    entry.metadata.source_icc = embedded.clone();
+    entry.metadata.icc = embedded.clone();
+
+    let Some(target) = space.profile() else {
+        return Ok(());
+    };
+    // gray profiles are kept as they are
+    if !matches!(colorspace, ColorSpace::RGB | ColorSpace::RGBA) {
+        return Ok(());
+    }
+    let source = match &embedded {
+        Some(profile) => match ColorProfile::new_from_slice(profile) {
+            Ok(profile) => profile,
+            Err(err) if strict => return Err(cms_error(err).with_format(format)),
+            Err(_) => {
+                entry.metadata.source_icc = None;
+                ColorProfile::new_srgb()
+            }
+        },
+        // linear light, not sRGB
+        None if matches!(format, Format::Exr | Format::Zune(ImageFormat::HDR)) => return Ok(()),
+        None => ColorProfile::new_srgb()
+    };
+    if entry.metadata.source_icc.is_some() || space != WorkingSpace::Srgb {
+        convert(&mut entry.image, &source, &target)?;
+    }
+    entry.metadata.icc = match space {
+        WorkingSpace::Srgb => None,
+        _ => Some(target.encode().map_err(cms_error)?)
+    };
+    Ok(())
+}
+
+fn text(text: &Option<ProfileText>) -> String {
+    match text {
+        Some(ProfileText::PlainString(text)) => text.clone(),
+        Some(ProfileText::Localizable(strings)) => strings
+            .iter()
+            .find(|string| string.language == "en")
+            .or(strings.first())
+            .map(|string| string.value.clone())
+            .unwrap_or_default(),
+        Some(ProfileText::Description(description)) => description.ascii_string.clone(),
+        None => String::new()
+    }
+}
+
+/// A four character header signature, e.g. `RGB ` or `mntr`
+fn signature(profile: &[u8], offset: usize) -> String {
+    profile
+        .get(offset..offset + 4)
+        .map(|signature| String::from_utf8_lossy(signature).trim_end().to_string())
+        .unwrap_or_default()
+}
+
+fn entry_profile(entry: &Entry, source: jboolean) -> Option<&[u8]> {
+    match source != 0 {
+        true => entry.metadata.source_icc.as_deref(),
+        false => entry.metadata.icc.as_deref()
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_iccProfileNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, source: jboolean) -> jobject {
+    jni_guard(&mut env, "iccProfileNative", |env| {
+        let Some(profile) = handles::with_entry(image_ptr, |entry| entry_profile(entry, source).map(<[u8]>::to_vec))? else {
+            return Ok(JObject::null());
+        };
+        let parsed = ColorProfile::new_from_slice(&profile).map_err(cms_error)?;
+        // major, minor and bugfix as binary coded decimal
+        let version = format!("{}.{}.{}", profile[8], profile[9] >> 4, profile[9] & 0xF);
+
+        let description = JObject::from(env.new_string(text(&parsed.description))?);
+        let copyright = JObject::from(env.new_string(text(&parsed.copyright))?);
+        let colorspace = JObject::from(env.new_string(signature(&profile, 16))?);
+        let class = JObject::from(env.new_string(signature(&profile, 12))?);
+        let version = JObject::from(env.new_string(version)?);
+
+        let object = env.new_object(
+            ICC_PROFILE_CLASS,
+            "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;J)V",
+            &[
+                JValue::Object(&description),
+                JValue::Object(&copyright),
+                JValue::Object(&colorspace),
+                JValue::Object(&class),
+                JValue::Object(&version),
+                JValue::Long(profile.len() as _)
+            ]
+        )?;
+        Ok(object)
+    })
+    .into_raw()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_iccProfileBytesNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, source: jboolean) -> jbyteArray {
+    jni_guard(&mut env, "iccProfileBytesNative", |env| {
+        let profile = handles::with_entry(image_ptr, |entry| entry_profile(entry, source).map(<[u8]>::to_vec))?;
+        match profile {
+            Some(profile) => Ok(env.byte_array_from_slice(&profile)?),
+            None => Ok(JObject::null().into())
+        }
+    })
+    .into_raw()
+}
+
@@ -0,0 +1,276 @@
+//! IPTC-IIM records, the caption and keyword fields JPEG files carry in
+//! their Photoshop `APP13` segment
+//!
+//! Other formats have no place for IPTC records, their IPTC fields are
+//! stored as XMP properties instead (see [`crate::xmp`]).
+use jni::objects::{JClass, JObject, JObjectArray, JValue};
+use jni::sys::{jint, jlong, jobjectArray, jsize};
+use jni::JNIEnv;
+
+use crate::containers;
+use crate::errors::{jni_guard, JniErrors};
+use crate::handles;
+use crate::xmp::string_array;
+
+/// Identifier JPEG APP13 segments holding Photoshop resources start with
+pub const JPEG_PHOTOSHOP_MARKER: &[u8] = b"Photoshop 3.0\0";
+
+const IPTC_DATASET_CLASS: &str = "ZilIptcDataset";
+
+/// Photoshop resource holding the IPTC records
+const IPTC_RESOURCE: u16 = 0x0404;
+/// Marks the start of each dataset
+const TAG_MARKER: u8 = 0x1C;
+
+/// The application record holding the editorial fields
+const APPLICATION_RECORD: u8 = 2;
+/// Envelope record dataset declaring the character set
+const CHARACTER_SET: (u8, u8) = (1, 90);
+/// ISO 2022 escape sequence declaring UTF-8
+const UTF8: &[u8] = b"\x1B%G";
+
+/// A dataset, e.g. a single keyword
+#[derive(Debug, Clone)]
+pub struct Dataset {
+    pub record: u8,
+    pub dataset: u8,
+    pub data: Vec<u8>
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Iptc {
+    pub datasets: Vec<Dataset>
+}
+
+/// Name of an application record dataset
+fn dataset_name(record: u8, dataset: u8) -> Option<&'static str> {
+    if record != APPLICATION_RECORD {
+        return None;
+    }
+    let name = match dataset {
+        0 => "RecordVersion",
+        5 => "ObjectName",
+        10 => "Urgency",
+        15 => "Category",
+        20 => "SupplementalCategories",
+        25 => "Keywords",
+        40 => "SpecialInstructions",
+        55 => "DateCreated",
+        60 => "TimeCreated",
+        80 => "By-line",
+        85 => "By-lineTitle",
+        90 => "City",
+        92 => "Sub-location",
+        95 => "Province-State",
+        100 => "Country-PrimaryLocationCode",
+        101 => "Country-PrimaryLocationName",
+        103 => "OriginalTransmissionReference",
+        105 => "Headline",
+        110 => "Credit",
+        115 => "Source",
+        116 => "CopyrightNotice",
+        118 => "Contact",
+        120 => "Caption-Abstract",
+        122 => "Writer-Editor",
+        _ => return None
+    };
+    Some(name)
+}
+
+fn be_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn be_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+/// The data of the IPTC resource among Photoshop image resources
+fn iptc_resource(mut resources: &[u8]) -> Option<&[u8]> {
+    while resources.starts_with(b"8BIM") {
+        let id = be_u16(resources, 4)?;
+        // pascal string name, padded to an even length along with its length byte
+        let name_length = usize::from(*resources.get(6)?);
+        let name_end = 6 + ((name_length + 2) & !1);
+        let size = be_u32(resources, name_end)? as usize;
+        let data = resources.get(name_end + 4..(name_end + 4).checked_add(size)?)?;
+
+        if id == IPTC_RESOURCE {
+            return Some(data);
+        }
+        resources = resources.get(name_end + 4 + size + (size & 1)..)?;
+    }
+    None
+}
+
+impl Iptc {
+    /// Parse IPTC-IIM datasets, stopping at the first malformed one
+    pub fn parse(mut data: &[u8]) -> Option<Iptc> {
+        let mut datasets = Vec::new();
+
+        while let [TAG_MARKER, record, dataset, ..] = *data {
+            let mut length = usize::from(be_u16(data, 3)?);
+            let mut start = 5;
+            // the high bit marks an extended dataset, the rest is the size of its length
+            if length & 0x8000 != 0 {
+                let size = length & 0x7FFF;
+                if size > 4 {
+                    break;
+                }
+                length = data.get(5..5 + size)?.iter().fold(0, |length, byte| (length << 8) | usize::from(*byte));
+                start += size;
+            }
+            let Some(value) = data.get(start..start.saturating_add(length)) else {
+                break;
+            };
+            datasets.push(Dataset {
+                record,
+                dataset,
+                data: value.to_vec()
+            });
+            data = &data[start + length..];
+        }
+        (!datasets.is_empty()).then_some(Iptc { datasets })
+    }
+
+    /// Read the IPTC records of a JPEG file, if it has any
+    pub fn extract_jpeg(data: &[u8]) -> Option<Iptc> {
+        let resources = containers::jpeg_segments(data)
+            .filter(|(marker, _)| *marker == 0xED)
+            .find_map(|(_, segment)| segment.strip_prefix(JPEG_PHOTOSHOP_MARKER))?;
+        Iptc::parse(iptc_resource(resources)?)
+    }
+
+    fn is_utf8(&self) -> bool {
+        self.datasets
+            .iter()
+            .any(|dataset| (dataset.record, dataset.dataset) == CHARACTER_SET && dataset.data == UTF8)
+    }
+
+    /// The text of a dataset, UTF-8 if the records declare it, otherwise
+    /// UTF-8 if it's valid and Latin-1 if not
+    pub fn text(&self, dataset: &Dataset) -> String {
+        match std::str::from_utf8(&dataset.data) {
+            Ok(text) => text.to_string(),
+            Err(_) if self.is_utf8() => String::from_utf8_lossy(&dataset.data).into_owned(),
+            Err(_) => dataset.data.iter().map(|byte| char::from(*byte)).collect()
+        }
+    }
+
+    /// Replace every application record dataset numbered `dataset` with `values`, removing it if there are none
+    ///
+    /// Values are written as UTF-8, so the other application records are converted to it too
+    pub fn set(&mut self, dataset: u8, values: &[String]) -> Result<(), JniErrors> {
+        if let Some(value) = values.iter().find(|value| value.len() > usize::from(u16::MAX >> 1)) {
+            return Err(JniErrors::InvalidArgument(format!("IPTC values should be shorter than 32768 bytes, found {}", value.len())));
+        }
+        let converted: Vec<Dataset> = self
+            .datasets
+            .iter()
+            .filter(|existing| (existing.record, existing.dataset) != CHARACTER_SET)
+            .filter(|existing| !(existing.record == APPLICATION_RECORD && existing.dataset == dataset))
+            .map(|existing| Dataset {
+                data: match existing.record {
+                    APPLICATION_RECORD => self.text(existing).into_bytes(),
+                    _ => existing.data.clone()
+                },
+                ..existing.clone()
+            })
+            .collect();
+
+        self.datasets = converted;
+        self.datasets.insert(
+            0,
+            Dataset {
+                record: CHARACTER_SET.0,
+                dataset: CHARACTER_SET.1,
+                data: UTF8.to_vec()
+            }
+        );
+        self.datasets.extend(values.iter().map(|value| Dataset {
+            record: APPLICATION_RECORD,
+            dataset,
+            data: value.as_bytes().to_vec()
+        }));
+        Ok(())
+    }
+
+    /// Serialize as a JPEG `APP13` segment's payload, holding a single Photoshop resource
+    pub fn to_jpeg_segment(&self) -> Vec<u8> {
+        let mut records = Vec::new();
+        for dataset in &self.datasets {
+            records.extend_from_slice(&[TAG_MARKER, dataset.record, dataset.dataset]);
+            // set only writes values that fit, longer ones came from the file
+            match u16::try_from(dataset.data.len()).ok().filter(|length| *length < 0x8000) {
+                Some(length) => records.extend_from_slice(&length.to_be_bytes()),
+                None => {
+                    records.extend_from_slice(&0x8004_u16.to_be_bytes());
+                    records.extend_from_slice(&(dataset.data.len() as u32).to_be_bytes());
+                }
+            }
+            records.extend_from_slice(&dataset.data);
+        }
+        let mut segment = JPEG_PHOTOSHOP_MARKER.to_vec();
+        segment.extend_from_slice(b"8BIM");
+        segment.extend_from_slice(&IPTC_RESOURCE.to_be_bytes());
+        // empty name, padded to an even length
+        segment.extend_from_slice(&[0, 0]);
+        segment.extend_from_slice(&(records.len() as u32).to_be_bytes());
+        segment.extend_from_slice(&records);
+        if records.len() % 2 == 1 {
+            segment.push(0);
+        }
+        segment
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_iptcDatasetsNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jobjectArray {
+    jni_guard(&mut env, "iptcDatasetsNative", |env| {
+        let iptc = handles::with_entry(image_ptr, |entry| entry.metadata.iptc.clone())?.unwrap_or_default();
+
+        let array = env.new_object_array(iptc.datasets.len() as jsize, IPTC_DATASET_CLASS, JObject::null())?;
+        for (index, dataset) in iptc.datasets.iter().enumerate() {
+            env.with_local_frame(4, |env| -> Result<(), JniErrors> {
+                let name = dataset_name(dataset.record, dataset.dataset)
+                    .map_or_else(|| format!("{}:{}", dataset.record, dataset.dataset), str::to_string);
+                let name = JObject::from(env.new_string(name)?);
+                let value = JObject::from(env.new_string(iptc.text(dataset))?);
+
+                let object = env.new_object(
+                    IPTC_DATASET_CLASS,
+                    "(IILjava/lang/String;Ljava/lang/String;)V",
+                    &[
+                        JValue::Int(jint::from(dataset.record)),
+                        JValue::Int(jint::from(dataset.dataset)),
+                        JValue::Object(&name),
+                        JValue::Object(&value)
+                    ]
+                )?;
+                env.set_object_array_element(&array, index as jsize, object)?;
+                Ok(())
+            })?;
+        }
+        Ok(array)
+    })
+    .into_raw()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setIptcDatasetNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, dataset: jint, values: JObjectArray) {
+    jni_guard(&mut env, "setIptcDatasetNative", |env| {
+        let dataset = u8::try_from(dataset)
+            .map_err(|_| JniErrors::InvalidArgument(format!("IPTC datasets are numbered 0-255, found {dataset}")))?;
+        let values = string_array(env, &values)?;
+
+        handles::with_entry(image_ptr, |entry| {
+            let iptc = entry.metadata.iptc.get_or_insert_with(Iptc::default);
+            iptc.set(dataset, &values)?;
+            // removing the last dataset leaves only the character set
+            if iptc.datasets.iter().all(|existing| existing.record != APPLICATION_RECORD) {
+                entry.metadata.iptc = None;
+            }
+            Ok(())
+        })?
+    })
+}
@@ -1,5 +1,6 @@
-use std::alloc::{dealloc, Layout};
 use std::ffi::c_void;
+use std::sync::PoisonError;
+
 use jni::objects::{JByteArray, JByteBuffer, JClass, JFloatArray, JIntArray, JObject, JString};
 use jni::JNIEnv;
 use jni::sys::{jfloat, jint, jlong};
@@ -32,225 +33,272 @@ use zune_imageprocs::sobel::Sobel;
 use zune_imageprocs::stretch_contrast::StretchContrast;
 use zune_imageprocs::transpose::Transpose;
 
+use crate::animation::FilterScope;
+use crate::errors::{jni_guard, JniErrors};
+use crate::format::Format;
+use crate::handles::Entry;
 
-#[no_mangle]
-pub extern "system" fn Java_ZilImageJni_createImagePtrNative(_env: JNIEnv, _class: JClass) -> jlong {
-    let image = Image::new(vec![], BitDepth::Unknown, 1, 1, ColorSpace::Unknown);
+mod animation;
+mod codecs;
+mod containers;
+mod decoder;
+mod encoder;
+mod errors;
+mod format;
+mod handles;
+mod icc;
+mod iptc;
+mod metadata;
+mod probe;
+mod sidecar;
+mod thumbnail;
+mod xmp;
 
-    let c = Box::new(image);
-    // convert it to a pointer
-    Box::into_raw(c) as jlong
+pub(crate) fn get_string(env: &mut JNIEnv, string: &JString) -> Result<String, JniErrors> {
+    Ok(env.get_string(string)?.into())
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_destroyImagePtrNative(_env: JNIEnv, _class: JClass, ptr: jlong) {
-    let ptr = ptr as *mut Image;
-    ptr.drop_in_place();
-    dealloc(ptr.cast(), Layout::new::<Image>())
+pub extern "system" fn Java_ZilImageJni_createImagePtrNative(mut env: JNIEnv, _class: JClass) -> jlong {
+    jni_guard(&mut env, "createImagePtrNative", |_| {
+        let image = Image::new(vec![], BitDepth::Unknown, 1, 1, ColorSpace::Unknown);
+        // hand out an opaque handle, never the pointer itself
+        Ok(handles::insert(image))
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_loadImageNative<'a>(mut env: JNIEnv<'a>, _class: JClass, image_ptr: jlong, filename: JString) {
-    let img_ptr = image_ptr as *mut Image;
-    let input_str: String = env.get_string(&filename).expect("Could not get input string").into();
-    let image = Image::open(&input_str);
-    match image {
-        Ok(im) => {
-            *img_ptr = im;
-        }
-        Err(e) => {
-            env.throw(format!("Cannot load image {e}")).expect("Cannot throw exception");
-        }
-    }
+pub extern "system" fn Java_ZilImageJni_destroyImagePtrNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) {
+    jni_guard(&mut env, "destroyImagePtrNative", |_| {
+        handles::remove(image_ptr)?;
+        Ok(())
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_getImageWidthNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jlong {
-    let img_ptr = image_ptr as *mut Image;
-    if !img_ptr.is_null() {
-        return unsafe { (*img_ptr).dimensions().0 } as jlong;
-    }
-    env.throw("Image ptr is null").expect("Cannot throw exception");
-    0
+pub extern "system" fn Java_ZilImageJni_loadImageNative<'a>(mut env: JNIEnv<'a>, _class: JClass, image_ptr: jlong, filename: JString) {
+    jni_guard(&mut env, "loadImageNative", |env| {
+        let input_str = get_string(env, &filename)?;
+        let entry = decoder::open_image(&input_str, &decoder::DecodeOptions::default())?;
+        handles::replace(image_ptr, entry)?;
+        Ok(())
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_getImageHeightNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jlong {
-    let img_ptr = image_ptr as *mut Image;
-    if !img_ptr.is_null() {
-        return unsafe { (*img_ptr).dimensions().1 } as jlong;
-    }
-    env.throw("Image ptr is null").expect("Cannot throw exception");
-    0
+pub extern "system" fn Java_ZilImageJni_getImageWidthNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jlong {
+    jni_guard(&mut env, "getImageWidthNative", |_| {
+        Ok(handles::with_image(image_ptr, |image| image.dimensions().0)? as jlong)
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_saveNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, filename: JString) {
-    let img_ptr = image_ptr as *const Image;
-    if img_ptr.is_null() {
-        env.throw("Image is null").expect("Could not throw exception");
-        return;
-    }
-    let input_str: String = env.get_string(&filename).expect("Could not get input string").into();
-
-    let img = &*img_ptr;
-    if let Err(err) = img.save(input_str) {
-        env.throw(err.to_string()).expect("Could not throw exception");
-    }
+pub extern "system" fn Java_ZilImageJni_getImageHeightNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jlong {
+    jni_guard(&mut env, "getImageHeightNative", |_| {
+        Ok(handles::with_image(image_ptr, |image| image.dimensions().1)? as jlong)
+    })
 }
 
-fn exec_imgproc<T>(env: &mut JNIEnv, image: jlong, filter: T)
+fn exec_imgproc<T>(image: jlong, filter: T) -> Result<(), JniErrors>
     where
         T: OperationsTrait,
 {
-    let image = image as *mut Image;
-    if image.is_null() {
-        env.throw("Image is null").expect("Failed to throw exception");
-        return;
-    }
-    let image = unsafe { &mut *image };
+    handles::with_entry(image, |entry| match entry.filter_scope {
+        FilterScope::ActiveFrame if entry.image.frames_len() > 1 => {
+            animation::execute_on_frame(&mut entry.image, entry.active_frame, &filter)
+        }
+        _ => Ok(filter.execute_impl(&mut entry.image)?)
+    })?
+}
 
-    if let Err(err) = filter.execute_impl(image) {
-        env.throw(err.to_string()).expect("Could not throw exception");
-    }
+/// Run a filter turning or mirroring the image, which leaves the pixels the
+/// way they are shown, so the orientation they were loaded with no longer applies
+fn exec_reorient<T>(image: jlong, filter: T) -> Result<(), JniErrors>
+    where
+        T: OperationsTrait,
+{
+    exec_imgproc(image, filter)?;
+    Ok(handles::with_entry(image, |entry| entry.metadata.reset_orientation())?)
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_exposureNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, exposure: jfloat, black_point: jfloat) {
-    let filter = Exposure::new(exposure, black_point);
-    exec_imgproc(&mut env, image_ptr, filter);
+pub extern "system" fn Java_ZilImageJni_exposureNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, exposure: jfloat, black_point: jfloat) {
+    jni_guard(&mut env, "exposureNative", |_| {
+        let filter = Exposure::new(exposure, black_point);
+        exec_imgproc(image_ptr, filter)
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_cropNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, new_width: jlong, new_height: jlong, x: jlong, y: jlong) {
-    let filter = Crop::new(new_width as usize, new_height as usize, x as usize, y as usize);
-    exec_imgproc(&mut env, image_ptr, filter);
+pub extern "system" fn Java_ZilImageJni_cropNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, new_width: jlong, new_height: jlong, x: jlong, y: jlong) {
+    jni_guard(&mut env, "cropNative", |_| {
+        let filter = Crop::new(new_width as usize, new_height as usize, x as usize, y as usize);
+        exec_imgproc(image_ptr, filter)
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_contrastNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, contrast: jfloat) {
-    let filter = Contrast::new(contrast);
-    exec_imgproc(&mut env, image_ptr, filter);
+pub extern "system" fn Java_ZilImageJni_contrastNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, contrast: jfloat) {
+    jni_guard(&mut env, "contrastNative", |_| {
+        let filter = Contrast::new(contrast);
+        exec_imgproc(image_ptr, filter)
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_bilateralFilterNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, d: jint, sigma_space: jfloat, sigma_color: jfloat) {
-    let filter = BilateralFilter::new(d, sigma_color, sigma_space);
-    exec_imgproc(&mut env, image_ptr, filter)
+pub extern "system" fn Java_ZilImageJni_bilateralFilterNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, d: jint, sigma_space: jfloat, sigma_color: jfloat) {
+    jni_guard(&mut env, "bilateralFilterNative", |_| {
+        let filter = BilateralFilter::new(d, sigma_color, sigma_space);
+        exec_imgproc(image_ptr, filter)
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_gammaNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, gamma: jfloat) {
-    let filter = Gamma::new(gamma);
-    exec_imgproc(&mut env, image_ptr, filter);
+pub extern "system" fn Java_ZilImageJni_gammaNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, gamma: jfloat) {
+    jni_guard(&mut env, "gammaNative", |_| {
+        let filter = Gamma::new(gamma);
+        exec_imgproc(image_ptr, filter)
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_getImageOutBufferSizeNative(_env: JNIEnv, _class: JClass, image_ptr: jlong) -> jlong {
-    let image = { &*(image_ptr as *const Image) };
+pub extern "system" fn Java_ZilImageJni_getImageOutBufferSizeNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jlong {
+    jni_guard(&mut env, "getImageOutBufferSizeNative", |_| {
+        let size = handles::with_image(image_ptr, |image| {
+            let (w, h) = image.dimensions();
+            let colorspace = image.colorspace().num_components();
+            let depth = image.depth().size_of();
 
-    let (w, h) = image.dimensions();
-    let colorspace = image.colorspace().num_components();
-    let depth = image.depth().size_of();
-
-    return (w * h * colorspace * depth) as _;
+            w * h * colorspace * depth
+        })?;
+        Ok(size as _)
+    })
 }
 
+/// # Safety
+///
+/// The returned memory must be released with `freeByteMemoryNative`
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_allocByteMemoryNative(_env: JNIEnv, _class: JClass, size: jlong) -> jlong {
-    let ptr = libc::malloc(size as _);
-    if ptr.is_null() {
-        return 0;
-    }
-    return ptr as _;
+pub unsafe extern "system" fn Java_ZilImageJni_allocByteMemoryNative(mut env: JNIEnv, _class: JClass, size: jlong) -> jlong {
+    jni_guard(&mut env, "allocByteMemoryNative", |_| {
+        let ptr = libc::malloc(size as _);
+        if ptr.is_null() {
+            return Ok(0);
+        }
+        Ok(ptr as _)
+    })
+}
+
+/// # Safety
+///
+/// `initial_ptr` must have been returned by `allocByteMemoryNative` or `resizeByteMemoryNative`
+/// and not freed yet
+#[no_mangle]
+pub unsafe extern "system" fn Java_ZilImageJni_resizeByteMemoryNative(mut env: JNIEnv, _class: JClass, initial_ptr: jlong, new_size: jlong) -> jlong {
+    jni_guard(&mut env, "resizeByteMemoryNative", |_| {
+        let ptr = initial_ptr as *mut c_void;
+        let c = libc::realloc(ptr, new_size as _);
+        if c.is_null() {
+            // free initial pointer
+            libc::free(ptr);
+            // then report we failed
+            return Ok(0);
+        }
+        Ok(c as _)
+    })
 }
 
+/// # Safety
+///
+/// `ptr` must have been returned by `allocByteMemoryNative` or `resizeByteMemoryNative`
+/// and not freed yet
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_resizeByteMemoryNative(_env: JNIEnv, _class: JClass, initial_ptr: jlong, new_size: jlong) -> jlong {
-    let ptr = initial_ptr as *mut c_void;
-    let c = libc::realloc(ptr, new_size as _);
-    if c.is_null() {
-        // free initial pointer
+pub unsafe extern "system" fn Java_ZilImageJni_freeByteMemoryNative(mut env: JNIEnv, _class: JClass, ptr: jlong) {
+    jni_guard(&mut env, "freeByteMemoryNative", |_| {
+        let ptr = ptr as *mut c_void;
         libc::free(ptr);
-        // then report we failed
-        return 0;
-    }
-    c as _
+        Ok(())
+    })
 }
 
-#[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_freeByteMemoryNative(_env: JNIEnv, _class: JClass, ptr: jlong) {
-    let ptr = ptr as *mut c_void;
-    libc::free(ptr);
+/// Write the active frame of an image into `output`, interleaving the channels
+fn write_active_frame(entry: &Entry, output: &mut [u8]) -> Result<(), JniErrors> {
+    let image = &entry.image;
+    let colorspace = image.colorspace();
+
+    if image.frames_len() == 0 {
+        return Err("No frames in image, did you load an image?".into());
+    }
+    let frame = image
+        .frames_ref()
+        .get(entry.active_frame)
+        .ok_or_else(|| animation::frame_out_of_range(entry.active_frame, image.frames_len()))?;
+    // write it to output
+    zune_image::utils::swizzle_channels(frame.channels_ref(colorspace, false), output)?;
+    Ok(())
 }
 
+/// # Safety
+///
+/// `native_out_ptr` must point to at least `native_out_length` writable bytes
 #[no_mangle]
 pub unsafe extern "system" fn Java_ZilImageJni_writeToBufferNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, native_out_ptr: jlong, native_out_length: jlong, array: JByteArray) {
+    jni_guard(&mut env, "writeToBufferNative", |env| {
+        // create the slice
+        let native_ptr = native_out_ptr as *mut u8;
+        let slice = std::slice::from_raw_parts_mut(native_ptr, native_out_length as usize);
 
-    // create the slice
-    let native_ptr = native_out_ptr as *mut u8;
-    let slice = std::slice::from_raw_parts_mut(native_ptr, native_out_length as usize);
-    let image = { &*(image_ptr as *const Image) };
-    let colorspace = image.colorspace();
+        handles::with_entry(image_ptr, |entry| write_active_frame(entry, slice))??;
 
-    match image.frames_ref().get(0) {
-        None => {
-            env.throw("No frames in image, did you load an image?").expect("Could not throw an exception");
-        }
-        Some(frame) => {
-            // write it to output
-            if let Err(e) = zune_image::utils::swizzle_channels(&frame.channels_ref(colorspace, false), slice) {
-                env.throw(e.to_string()).expect("Could not throw exception");
-            }
-        }
-    }
-    let (_, b, _) = slice.align_to::<i8>();
-    if let Err(e) = env.set_byte_array_region(&array, 0, b) {
-        env.throw(e.to_string()).expect("Could not throw error");
-    }
+        let (_, b, _) = slice.align_to::<i8>();
+        env.set_byte_array_region(&array, 0, b)?;
+        Ok(())
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_writeToNioBufferNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, buffer: JByteBuffer) {
-    let buffer_ptr = env.get_direct_buffer_address(&buffer).expect("Could not get buffer address");
-    let size = env.get_direct_buffer_capacity(&buffer).expect("Could not get buffer size");
+pub extern "system" fn Java_ZilImageJni_writeToNioBufferNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, buffer: JByteBuffer) {
+    jni_guard(&mut env, "writeToNioBufferNative", |env| {
+        let buffer_ptr = env.get_direct_buffer_address(&buffer)?;
+        let size = env.get_direct_buffer_capacity(&buffer)?;
 
-    let new_buff = std::slice::from_raw_parts_mut(buffer_ptr, size);
-    // create the slice
-    let image = { &*(image_ptr as *const Image) };
-    let colorspace = image.colorspace();
+        // safety: the jvm guarantees a direct buffer's address is valid for its capacity
+        let new_buff = unsafe { std::slice::from_raw_parts_mut(buffer_ptr, size) };
 
-    match image.frames_ref().get(0) {
-        None => {
-            env.throw("No frames in image, did you load an image?").expect("Could not throw an exception");
-        }
-        Some(frame) => {
-            // write it to output
-            if let Err(e) = zune_image::utils::swizzle_channels(&frame.channels_ref(colorspace, false), new_buff) {
-                env.throw(e.to_string()).expect("Could not throw exception");
-            }
-        }
-    }
+        handles::with_entry(image_ptr, |entry| write_active_frame(entry, new_buff))??;
+        Ok(())
+    })
 }
 
 #[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_getDepthNative(_env: JNIEnv, _class: JClass, image: jlong) -> jlong {
-    let image = { &*(image as *const Image) };
+pub extern "system" fn Java_ZilImageJni_getDepthNative(mut env: JNIEnv, _class: JClass, image: jlong) -> jlong {
+    jni_guard(&mut env, "getDepthNative", |_| {
+        let depth = handles::with_image(image, |image| image.depth())?;
+        Ok(depth_to_im_long(depth))
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_getColorSpaceNative(mut env: JNIEnv, _class: JClass, image: jlong) -> jlong {
+    jni_guard(&mut env, "getColorSpaceNative", |_| {
+        let colorspace = handles::with_image(image, |image| image.colorspace())?;
+        Ok(colorspace_to_im_long(colorspace))
+    })
+}
+
+pub(crate) fn depth_to_im_long(depth: BitDepth) -> jlong {
     // nb should match with definition in ZilImage otherwise UB
-    return match image.depth() {
+    match depth {
         BitDepth::Eight => 1,
         BitDepth::Sixteen => 2,
         BitDepth::Float32 => 3,
         _ => 0
-    };
+    }
 }
 
-#[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_getColorSpaceNative(_env: JNIEnv, _class: JClass, image: jlong) -> jlong {
+pub(crate) fn colorspace_to_im_long(colorspace: ColorSpace) -> jlong {
     // nb: should match the bindings in zilimage otherwise :)))
-    let image = { &*(image as *const Image) };
-
-    return match image.colorspace() {
+    match colorspace {
         ColorSpace::RGB => 1,
         ColorSpace::RGBA => 2,
         ColorSpace::YCbCr => 3,
@@ -264,17 +312,17 @@ pub unsafe extern "system" fn Java_ZilImageJni_getColorSpaceNative(_env: JNIEnv,
         ColorSpace::HSL => 11,
         ColorSpace::HSV => 12,
         _ => 0
-    };
+    }
 }
 
 fn im_long_to_depth(data: jlong) -> Option<BitDepth> {
-    return match data {
+    match data {
         0 => None,
         1 => Some(BitDepth::Eight),
         2 => Some(BitDepth::Sixteen),
         3 => Some(BitDepth::Float32),
         _ => None
-    };
+    }
 }
 
 fn im_long_to_colorspace(data: jlong) -> Option<ColorSpace> {
@@ -296,270 +344,263 @@ fn im_long_to_colorspace(data: jlong) -> Option<ColorSpace> {
     }
 }
 
-fn im_long_to_format(data: jlong) -> Option<ImageFormat> {
+pub(crate) fn im_long_to_format(data: jlong) -> Option<Format> {
     match data {
-        0 => Some(ImageFormat::Unknown),
-        1 => Some(ImageFormat::JPEG),
-        2 => Some(ImageFormat::PNG),
-        3 => Some(ImageFormat::PPM),
-        4 => Some(ImageFormat::PSD),
-        5 => Some(ImageFormat::Farbfeld),
-        6 => Some(ImageFormat::QOI),
-        7 => Some(ImageFormat::JPEG_XL),
-        8 => Some(ImageFormat::HDR),
-        9 => Some(ImageFormat::BMP),
+        0 => Some(ImageFormat::Unknown.into()),
+        1 => Some(ImageFormat::JPEG.into()),
+        2 => Some(ImageFormat::PNG.into()),
+        3 => Some(ImageFormat::PPM.into()),
+        4 => Some(ImageFormat::PSD.into()),
+        5 => Some(ImageFormat::Farbfeld.into()),
+        6 => Some(ImageFormat::QOI.into()),
+        7 => Some(ImageFormat::JPEG_XL.into()),
+        8 => Some(ImageFormat::HDR.into()),
+        9 => Some(ImageFormat::BMP.into()),
+        10 => Some(Format::WebP),
+        11 => Some(Format::Gif),
+        12 => Some(Format::Ico),
+        13 => Some(Format::Tiff),
+        14 => Some(Format::Raw),
+        15 => Some(Format::Exr),
+        16 => Some(Format::Svg),
         _ => None,
     }
 }
 
-#[no_mangle]
-pub unsafe extern "system" fn Java_ZilImageJni_saveToNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, filename: JString, format: jlong) {
-    let img_ptr = image_ptr as *const Image;
-    if img_ptr.is_null() {
-        env.throw("Image is null").expect("Could not throw exception");
-        return;
-    }
-    let input_str: String = env.get_string(&filename).expect("Could not get input string").into();
-    if let Some(format) = im_long_to_format(format) {
-        let img = &*img_ptr;
-        if let Err(err) = img.save_to(input_str, format) {
-            env.throw(err.to_string()).expect("Could not throw exception");
-        }
-    } else {
-        env.throw("Could not determine format specified").expect("Could not throw exception");
+pub(crate) fn format_to_im_long(format: Format) -> jlong {
+    // nb: should match im_long_to_format
+    match format {
+        Format::Zune(ImageFormat::JPEG) => 1,
+        Format::Zune(ImageFormat::PNG) => 2,
+        Format::Zune(ImageFormat::PPM) => 3,
+        Format::Zune(ImageFormat::PSD) => 4,
+        Format::Zune(ImageFormat::Farbfeld) => 5,
+        Format::Zune(ImageFormat::QOI) => 6,
+        Format::Zune(ImageFormat::JPEG_XL) => 7,
+        Format::Zune(ImageFormat::HDR) => 8,
+        Format::Zune(ImageFormat::BMP) => 9,
+        Format::WebP => 10,
+        Format::Gif => 11,
+        Format::Ico => 12,
+        Format::Tiff => 13,
+        Format::Raw => 14,
+        Format::Exr => 15,
+        Format::Svg => 16,
+        _ => 0
     }
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_convertColorSpaceNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, colorspace: jlong) {
-    if let Some(colorspace) = im_long_to_colorspace(colorspace) {
-        let filter = ColorspaceConv::new(colorspace);
-        exec_imgproc(&mut env, image_ptr, filter)
-    } else {
-        env.throw("Could not convert colorspace ").expect("Could not throw error");
-    }
+    jni_guard(&mut env, "convertColorSpaceNative", |_| {
+        let colorspace = im_long_to_colorspace(colorspace)
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown colorspace {colorspace}")))?;
+        exec_imgproc(image_ptr, ColorspaceConv::new(colorspace))
+    })
 }
 
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_convertDepthNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, depth: jlong) {
-    if let Some(depth) = im_long_to_depth(depth) {
-        let filter = Depth::new(depth);
-        exec_imgproc(&mut env, image_ptr, filter)
-    } else {
-        env.throw("Could not convert depth").expect("Could not throw error");
-    }
+    jni_guard(&mut env, "convertDepthNative", |_| {
+        let depth = im_long_to_depth(depth)
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown depth {depth}")))?;
+        exec_imgproc(image_ptr, Depth::new(depth))
+    })
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_stretchContrastNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, lower: f32, higher: f32) {
-    exec_imgproc(&mut env, image_ptr, StretchContrast::new(lower, higher));
+    jni_guard(&mut env, "stretchContrastNative", |_| {
+        exec_imgproc(image_ptr, StretchContrast::new(lower, higher))
+    })
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_scharrNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) {
-    exec_imgproc(&mut env, image_ptr, Scharr);
+    jni_guard(&mut env, "scharrNative", |_| exec_imgproc(image_ptr, Scharr))
 }
 
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_sobelNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) {
-    exec_imgproc(&mut env, image_ptr, Sobel);
+    jni_guard(&mut env, "sobelNative", |_| exec_imgproc(image_ptr, Sobel))
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_brightenNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, by: f32) {
-    exec_imgproc(&mut env, image_ptr, Brighten::new(by));
+    jni_guard(&mut env, "brightenNative", |_| exec_imgproc(image_ptr, Brighten::new(by)))
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_cloneNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jlong {
-    let image = image_ptr as *mut Image;
-    if image.is_null() {
-        env.throw("Image is null").expect("Failed to throw exception");
-        return 0 as _;
-    }
-    let image = unsafe { &*image };
-    let new_clone = image.clone();
-
-    let c = Box::new(new_clone);
-    // convert it to a pointer
-    Box::into_raw(c) as jlong
+    jni_guard(&mut env, "cloneNative", |_| {
+        let new_clone = handles::with_entry(image_ptr, |entry| entry.clone())?;
+        // the clone gets its own handle
+        Ok(handles::insert_entry(new_clone))
+    })
 }
 
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_transposeNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) {
-    exec_imgproc(&mut env, image_ptr, Transpose);
+    jni_guard(&mut env, "transposeNative", |_| exec_reorient(image_ptr, Transpose))
 }
 
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_flopNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) {
-    exec_imgproc(&mut env, image_ptr, Flop);
+    jni_guard(&mut env, "flopNative", |_| exec_reorient(image_ptr, Flop))
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_flipNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) {
-    exec_imgproc(&mut env, image_ptr, Flip);
+    jni_guard(&mut env, "flipNative", |_| exec_reorient(image_ptr, Flip))
 }
 
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_verticalFlipNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) {
-    exec_imgproc(&mut env, image_ptr, VerticalFlip);
+    jni_guard(&mut env, "verticalFlipNative", |_| exec_reorient(image_ptr, VerticalFlip))
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_boxBlurNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, radius: jlong) {
-    exec_imgproc(&mut env, image_ptr, BoxBlur::new(radius.clamp(0, 10000) as _));
+    jni_guard(&mut env, "boxBlurNative", |_| {
+        exec_imgproc(image_ptr, BoxBlur::new(radius.clamp(0, 10000) as _))
+    })
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_gaussianBlurNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, radius: jlong) {
-    exec_imgproc(&mut env, image_ptr, GaussianBlur::new(radius.clamp(0, 10000) as _));
+    jni_guard(&mut env, "gaussianBlurNative", |_| {
+        exec_imgproc(image_ptr, GaussianBlur::new(radius.clamp(0, 10000) as _))
+    })
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_histogramNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, histogram_map: JObject) {
-    let histogram = ChannelHistogram::new();
-    // exec filter
-    let image = image_ptr as *mut Image;
-    if image.is_null() {
-        env.throw("Image is null").expect("Failed to throw exception");
-        return;
-    }
-    let image = unsafe { &mut *image };
-
-    if let Err(err) = histogram.execute_impl(image) {
-        env.throw(err.to_string()).expect("Could not throw exception");
-    }
-
-    let map = env.get_map(&histogram_map).expect(" Could not convert ptr into map");
-    let histograms = histogram.histogram().expect("Could not get histograms");
-
-    for (c, histo) in histograms.iter().enumerate() {
-        let arr = env.new_long_array(histo.len() as _).expect("Could not create array");
-        let histo: Vec<i64> = histo.iter().map(|x| *x as i64).collect();
-        env.set_long_array_region(&arr, 0, histo.as_ref()).expect("Could not write to array");
-        let new_str = env.new_string(c.to_string()).expect("Could not create string");
-        map.put(&mut env, &new_str, &arr).expect("Could not write to array");
-    }
+    jni_guard(&mut env, "histogramNative", |env| {
+        let histogram = ChannelHistogram::new();
+        // exec filter
+        handles::with_image(image_ptr, |image| histogram.execute_impl(image))??;
+
+        let map = env.get_map(&histogram_map)?;
+        let histograms = histogram.histogram().map_err(|e| format!("Could not get histograms: {e}"))?;
+
+        for (c, histo) in histograms.iter().enumerate() {
+            let arr = env.new_long_array(histo.len() as _)?;
+            let histo: Vec<i64> = histo.iter().map(|x| *x as i64).collect();
+            env.set_long_array_region(&arr, 0, histo.as_ref())?;
+            let new_str = env.new_string(c.to_string())?;
+            map.put(env, &new_str, &arr)?;
+        }
+        Ok(())
+    })
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_exifMetadataNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, metadata_map: JObject) {
-    // exec filter
-    let image = image_ptr as *mut Image;
-    if image.is_null() {
-        env.throw("Image is null").expect("Failed to throw exception");
-        return;
-    }
-    let image = unsafe { &mut *image };
-
-
-    let map = env.get_map(&metadata_map).expect(" Could not convert ptr into map");
-    if let Some(meta) = image.metadata().exif() {
-        for field in meta {
-            let key = field.tag.to_string();
-
-            // some tags may have leading quotes yet they
-            // are enclosed in a string.
-            // This helps remove them
-            let value = field
-                .display_value()
-                .with_unit(field)
-                .to_string()
-                .trim_start_matches(|x| x == '\"')
-                .trim_end_matches(|x| x == '\"')
-                .to_string();
-
-            if value.len() < 100 {
-                // put small strings, some strings are too huge they mess up everything
-
-                let new_str_k = env.new_string(key).unwrap();
-                let new_str_v = env.new_string(value).unwrap();
-                map.put(&mut env, &new_str_k, &new_str_v).expect("Could not put the string into map");
+    jni_guard(&mut env, "exifMetadataNative", |env| {
+        let entry = handles::get(image_ptr)?;
+        let entry = entry.lock().unwrap_or_else(PoisonError::into_inner);
+
+        let map = env.get_map(&metadata_map)?;
+        if let Some(exif) = &entry.metadata.exif {
+            // values of unknown types have no display form
+            for field in exif.fields.iter().filter(|field| !matches!(field.value, exif::Value::Unknown(..))) {
+                let key = field.tag.to_string();
+
+                // some tags may have leading quotes yet they
+                // are enclosed in a string.
+                // This helps remove them
+                let value = field
+                    .display_value()
+                    .with_unit(field)
+                    .to_string()
+                    .trim_start_matches('\"')
+                    .trim_end_matches('\"')
+                    .to_string();
+
+                if value.len() < 100 {
+                    // put small strings, some strings are too huge they mess up everything
+
+                    let new_str_k = env.new_string(key)?;
+                    let new_str_v = env.new_string(value)?;
+                    map.put(env, &new_str_k, &new_str_v)?;
+                }
             }
         }
-    }
+        Ok(())
+    })
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_writeFourChannelToIntArrayNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, native_ptr: jlong, native_length: jlong, array: JIntArray) {
-    let env = &mut env;
-    let image = image_ptr as *mut Image;
-    if image.is_null() {
-        env.throw("Image is null").expect("Failed to throw exception");
-        return;
-    }
-    let image = unsafe { &mut *image };
-
-    let native_ptr = native_ptr as *mut u8;
-    let slice = unsafe { std::slice::from_raw_parts_mut(native_ptr, native_length as usize) };
-    let colorspace = image.colorspace();
-    if colorspace.num_components() != 4 {
-        env.throw("The colorspace is not 4 component colorspace").expect("Could not throw exception");
-    }
-    assert_eq!(colorspace.num_components(), 4);
-    // write to our output first
+    jni_guard(&mut env, "writeFourChannelToIntArrayNative", |env| {
+        let native_ptr = native_ptr as *mut u8;
+        let slice = unsafe { std::slice::from_raw_parts_mut(native_ptr, native_length as usize) };
 
-    match image.frames_ref().get(0) {
-        None => {
-            env.throw("No frames in image, did you load an image?").expect("Could not throw an exception");
-        }
-        Some(frame) => {
-            // write it to output
-            if let Err(e) = zune_image::utils::swizzle_channels(&frame.channels_ref(colorspace, false), slice) {
-                env.throw(e.to_string()).expect("Could not throw exception");
+        handles::with_entry(image_ptr, |entry| {
+            if entry.image.colorspace().num_components() != 4 {
+                return Err(JniErrors::InvalidArgument("The colorspace is not 4 component colorspace".to_string()));
             }
+            // write to our output first
+            write_active_frame(entry, slice)
+        })??;
+
+        // align to i32
+        // so this will do it in native endian,assuming the image is BGRA, native endian becomes
+        // argb
+        let (a, output, c) = unsafe { slice.align_to::<i32>() };
+        if !a.is_empty() || !c.is_empty() {
+            return Err(JniErrors::InvalidArgument("Native buffer is not aligned to a four byte boundary".to_string()));
         }
-    }
 
-    // align to i32
-    // so this will do it in native endian,assuming the image is BGRA, native endian becomes
-    // argb
-    let (a, output, c) = unsafe { slice.align_to::<i32>() };
-    assert_eq!(a.len(), 0);
-    assert_eq!(c.len(), 0);
-
-    // write to native endian
-    env.set_int_array_region(&array, 0, output).expect("Not an array region");
+        // write to native endian
+        env.set_int_array_region(&array, 0, output)?;
+        Ok(())
+    })
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_rotateNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, angle: f32) {
-    exec_imgproc(&mut env, image_ptr, Rotate::new(angle))
+    jni_guard(&mut env, "rotateNative", |_| exec_reorient(image_ptr, Rotate::new(angle)))
 }
 
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_hslAdjustNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, hue: f32, saturation: f32, lightness: f32) {
-    exec_imgproc(&mut env, image_ptr, HsvAdjust::new(hue, saturation, lightness))
+    jni_guard(&mut env, "hslAdjustNative", |_| {
+        exec_imgproc(image_ptr, HsvAdjust::new(hue, saturation, lightness))
+    })
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_medianBlurNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, radius: jlong) {
-    exec_imgproc(&mut env, image_ptr, Median::new(radius as _))
+    jni_guard(&mut env, "medianBlurNative", |_| exec_imgproc(image_ptr, Median::new(radius as _)))
 }
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_colorMatrixNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, array: JFloatArray) {
-    let mut out_array = [0.0f32; 20];
-    // write array to out array
-    if let Err(e) = env.get_float_array_region(array, 0, &mut out_array) {
-        env.throw(e.to_string()).expect("Cannot throw exception");
-    };
-    let filter = ColorMatrix::try_from_slice(&out_array).expect("Shouldn't happen");
-    exec_imgproc(&mut env, image_ptr, filter);
+    jni_guard(&mut env, "colorMatrixNative", |env| {
+        let mut out_array = [0.0f32; 20];
+        // write array to out array
+        env.get_float_array_region(array, 0, &mut out_array)?;
+        let filter = ColorMatrix::try_from_slice(&out_array)
+            .ok_or_else(|| JniErrors::InvalidArgument("Color matrix should have 20 elements".to_string()))?;
+        exec_imgproc(image_ptr, filter)
+    })
 }
 
 
 #[no_mangle]
 extern "system" fn Java_ZilImageJni_resizeImageNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, new_width: jlong, new_height: jlong) {
-    exec_imgproc(&mut env, image_ptr,
-                 Resize::new(new_width.clamp(0, 100000) as _,
-                             new_height.clamp(0, 1000000) as _,
-                             ResizeMethod::Bilinear),
-    )
-}
\ No newline at end of file
+    jni_guard(&mut env, "resizeImageNative", |_| {
+        exec_imgproc(image_ptr,
+                     Resize::new(new_width.clamp(0, 100000) as _,
+                                 new_height.clamp(0, 1000000) as _,
+                                 ResizeMethod::Bilinear),
+        )
+    })
+}
@@ -0,0 +1,922 @@
+//! Metadata carried alongside the pixels of an image
+//!
+//! zune reads EXIF from a few formats and writes none of it, so EXIF is
+//! read from the encoded bytes here, kept parsed in the image's handle
+//! and written back by the JPEG, PNG, WebP and JPEG-XL encoders.
+//!
+//! Before saving, EXIF is updated to describe the edited pixels, see
+//! [`Exif::for_image`]. XMP and IPTC are kept alongside it, see
+//! [`crate::xmp`] and [`crate::iptc`].
+use std::io::Cursor;
+
+use exif::experimental::Writer;
+use exif::{Context, DateTime, Field, In, Tag, Value};
+use jni::objects::{JClass, JObject, JObjectArray, JString, JValue};
+use jni::sys::{jbyteArray, jint, jlong, jobject, jobjectArray, jsize, jstring};
+use jni::JNIEnv;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::ImageErrors;
+use zune_image::image::Image;
+use zune_image::traits::OperationsTrait;
+use zune_imageprocs::flip::{Flip, VerticalFlip};
+use zune_imageprocs::rotate::Rotate;
+
+use crate::containers;
+use crate::errors::{jni_guard, JniErrors};
+use crate::format::Format;
+use crate::handles::{self, Entry};
+use crate::iptc::Iptc;
+use crate::xmp::Xmp;
+use crate::get_string;
+
+/// Identifier JPEG APP1 segments holding EXIF start with
+pub const JPEG_EXIF_MARKER: &[u8] = b"Exif\0\0";
+
+/// Written to the `Software` tag of saved files
+const SOFTWARE: &str = "Pixly";
+
+const EXIF_FIELD_CLASS: &str = "ZilExifField";
+const CAPTURE_INFO_CLASS: &str = "ZilCaptureInfo";
+
+/// Longest raw value, in bytes, and formatted value, in characters, returned
+/// along with the other fields, longer ones are fetched one at a time
+const MAX_INLINE_VALUE: usize = 256;
+
+/// Metadata of an image's pixels, written to the files it's saved to
+#[derive(Debug, Clone)]
+pub struct Metadata {
+    /// ICC profile of the pixels, `None` for sRGB
+    pub icc: Option<Vec<u8>>,
+    /// ICC profile embedded in the file the image was loaded from
+    pub source_icc: Option<Vec<u8>>,
+    pub exif: Option<Exif>,
+    pub xmp: Option<Xmp>,
+    /// IPTC records, only JPEG files carry them
+    pub iptc: Option<Iptc>,
+    /// EXIF orientation the pixels were turned upright from on load, 1 if they weren't
+    pub applied_orientation: u16
+}
+
+impl Default for Metadata {
+    fn default() -> Self {
+        Metadata {
+            icc: None,
+            source_icc: None,
+            exif: None,
+            xmp: None,
+            iptc: None,
+            applied_orientation: 1
+        }
+    }
+}
+
+impl Metadata {
+    /// Mark the pixels as upright after they were turned or mirrored
+    ///
+    /// They are saved the way they are shown, a viewer applying the
+    /// orientation they were loaded with would turn them a second time
+    pub fn reset_orientation(&mut self) {
+        if let Some(exif) = &mut self.exif {
+            if exif.field(Tag::Orientation).is_some() {
+                exif.set(Tag::Orientation, Value::Short(vec![1]));
+            }
+        }
+        if let Some(xmp) = &mut self.xmp {
+            xmp.remove("tiff:Orientation");
+        }
+    }
+}
+
+/// Seconds in a day, for shifting dates
+const SECONDS_PER_DAY: i64 = 86_400;
+
+/// TIFF tags describing how a file's pixels are stored: NewSubfileType,
+/// BitsPerSample, Compression, PhotometricInterpretation, the strip and
+/// tile tags, SamplesPerPixel, PlanarConfiguration, Predictor, SubIFDs,
+/// ExtraSamples and SampleFormat
+const TIFF_LAYOUT_TAGS: [u16; 17] = [
+    0x00FE, 0x0102, 0x0103, 0x0106, 0x0111, 0x0115, 0x0116, 0x0117, 0x011C, 0x013D, 0x0142, 0x0143, 0x0144, 0x0145, 0x014A,
+    0x0152, 0x0153
+];
+
+/// Parsed EXIF, the fields of every IFD along with the thumbnail
+#[derive(Debug, Clone)]
+pub struct Exif {
+    pub fields: Vec<Field>,
+    /// JPEG thumbnail of the second IFD
+    pub thumbnail: Option<Vec<u8>>,
+    pub little_endian: bool,
+    /// Value fields, as stored, of the entries whose type EXIF doesn't
+    /// define, those are read but never written back out
+    pub unknown_values: Vec<(In, Tag, [u8; 4])>
+}
+
+impl Exif {
+    /// EXIF without any fields, for images that had none
+    pub fn empty() -> Exif {
+        Exif {
+            fields: vec![],
+            thumbnail: None,
+            little_endian: true,
+            unknown_values: vec![]
+        }
+    }
+
+    /// Parse EXIF stored as a TIFF structure
+    pub fn parse(tiff: &[u8]) -> Option<Exif> {
+        let (fields, little_endian) = exif::parse_exif(tiff).ok()?;
+        let uint = |tag: Tag| {
+            let field = fields.iter().find(|field| field.tag == tag && field.ifd_num == In::THUMBNAIL)?;
+            field.value.get_uint(0).map(|value| value as usize)
+        };
+        let thumbnail = match (uint(Tag::JPEGInterchangeFormat), uint(Tag::JPEGInterchangeFormatLength)) {
+            (Some(offset), Some(length)) => tiff.get(offset..offset.checked_add(length)?).map(<[u8]>::to_vec),
+            _ => None
+        };
+        // the parser can't size values of unknown types, the offset is that of the entry's value field
+        let unknown_values = fields
+            .iter()
+            .filter_map(|field| match field.value {
+                Value::Unknown(_, _, offset) => {
+                    let value = tiff.get(offset as usize..offset as usize + 4)?.try_into().ok()?;
+                    Some((field.ifd_num, field.tag, value))
+                }
+                _ => None
+            })
+            .collect();
+        Some(Exif {
+            fields,
+            thumbnail,
+            little_endian,
+            unknown_values
+        })
+    }
+
+    /// Read the EXIF embedded in an encoded image, if it has any
+    pub fn extract(data: &[u8], format: Format) -> Option<Exif> {
+        let tiff = match format {
+            Format::Zune(ImageFormat::JPEG) => containers::jpeg_segments(data)
+                .filter(|(marker, _)| *marker == 0xE1)
+                .find_map(|(_, segment)| segment.strip_prefix(JPEG_EXIF_MARKER))?,
+            Format::Zune(ImageFormat::PNG) => containers::png_chunks(data)
+                .find(|(kind, _)| *kind == b"eXIf")
+                .map(|(_, chunk)| chunk)?,
+            Format::WebP => {
+                let (_, chunk) = containers::riff_chunks(data).find(|(kind, _)| *kind == b"EXIF")?;
+                // some writers keep the JPEG identifier
+                chunk.strip_prefix(JPEG_EXIF_MARKER).unwrap_or(chunk)
+            }
+            Format::Zune(ImageFormat::JPEG_XL) => {
+                let (_, payload) = containers::bmff_boxes(data).find(|(kind, _)| *kind == b"Exif")?;
+                // offset of the TIFF header within the rest of the box
+                let offset = u32::from_be_bytes(payload.get(..4)?.try_into().ok()?) as usize;
+                payload.get(4usize.checked_add(offset)?..)?
+            }
+            // the file itself is a TIFF structure, the tags laying out its pixels don't describe other files
+            Format::Tiff | Format::Raw => {
+                let mut exif = Exif::parse(data)?;
+                exif.fields
+                    .retain(|field| !(field.tag.context() == Context::Tiff && TIFF_LAYOUT_TAGS.contains(&field.tag.number())));
+                return Some(exif);
+            }
+            _ => return None
+        };
+        Exif::parse(tiff)
+    }
+
+    /// The field for `tag`, searching the main image's IFDs
+    pub fn field(&self, tag: Tag) -> Option<&Field> {
+        self.fields
+            .iter()
+            .find(|field| field.tag == tag && field.ifd_num == In::PRIMARY)
+    }
+
+    /// Replace or add the field for `tag` in the main image's IFDs
+    pub fn set(&mut self, tag: Tag, value: Value) {
+        match self
+            .fields
+            .iter_mut()
+            .find(|field| field.tag == tag && field.ifd_num == In::PRIMARY)
+        {
+            Some(field) => field.value = value,
+            None => self.fields.push(Field {
+                tag,
+                ifd_num: In::PRIMARY,
+                value
+            })
+        }
+    }
+
+    /// EXIF orientation, 1-8, 1 if the tag is missing or invalid
+    pub fn orientation(&self) -> u16 {
+        self.field(Tag::Orientation)
+            .and_then(|field| field.value.get_uint(0))
+            .and_then(|orientation| u16::try_from(orientation).ok())
+            .filter(|orientation| (1..=8).contains(orientation))
+            .unwrap_or(1)
+    }
+
+    /// Remove the fields whose tag is named `name` from every IFD
+    ///
+    /// Names are the keys of Kotlin's `exifMetadata`, e.g. `BodySerialNumber`
+    pub fn remove(&mut self, name: &str) {
+        self.fields.retain(|field| field.tag.to_string() != name);
+    }
+
+    pub fn remove_group(&mut self, group: ExifGroup) {
+        match group {
+            ExifGroup::Gps => self.fields.retain(|field| field.tag.context() != Context::Gps),
+            ExifGroup::MakerNote => self.fields.retain(|field| field.tag != Tag::MakerNote),
+            ExifGroup::Thumbnail => {
+                self.fields.retain(|field| field.ifd_num != In::THUMBNAIL);
+                self.thumbnail = None;
+            }
+        }
+    }
+
+    /// Set a text field of the main image, `None` removes it
+    pub fn set_text(&mut self, tag: Tag, text: Option<&str>) -> Result<(), JniErrors> {
+        let Some(text) = text else {
+            self.fields.retain(|field| !(field.tag == tag && field.ifd_num == In::PRIMARY));
+            return Ok(());
+        };
+        // EXIF strings are NUL terminated
+        if text.contains('\0') {
+            return Err(JniErrors::InvalidArgument(format!("{tag} should not contain NUL characters")));
+        }
+        self.set(tag, Value::Ascii(vec![text.as_bytes().to_vec()]));
+        Ok(())
+    }
+
+    /// Set a date field, `value` is in the EXIF format, `YYYY:MM:DD HH:MM:SS`
+    pub fn set_date(&mut self, date: ExifDate, value: &str) -> Result<(), JniErrors> {
+        let seconds = parse_date(value.as_bytes()).ok_or_else(|| {
+            JniErrors::InvalidArgument(format!("Invalid date {value:?}, expected YYYY:MM:DD HH:MM:SS"))
+        })?;
+        self.set(date.tag(), Value::Ascii(vec![format_date(seconds).into_bytes()]));
+        Ok(())
+    }
+
+    /// Move every date of the main image by `seconds`, e.g. to fix a camera clock set to the wrong time zone
+    ///
+    /// Blank or malformed dates are left alone
+    pub fn shift_dates(&mut self, seconds: i64) -> Result<(), JniErrors> {
+        for date in [ExifDate::Modified, ExifDate::Original, ExifDate::Digitized] {
+            let current = self
+                .field(date.tag())
+                .and_then(|field| match &field.value {
+                    Value::Ascii(strings) => strings.first().and_then(|value| parse_date(value)),
+                    _ => None
+                });
+            let Some(current) = current else {
+                continue;
+            };
+            let shifted = current
+                .checked_add(seconds)
+                .filter(|shifted| (0..=9999).contains(&civil_from_days(shifted.div_euclid(SECONDS_PER_DAY)).0))
+                .ok_or_else(|| JniErrors::InvalidArgument(format!("Shifting {} by {seconds} seconds leaves years 0-9999", date.tag())))?;
+
+            self.set(date.tag(), Value::Ascii(vec![format_date(shifted).into_bytes()]));
+        }
+        Ok(())
+    }
+
+    /// The first string of a text field of the main image, `None` if it's missing or blank
+    fn text(&self, tag: Tag) -> Option<String> {
+        match &self.field(tag)?.value {
+            Value::Ascii(strings) => Some(String::from_utf8_lossy(strings.first()?).trim().to_string()).filter(|text| !text.is_empty()),
+            _ => None
+        }
+    }
+
+    /// A value of a rational field of the main image
+    fn rational(&self, tag: Tag, index: usize) -> Option<f64> {
+        let value = match &self.field(tag)?.value {
+            Value::Rational(values) => values.get(index)?.to_f64(),
+            Value::SRational(values) => values.get(index)?.to_f64(),
+            _ => return None
+        };
+        // a zero denominator marks an unknown value
+        value.is_finite().then_some(value)
+    }
+
+    /// A GPS coordinate in decimal degrees, negative if its reference is `negative`
+    fn coordinate(&self, tag: Tag, reference: Tag, negative: &str) -> Option<f64> {
+        // degrees, minutes and seconds
+        let degrees = self.rational(tag, 0)? + self.rational(tag, 1).unwrap_or(0.0) / 60.0 + self.rational(tag, 2).unwrap_or(0.0) / 3600.0;
+
+        match self.text(reference).as_deref() {
+            Some(reference) if reference.eq_ignore_ascii_case(negative) => Some(-degrees),
+            _ => Some(degrees)
+        }
+    }
+
+    /// When the picture was taken, along with its sub seconds and offset from UTC if known
+    ///
+    /// Falls back to when it was digitized, then when the file was changed
+    fn capture_date(&self) -> Option<DateTime> {
+        let dates = [
+            (Tag::DateTimeOriginal, Tag::SubSecTimeOriginal, Tag::OffsetTimeOriginal),
+            (Tag::DateTimeDigitized, Tag::SubSecTimeDigitized, Tag::OffsetTimeDigitized),
+            (Tag::DateTime, Tag::SubSecTime, Tag::OffsetTime)
+        ];
+        dates.iter().find_map(|(date, subsec, offset)| {
+            let value = self.text(*date)?;
+            // reject dates that don't exist
+            parse_date(value.as_bytes())?;
+            let mut date = DateTime::from_ascii(value.as_bytes()).ok()?;
+
+            // malformed sub seconds and offsets are left out
+            if let Some(subsec) = self.text(*subsec) {
+                let _ = date.parse_subsec(subsec.as_bytes());
+            }
+            if let Some(offset) = self.text(*offset) {
+                let _ = date.parse_offset(offset.as_bytes());
+            }
+            Some(date)
+        })
+    }
+
+    /// Interpret the fields describing where, when and how the picture was taken
+    pub fn capture_info(&self) -> CaptureInfo {
+        let altitude = self.rational(Tag::GPSAltitude, 0).map(|altitude| {
+            // 1 marks an altitude below sea level
+            match self.field(Tag::GPSAltitudeRef).and_then(|field| field.value.get_uint(0)) {
+                Some(1) => -altitude,
+                _ => altitude
+            }
+        });
+        let (date_time, offset_minutes) = match self.capture_date() {
+            Some(date) => {
+                let mut text = format!(
+                    "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+                    date.year, date.month, date.day, date.hour, date.minute, date.second
+                );
+                if let Some(nanosecond) = date.nanosecond.filter(|nanosecond| *nanosecond != 0) {
+                    text.push_str(format!(".{nanosecond:09}").trim_end_matches('0'));
+                }
+                (Some(text), date.offset)
+            }
+            None => (None, None)
+        };
+        CaptureInfo {
+            make: self.text(Tag::Make),
+            model: self.text(Tag::Model),
+            lens_model: self.text(Tag::LensModel),
+            latitude: self.coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S"),
+            longitude: self.coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"),
+            altitude,
+            date_time,
+            offset_minutes,
+            exposure_time: self.rational(Tag::ExposureTime, 0),
+            f_number: self.rational(Tag::FNumber, 0),
+            iso: self.field(Tag::PhotographicSensitivity).and_then(|field| field.value.get_uint(0)),
+            focal_length: self.rational(Tag::FocalLength, 0)
+        }
+    }
+
+    /// A copy describing a `width` x `height` image we wrote
+    ///
+    /// The thumbnail shows the unedited image and is dropped, the pixel
+    /// dimensions and software tags are set to match the saved file.
+    /// The orientation is kept, it's reset when [`apply_orientation`] or an
+    /// edit turns the pixels, see [`Metadata::reset_orientation`].
+    pub fn for_image(&self, width: usize, height: usize) -> Exif {
+        let mut exif = Exif {
+            fields: self
+                .fields
+                .iter()
+                .filter(|field| field.ifd_num == In::PRIMARY && !matches!(field.value, Value::Unknown(..)))
+                .cloned()
+                .collect(),
+            thumbnail: None,
+            little_endian: self.little_endian,
+            unknown_values: vec![]
+        };
+        let (width, height) = (width as u32, height as u32);
+
+        exif.set(Tag::PixelXDimension, Value::Long(vec![width]));
+        exif.set(Tag::PixelYDimension, Value::Long(vec![height]));
+        // only TIFF based files are expected to carry these, update them if present
+        if exif.field(Tag::ImageWidth).is_some() {
+            exif.set(Tag::ImageWidth, Value::Long(vec![width]));
+            exif.set(Tag::ImageLength, Value::Long(vec![height]));
+        }
+        exif.set(Tag::Software, Value::Ascii(vec![SOFTWARE.as_bytes().to_vec()]));
+        exif
+    }
+
+    /// Serialize as a TIFF structure, the form every format embeds
+    pub fn to_tiff(&self) -> Result<Vec<u8>, JniErrors> {
+        let mut writer = Writer::new();
+        // fields of unknown types can't be written
+        for field in self.fields.iter().filter(|field| !matches!(field.value, Value::Unknown(..))) {
+            writer.push_field(field);
+        }
+        if let Some(thumbnail) = &self.thumbnail {
+            writer.set_jpeg(thumbnail, In::THUMBNAIL);
+        }
+        let mut output = Cursor::new(Vec::new());
+        writer
+            .write(&mut output, self.little_endian)
+            .map_err(|e| ImageErrors::GenericString(format!("Could not write EXIF: {e}")))?;
+        Ok(output.into_inner())
+    }
+}
+
+
+/// Where, when and how a picture was taken, mirrors `ZilCaptureInfo` in Kotlin
+#[derive(Debug, Clone)]
+pub struct CaptureInfo {
+    pub make: Option<String>,
+    pub model: Option<String>,
+    pub lens_model: Option<String>,
+    /// Decimal degrees, negative south of the equator
+    pub latitude: Option<f64>,
+    /// Decimal degrees, negative west of Greenwich
+    pub longitude: Option<f64>,
+    /// Meters, negative below sea level
+    pub altitude: Option<f64>,
+    /// Local time in the ISO 8601 format, without an offset
+    pub date_time: Option<String>,
+    /// Offset of `date_time` from UTC
+    pub offset_minutes: Option<i16>,
+    /// Seconds
+    pub exposure_time: Option<f64>,
+    pub f_number: Option<f64>,
+    pub iso: Option<u32>,
+    /// Millimeters
+    pub focal_length: Option<f64>
+}
+
+/// Tags removed together, mirrors `ZilExifGroup` in Kotlin
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ExifGroup {
+    /// Location, along with the time and altitude it was recorded at
+    Gps,
+    /// Camera specific data, often holding serial numbers
+    MakerNote,
+    /// The embedded preview and its IFD
+    Thumbnail
+}
+
+impl ExifGroup {
+    pub fn from_ordinal(ordinal: i32) -> Option<ExifGroup> {
+        match ordinal {
+            0 => Some(ExifGroup::Gps),
+            1 => Some(ExifGroup::MakerNote),
+            2 => Some(ExifGroup::Thumbnail),
+            _ => None
+        }
+    }
+}
+
+/// Date fields of the main image, mirrors `ZilExifDate` in Kotlin
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ExifDate {
+    /// When the file was last changed
+    Modified,
+    /// When the picture was taken
+    Original,
+    /// When the picture was stored digitally, e.g. when a film was scanned
+    Digitized
+}
+
+impl ExifDate {
+    pub fn from_ordinal(ordinal: i32) -> Option<ExifDate> {
+        match ordinal {
+            0 => Some(ExifDate::Modified),
+            1 => Some(ExifDate::Original),
+            2 => Some(ExifDate::Digitized),
+            _ => None
+        }
+    }
+
+    fn tag(self) -> Tag {
+        match self {
+            ExifDate::Modified => Tag::DateTime,
+            ExifDate::Original => Tag::DateTimeOriginal,
+            ExifDate::Digitized => Tag::DateTimeDigitized
+        }
+    }
+}
+
+/// Days since 1970-01-01 of a date in the proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    // count from March so the leap day ends the year
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+
+    era * 146_097 + day_of_era - 719_468
+}
+
+/// Year, month and day of a count of days since 1970-01-01, the inverse of [`days_from_civil`]
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 3 } else { month - 9 };
+
+    (year_of_era + era * 400 + i64::from(month <= 2), month, day)
+}
+
+/// Seconds since 1970-01-01 of an EXIF date, `None` if it's blank or not a real date
+fn parse_date(value: &[u8]) -> Option<i64> {
+    let date = DateTime::from_ascii(value).ok()?;
+    let (year, month, day) = (i64::from(date.year), i64::from(date.month), i64::from(date.day));
+
+    // from_ascii only checks the digits, reject dates such as February 30th
+    let days = days_from_civil(year, month, day);
+    if !(1..=12).contains(&month) || civil_from_days(days) != (year, month, day) {
+        return None;
+    }
+    if date.hour > 23 || date.minute > 59 || date.second > 59 {
+        return None;
+    }
+    let time = i64::from(date.hour) * 3600 + i64::from(date.minute) * 60 + i64::from(date.second);
+    Some(days * SECONDS_PER_DAY + time)
+}
+
+/// An EXIF date from seconds since 1970-01-01
+fn format_date(seconds: i64) -> String {
+    let (year, month, day) = civil_from_days(seconds.div_euclid(SECONDS_PER_DAY));
+    let time = seconds.rem_euclid(SECONDS_PER_DAY);
+
+    format!("{year:04}:{month:02}:{day:02} {:02}:{:02}:{:02}", time / 3600, time / 60 % 60, time % 60)
+}
+
+/// Name of the IFD a field belongs to, `IFD0` and `IFD1` for the main
+/// image and the thumbnail, `Exif`, `GPS` and `Interop` for the IFDs the
+/// main image points to
+fn ifd_name(field: &Field) -> String {
+    let sub_ifd = match field.tag.context() {
+        Context::Tiff => return format!("IFD{}", field.ifd_num.index()),
+        Context::Exif => "Exif",
+        Context::Gps => "GPS",
+        // the only other context, the enum is non exhaustive
+        _ => "Interop"
+    };
+    match field.ifd_num {
+        In::PRIMARY => sub_ifd.to_string(),
+        ifd => format!("IFD{}/{sub_ifd}", ifd.index())
+    }
+}
+
+/// TIFF type code, value count and bytes of a field's value as stored in
+/// a file with the byte order of `exif`
+///
+/// Values of unknown types are the four bytes of their entry's value field
+fn raw_value(exif: &Exif, field: &Field) -> (u16, usize, Vec<u8>) {
+    let little_endian = exif.little_endian;
+    macro_rules! numbers {
+        ($values:expr) => {
+            $values
+                .iter()
+                .flat_map(|v| if little_endian { v.to_le_bytes() } else { v.to_be_bytes() })
+                .collect()
+        };
+    }
+    match &field.value {
+        Value::Byte(bytes) => (1, bytes.len(), bytes.clone()),
+        Value::Ascii(strings) => {
+            // each string keeps its NUL terminator
+            let bytes: Vec<u8> = strings.iter().flat_map(|string| string.iter().copied().chain([0])).collect();
+            (2, bytes.len(), bytes)
+        }
+        Value::Short(values) => (3, values.len(), numbers!(values)),
+        Value::Long(values) => (4, values.len(), numbers!(values)),
+        Value::Rational(values) => (5, values.len(), numbers!(values.iter().flat_map(|v| [v.num, v.denom]).collect::<Vec<_>>())),
+        Value::SByte(values) => (6, values.len(), values.iter().map(|v| *v as u8).collect()),
+        Value::Undefined(bytes, _) => (7, bytes.len(), bytes.clone()),
+        Value::SShort(values) => (8, values.len(), numbers!(values)),
+        Value::SLong(values) => (9, values.len(), numbers!(values)),
+        Value::SRational(values) => (10, values.len(), numbers!(values.iter().flat_map(|v| [v.num, v.denom]).collect::<Vec<_>>())),
+        Value::Float(values) => (11, values.len(), numbers!(values)),
+        Value::Double(values) => (12, values.len(), numbers!(values)),
+        Value::Unknown(kind, count, _) => {
+            let value = exif
+                .unknown_values
+                .iter()
+                .find(|(ifd, tag, _)| (*ifd, *tag) == (field.ifd_num, field.tag))
+                .map_or_else(Vec::new, |(_, _, value)| value.to_vec());
+            (*kind, *count as usize, value)
+        }
+    }
+}
+
+/// A field's value formatted for display, strings are shown as is
+fn formatted(exif: &Exif, field: &Field) -> String {
+    match &field.value {
+        Value::Ascii(strings) => strings
+            .iter()
+            .map(|string| String::from_utf8_lossy(string))
+            .collect::<Vec<_>>()
+            .join(", "),
+        Value::Unknown(kind, count, _) => {
+            let (_, _, value) = raw_value(exif, field);
+            let hex: Vec<String> = value.iter().map(|byte| format!("{byte:02x}")).collect();
+            format!("Unknown type {kind}, {count} values, value field {}", hex.join(" "))
+        }
+        _ => field.display_value().with_unit(field).to_string()
+    }
+}
+
+/// Turn the pixels of every frame upright according to an EXIF orientation
+///
+/// zune's `Flip` turns the image around rather than mirroring it, so a
+/// horizontal mirror is that undone vertically. Its `Transpose` fails on
+/// images narrower or shorter than 8 pixels, transposing is done as a
+/// rotation followed by a vertical mirror instead.
+pub fn orient(image: &mut Image, orientation: u16) -> Result<(), ImageErrors> {
+    match orientation {
+        2 => {
+            Flip.execute(image)?;
+            VerticalFlip.execute(image)
+        }
+        3 => Flip.execute(image),
+        4 => VerticalFlip.execute(image),
+        5 => {
+            Rotate::new(270.0).execute(image)?;
+            VerticalFlip.execute(image)
+        }
+        6 => Rotate::new(90.0).execute(image),
+        7 => {
+            Rotate::new(90.0).execute(image)?;
+            VerticalFlip.execute(image)
+        }
+        8 => Rotate::new(270.0).execute(image),
+        _ => Ok(())
+    }
+}
+
+/// Rotate and mirror the image as its EXIF orientation asks, then reset the tag to 1
+///
+/// The orientation applied is kept in the entry's metadata
+pub fn apply_orientation(entry: &mut Entry) -> Result<(), JniErrors> {
+    let Some(exif) = &entry.metadata.exif else {
+        return Ok(());
+    };
+    let orientation = exif.orientation();
+    if orientation == 1 {
+        return Ok(());
+    }
+    orient(&mut entry.image, orientation)?;
+
+    entry.metadata.reset_orientation();
+    entry.metadata.applied_orientation = orientation;
+    Ok(())
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_appliedOrientationNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jint {
+    jni_guard(&mut env, "appliedOrientationNative", |_| {
+        Ok(handles::with_entry(image_ptr, |entry| entry.metadata.applied_orientation)?.into())
+    })
+}
+
+/// Run `edit` on the EXIF of an image, creating it if the image has none
+///
+/// Edits that only remove data shouldn't create it, `create` is false for those
+fn edit_exif<F>(image_ptr: jlong, create: bool, edit: F) -> Result<(), JniErrors>
+where
+    F: FnOnce(&mut Exif) -> Result<(), JniErrors>
+{
+    handles::with_entry(image_ptr, |entry| match &mut entry.metadata.exif {
+        Some(exif) => edit(exif),
+        None if create => edit(entry.metadata.exif.insert(Exif::empty())),
+        None => Ok(())
+    })?
+}
+
+/// Read the ordinal of a Kotlin enum passed as `name`
+pub fn ordinal(env: &mut JNIEnv, value: &JObject, name: &str) -> Result<i32, JniErrors> {
+    if value.is_null() {
+        return Err(JniErrors::InvalidArgument(format!("{name} should not be null")));
+    }
+    Ok(env.call_method(value, "ordinal", "()I", &[])?.i()?)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_removeExifTagsNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, tags: JObjectArray) {
+    jni_guard(&mut env, "removeExifTagsNative", |env| {
+        let mut names = Vec::new();
+        for index in 0..env.get_array_length(&tags)? {
+            let name = JString::from(env.get_object_array_element(&tags, index)?);
+            names.push(get_string(env, &name)?);
+        }
+        edit_exif(image_ptr, false, |exif| {
+            names.iter().for_each(|name| exif.remove(name));
+            Ok(())
+        })
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_removeExifGroupNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, group: JObject) {
+    jni_guard(&mut env, "removeExifGroupNative", |env| {
+        let ordinal = ordinal(env, &group, "group")?;
+        let group = ExifGroup::from_ordinal(ordinal)
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown EXIF group {ordinal}")))?;
+        edit_exif(image_ptr, false, |exif| {
+            exif.remove_group(group);
+            Ok(())
+        })
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setExifDateNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, date: JObject, value: JString) {
+    jni_guard(&mut env, "setExifDateNative", |env| {
+        let ordinal = ordinal(env, &date, "date")?;
+        let date = ExifDate::from_ordinal(ordinal)
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown EXIF date {ordinal}")))?;
+        let value = get_string(env, &value)?;
+        edit_exif(image_ptr, true, |exif| exif.set_date(date, &value))
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_shiftExifDatesNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, seconds: jlong) {
+    jni_guard(&mut env, "shiftExifDatesNative", |_| edit_exif(image_ptr, false, |exif| exif.shift_dates(seconds)))
+}
+
+/// Set a text field from a nullable Kotlin string, null removes it
+fn set_exif_text(env: &mut JNIEnv, image_ptr: jlong, tag: Tag, text: &JString) -> Result<(), JniErrors> {
+    let text = if text.is_null() { None } else { Some(get_string(env, text)?) };
+    edit_exif(image_ptr, text.is_some(), |exif| exif.set_text(tag, text.as_deref()))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setExifArtistNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, artist: JString) {
+    jni_guard(&mut env, "setExifArtistNative", |env| set_exif_text(env, image_ptr, Tag::Artist, &artist))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setExifCopyrightNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, copyright: JString) {
+    jni_guard(&mut env, "setExifCopyrightNative", |env| set_exif_text(env, image_ptr, Tag::Copyright, &copyright))
+}
+
+/// Convert every EXIF field of an image to a Kotlin `ZilExifField`
+fn exif_fields<'local>(env: &mut JNIEnv<'local>, exif: &Exif) -> Result<JObjectArray<'local>, JniErrors> {
+    let array = env.new_object_array(exif.fields.len() as jsize, EXIF_FIELD_CLASS, JObject::null())?;
+
+    for (index, field) in exif.fields.iter().enumerate() {
+        // makernotes can hold hundreds of fields, don't keep a local reference for each
+        env.with_local_frame(8, |env| -> Result<(), JniErrors> {
+            let (kind, count, raw) = raw_value(exif, field);
+            let text = formatted(exif, field);
+            let complete = raw.len() <= MAX_INLINE_VALUE && text.chars().count() <= MAX_INLINE_VALUE;
+
+            let ifd = JObject::from(env.new_string(ifd_name(field))?);
+            let name = JObject::from(env.new_string(field.tag.to_string())?);
+            let raw = match raw.len() <= MAX_INLINE_VALUE {
+                true => JObject::from(env.byte_array_from_slice(&raw)?),
+                false => JObject::null()
+            };
+            let text: String = match complete {
+                true => text,
+                false => text.chars().take(MAX_INLINE_VALUE).chain(['…']).collect()
+            };
+            let text = JObject::from(env.new_string(text)?);
+
+            let object = env.new_object(
+                EXIF_FIELD_CLASS,
+                "(Ljava/lang/String;ILjava/lang/String;IJ[BZLjava/lang/String;Z)V",
+                &[
+                    JValue::Object(&ifd),
+                    JValue::Int(jint::from(field.tag.number())),
+                    JValue::Object(&name),
+                    JValue::Int(jint::from(kind)),
+                    JValue::Long(count as _),
+                    JValue::Object(&raw),
+                    JValue::Bool(exif.little_endian.into()),
+                    JValue::Object(&text),
+                    JValue::Bool(complete.into())
+                ]
+            )?;
+            env.set_object_array_element(&array, index as jsize, object)?;
+            Ok(())
+        })?;
+    }
+    Ok(array)
+}
+
+/// Find a field by the IFD and tag id reported in a `ZilExifField`
+fn find_field<T, F>(env: &mut JNIEnv, image_ptr: jlong, ifd: &JString, tag: jint, func: F) -> Result<Option<T>, JniErrors>
+where
+    F: FnOnce(&Exif, &Field) -> T
+{
+    let ifd = get_string(env, ifd)?;
+    handles::with_entry(image_ptr, |entry| {
+        let exif = entry.metadata.exif.as_ref()?;
+        let field = exif
+            .fields
+            .iter()
+            .find(|field| jint::from(field.tag.number()) == tag && ifd_name(field) == ifd)?;
+        Some(func(exif, field))
+    })
+    .map_err(JniErrors::from)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_exifFieldsNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jobjectArray {
+    jni_guard(&mut env, "exifFieldsNative", |env| {
+        let exif = handles::with_entry(image_ptr, |entry| entry.metadata.exif.clone())?.unwrap_or_else(Exif::empty);
+        exif_fields(env, &exif)
+    })
+    .into_raw()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_exifFieldBytesNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, ifd: JString, tag: jint) -> jbyteArray {
+    jni_guard(&mut env, "exifFieldBytesNative", |env| {
+        match find_field(env, image_ptr, &ifd, tag, |exif, field| raw_value(exif, field).2)? {
+            Some(raw) => Ok(env.byte_array_from_slice(&raw)?),
+            None => Ok(JObject::null().into())
+        }
+    })
+    .into_raw()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_exifFieldStringNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, ifd: JString, tag: jint) -> jstring {
+    jni_guard(&mut env, "exifFieldStringNative", |env| {
+        match find_field(env, image_ptr, &ifd, tag, formatted)? {
+            Some(text) => Ok(env.new_string(text)?),
+            None => Ok(JObject::null().into())
+        }
+    })
+    .into_raw()
+}
+
+pub fn optional_string<'local>(env: &mut JNIEnv<'local>, value: Option<&str>) -> Result<JObject<'local>, JniErrors> {
+    match value {
+        Some(value) => Ok(env.new_string(value)?.into()),
+        None => Ok(JObject::null())
+    }
+}
+
+/// A `java.lang.Double`, the type of a nullable Kotlin `Double`
+fn optional_double<'local>(env: &mut JNIEnv<'local>, value: Option<f64>) -> Result<JObject<'local>, JniErrors> {
+    match value {
+        Some(value) => Ok(env.new_object("java/lang/Double", "(D)V", &[JValue::Double(value)])?),
+        None => Ok(JObject::null())
+    }
+}
+
+/// A `java.lang.Integer`, the type of a nullable Kotlin `Int`
+fn optional_int<'local>(env: &mut JNIEnv<'local>, value: Option<i64>) -> Result<JObject<'local>, JniErrors> {
+    match value.and_then(|value| jint::try_from(value).ok()) {
+        Some(value) => Ok(env.new_object("java/lang/Integer", "(I)V", &[JValue::Int(value)])?),
+        None => Ok(JObject::null())
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_captureInfoNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jobject {
+    jni_guard(&mut env, "captureInfoNative", |env| {
+        let Some(info) = handles::with_entry(image_ptr, |entry| entry.metadata.exif.as_ref().map(Exif::capture_info))? else {
+            return Ok(JObject::null());
+        };
+        let make = optional_string(env, info.make.as_deref())?;
+        let model = optional_string(env, info.model.as_deref())?;
+        let lens_model = optional_string(env, info.lens_model.as_deref())?;
+        let latitude = optional_double(env, info.latitude)?;
+        let longitude = optional_double(env, info.longitude)?;
+        let altitude = optional_double(env, info.altitude)?;
+        let date_time = optional_string(env, info.date_time.as_deref())?;
+        let offset_minutes = optional_int(env, info.offset_minutes.map(i64::from))?;
+        let exposure_time = optional_double(env, info.exposure_time)?;
+        let f_number = optional_double(env, info.f_number)?;
+        let iso = optional_int(env, info.iso.map(i64::from))?;
+        let focal_length = optional_double(env, info.focal_length)?;
+
+        let object = env.new_object(
+            CAPTURE_INFO_CLASS,
+            "(Ljava/lang/String;Ljava/lang/String;Ljava/lang/String;Ljava/lang/Double;Ljava/lang/Double;Ljava/lang/Double;Ljava/lang/String;Ljava/lang/Integer;Ljava/lang/Double;Ljava/lang/Double;Ljava/lang/Integer;Ljava/lang/Double;)V",
+            &[
+                JValue::Object(&make),
+                JValue::Object(&model),
+                JValue::Object(&lens_model),
+                JValue::Object(&latitude),
+                JValue::Object(&longitude),
+                JValue::Object(&altitude),
+                JValue::Object(&date_time),
+                JValue::Object(&offset_minutes),
+                JValue::Object(&exposure_time),
+                JValue::Object(&f_number),
+                JValue::Object(&iso),
+                JValue::Object(&focal_length)
+            ]
+        )?;
+        Ok(object)
+    })
+    .into_raw()
+}
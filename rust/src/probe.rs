@@ -0,0 +1,411 @@
+//! Header only image inspection
+//!
+//! Probing reads just enough of a file to parse its headers, letting the
+//! directory viewer list large images without decoding their pixels.
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+
+use jni::objects::{JClass, JString, JValue};
+use jni::sys::jobject;
+use jni::JNIEnv;
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_core::options::DecoderOptions;
+use zune_image::codecs::ImageFormat;
+use zune_image::errors::ImageErrors;
+
+use crate::codecs;
+use crate::errors::{jni_guard, JniErrors};
+use crate::format::Format;
+use crate::{colorspace_to_im_long, depth_to_im_long, format_to_im_long, get_string};
+
+/// How many bytes we read from a file before attempting to parse its headers.
+///
+/// Big enough to cover the EXIF and ICC segments most cameras place before
+/// a JPEG's frame header, headers lying further in are found by seeking
+const PROBE_PREFIX: u64 = 256 * 1024;
+
+/// TIFF tags pointing to further directories: SubIFDs, EXIF and GPS
+const TIFF_DIRECTORY_TAGS: [u16; 3] = [0x014A, 0x8769, 0x8825];
+const TIFF_NEW_SUBFILE_TYPE: u16 = 0x00FE;
+const TIFF_IMAGE_WIDTH: u16 = 0x0100;
+const TIFF_IMAGE_LENGTH: u16 = 0x0101;
+const TIFF_MAKE: u16 = 0x010F;
+const TIFF_DNG_VERSION: u16 = 0xC612;
+const TIFF_ACTIVE_AREA: u16 = 0xC68D;
+/// Camera makes `rawloader` picks a decoder by, files from scanners and
+/// editors also name a make but are plain TIFFs
+const RAW_MAKES: [&str; 23] = [
+    "SONY", "Mamiya-OP Co.,Ltd.", "OLYMPUS IMAGING CORP.", "OLYMPUS CORPORATION", "OLYMPUS OPTICAL CO.,LTD", "SAMSUNG",
+    "SEIKO EPSON CORP.", "EASTMAN KODAK COMPANY", "Eastman Kodak Company", "KODAK", "Kodak", "Panasonic", "LEICA",
+    "FUJIFILM", "PENTAX Corporation", "RICOH IMAGING COMPANY, LTD.", "PENTAX", "Leaf", "Hasselblad", "NIKON CORPORATION",
+    "NIKON", "Canon", "Phase One A/S"
+];
+/// Bound on the directories read, in case they form a loop or a tree too large to be real
+const MAX_TIFF_DIRECTORIES: usize = 256;
+/// Larger directory values are pixel data or previews rather than headers and are left unread
+const MAX_TIFF_VALUE: u64 = 16 * 1024 * 1024;
+
+const IMAGE_INFO_CLASS: &str = "ZilImageInfo";
+
+/// Image properties which can be determined without decoding
+#[derive(Debug, Copy, Clone)]
+pub struct ImageInfo {
+    pub width: usize,
+    pub height: usize,
+    pub format: Format,
+    pub depth: BitDepth,
+    pub colorspace: ColorSpace
+}
+
+/// Parse the headers of an encoded image held in memory
+///
+/// `data` may be truncated, as long as it contains the image headers
+pub fn probe_bytes(data: &[u8]) -> Result<ImageInfo, JniErrors> {
+    let format = match Format::guess(data) {
+        Format::Zune(ImageFormat::Unknown) => {
+            return Err(ImageErrors::ImageDecoderNotImplemented(ImageFormat::Unknown).into())
+        }
+        Format::Zune(format) => format,
+        Format::WebP => return codecs::probe_webp(data),
+        Format::Gif => return codecs::probe_gif(data),
+        Format::Tiff => return codecs::probe_tiff(Cursor::new(data)),
+        Format::Raw => return codecs::probe_raw(data),
+        Format::Exr => return codecs::probe_exr(data),
+        Format::Svg => return codecs::probe_svg(data),
+        Format::Ico => return Err(ImageErrors::ImageDecoderNotImplemented(ImageFormat::Unknown).into())
+    };
+
+    let mut decoder = format
+        .get_decoder_with_options(data, DecoderOptions::default())
+        .map_err(|e| JniErrors::from(e).with_format(format))?;
+
+    let metadata = decoder
+        .read_headers()
+        .map_err(|e| JniErrors::from(e).with_format(format))?;
+
+    let (width, height) = match &metadata {
+        Some(metadata) => metadata.get_dimensions(),
+        None => decoder
+            .dimensions()
+            .ok_or_else(|| JniErrors::Generic(format!("Could not read dimensions of {format:?} image")))?
+    };
+    let depth = metadata
+        .map(|m| m.get_depth())
+        .unwrap_or(BitDepth::Unknown);
+
+    Ok(ImageInfo {
+        width,
+        height,
+        format: format.into(),
+        depth,
+        // what the image will be in once decoded, e.g. RGB rather than YCbCr for JPEG
+        colorspace: decoder.out_colorspace()
+    })
+}
+
+/// Read `length` bytes at `offset` of `file`
+fn read_at(file: &mut File, offset: u64, length: usize) -> std::io::Result<Vec<u8>> {
+    let mut buffer = vec![0; length];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Bytes per value of a TIFF field type
+fn tiff_type_size(kind: u16) -> u64 {
+    match kind {
+        3 | 8 => 2,
+        4 | 9 | 11 | 13 => 4,
+        5 | 10 | 12 => 8,
+        _ => 1
+    }
+}
+
+/// A directory entry, with the value it points to if that was read
+struct TiffEntry {
+    tag: u16,
+    kind: u16,
+    value: Vec<u8>
+}
+
+/// The directories of a TIFF structured file, see [`read_tiff_headers`]
+struct TiffHeaders {
+    directories: Vec<Vec<TiffEntry>>,
+    /// Where each directory and value that was read lies in the file
+    ranges: Vec<(u64, Vec<u8>)>,
+    file_size: u64,
+    big_endian: bool
+}
+
+impl TiffHeaders {
+    fn u16_at(&self, bytes: &[u8], offset: usize) -> u16 {
+        let bytes = [bytes[offset], bytes[offset + 1]];
+        if self.big_endian { u16::from_be_bytes(bytes) } else { u16::from_le_bytes(bytes) }
+    }
+
+    fn u32_at(&self, bytes: &[u8], offset: usize) -> u32 {
+        let bytes = bytes[offset..offset + 4].try_into().unwrap();
+        if self.big_endian { u32::from_be_bytes(bytes) } else { u32::from_le_bytes(bytes) }
+    }
+
+    /// The `index`th value of a SHORT or LONG entry
+    fn value(&self, directory: &[TiffEntry], tag: u16, index: usize) -> Option<usize> {
+        let entry = directory.iter().find(|entry| entry.tag == tag)?;
+        match entry.kind {
+            3 => entry.value.get(index * 2..index * 2 + 2).map(|bytes| usize::from(self.u16_at(bytes, 0))),
+            4 => entry.value.get(index * 4..index * 4 + 4).map(|bytes| self.u32_at(bytes, 0) as usize),
+            _ => None
+        }
+    }
+
+    /// Whether a directory declares a DNG version or a camera make RAW decoders are chosen by
+    fn is_camera(&self) -> bool {
+        self.directories.iter().flatten().any(|entry| match entry.tag {
+            TIFF_DNG_VERSION => true,
+            TIFF_MAKE => {
+                let make = entry.value.split(|byte| *byte == 0).next().unwrap_or_default();
+                RAW_MAKES.iter().any(|raw_make| raw_make.as_bytes() == make)
+            }
+            _ => false
+        })
+    }
+
+    /// Dimensions of the sensor data, the largest directory not marked as
+    /// a reduced resolution preview, less the DNG active area's borders
+    ///
+    /// Other RAW formats are cropped by camera specific amounts the decoder
+    /// knows, they are reported at full sensor size
+    fn camera_dimensions(&self) -> Option<(usize, usize)> {
+        self.directories
+            .iter()
+            .filter(|directory| self.value(directory, TIFF_NEW_SUBFILE_TYPE, 0).unwrap_or(0) & 1 == 0)
+            .filter_map(|directory| {
+                let width = self.value(directory, TIFF_IMAGE_WIDTH, 0)?;
+                let height = self.value(directory, TIFF_IMAGE_LENGTH, 0)?;
+                // top, left, bottom, right
+                let area: Option<Vec<usize>> = (0..4).map(|index| self.value(directory, TIFF_ACTIVE_AREA, index)).collect();
+                match area.as_deref() {
+                    Some(&[top, left, bottom, right]) if bottom <= height && right <= width => {
+                        Some((right.saturating_sub(left), bottom.saturating_sub(top)))
+                    }
+                    _ => Some((width, height))
+                }
+            })
+            .max_by_key(|(width, height)| width * height)
+    }
+
+    /// The file as far as it was read, everything else reads as zeros
+    fn reader(&self) -> SparseFile<'_> {
+        SparseFile {
+            ranges: &self.ranges,
+            size: self.file_size,
+            position: 0
+        }
+    }
+}
+
+/// A file of which only some ranges were read, the rest reads as zeros
+struct SparseFile<'a> {
+    ranges: &'a [(u64, Vec<u8>)],
+    size: u64,
+    position: u64
+}
+
+impl Read for SparseFile<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let length = (self.size.saturating_sub(self.position)).min(buf.len() as u64);
+        let (start, end) = (self.position, self.position + length);
+        let buf = &mut buf[..length as usize];
+        buf.fill(0);
+
+        for (offset, data) in self.ranges {
+            let (overlap_start, overlap_end) = (start.max(*offset), end.min(offset + data.len() as u64));
+            if overlap_start < overlap_end {
+                buf[(overlap_start - start) as usize..(overlap_end - start) as usize]
+                    .copy_from_slice(&data[(overlap_start - offset) as usize..(overlap_end - offset) as usize]);
+            }
+        }
+        self.position = end;
+        Ok(length as usize)
+    }
+}
+
+impl Seek for SparseFile<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        let position = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset)
+        };
+        self.position = position.ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "Seek before the start of the file"))?;
+        Ok(self.position)
+    }
+}
+
+/// Read the directories of a TIFF structured file (TIFF and most RAW formats)
+/// and the values they point to by seeking through `file`
+///
+/// Directories may lie after the pixel data, only they and their values
+/// are read, so this costs about as much memory as the headers
+fn read_tiff_headers(file: &mut File, prefix: &[u8], file_size: u64) -> std::io::Result<TiffHeaders> {
+    let mut headers = TiffHeaders {
+        directories: Vec::new(),
+        ranges: vec![(0, prefix.to_vec())],
+        file_size,
+        big_endian: prefix.starts_with(b"MM")
+    };
+    let mut pending = vec![u64::from(headers.u32_at(prefix, 4))];
+    let mut visited = HashSet::new();
+
+    while let Some(offset) = pending.pop() {
+        // directories may point back at each other
+        if offset == 0 || offset + 2 > file_size || visited.len() >= MAX_TIFF_DIRECTORIES || !visited.insert(offset) {
+            continue;
+        }
+        let entries = usize::from(headers.u16_at(&read_at(file, offset, 2)?, 0));
+        // entry count, 12 byte entries and the offset of the next directory
+        let length = 2 + entries * 12 + 4;
+        if offset + length as u64 > file_size {
+            continue;
+        }
+        let directory = read_at(file, offset, length)?;
+        let mut parsed = Vec::with_capacity(entries);
+
+        for entry in directory[2..length - 4].chunks_exact(12) {
+            let (tag, kind) = (headers.u16_at(entry, 0), headers.u16_at(entry, 2));
+            let size = u64::from(headers.u32_at(entry, 4)) * tiff_type_size(kind);
+
+            let value = match size {
+                // values of up to four bytes are stored in the entry itself
+                0..=4 => entry[8..8 + size as usize].to_vec(),
+                // larger ones are pixel data or previews rather than headers
+                size if size > MAX_TIFF_VALUE => continue,
+                size => {
+                    let value_offset = u64::from(headers.u32_at(entry, 8));
+                    if value_offset + size > file_size {
+                        continue;
+                    }
+                    let value = read_at(file, value_offset, size as usize)?;
+                    headers.ranges.push((value_offset, value.clone()));
+                    value
+                }
+            };
+            if TIFF_DIRECTORY_TAGS.contains(&tag) {
+                pending.extend(value.chunks_exact(4).map(|offset| u64::from(headers.u32_at(offset, 0))));
+            }
+            parsed.push(TiffEntry { tag, kind, value });
+        }
+        pending.push(u64::from(headers.u32_at(&directory, length - 4)));
+        headers.ranges.push((offset, directory));
+        headers.directories.push(parsed);
+    }
+    Ok(headers)
+}
+
+/// The headers of a JPEG file up to its first scan, leaving out application segments and comments
+///
+/// EXIF, ICC and XMP segments can add up to megabytes before the frame
+/// header, they are skipped by seeking rather than read
+fn read_jpeg_headers(file: &mut File) -> std::io::Result<Vec<u8>> {
+    let mut headers = vec![0xFF, 0xD8];
+    let mut position = 2;
+
+    loop {
+        let header = read_at(file, position, 4)?;
+        match header[..2] {
+            // fill bytes
+            [0xFF, 0xFF] => position += 1,
+            [0xFF, marker] => {
+                let length = u64::from(u16::from_be_bytes([header[2], header[3]]));
+                // Adobe APP14 tells how to interpret the color components, keep it
+                let metadata = matches!(marker, 0xE0..=0xEF | 0xFE) && marker != 0xEE;
+                if !metadata {
+                    headers.extend(read_at(file, position, 2 + length as usize)?);
+                }
+                // start of scan, what follows is entropy coded
+                if marker == 0xDA {
+                    return Ok(headers);
+                }
+                position += 2 + length;
+            }
+            _ => return Ok(headers)
+        }
+    }
+}
+
+/// Probe the file at `path`, reading only its headers
+///
+/// Headers beyond the first few bytes, such as TIFF directories at the end
+/// of the file or a JPEG frame header after megabytes of metadata, are
+/// found by seeking rather than reading the whole file
+pub fn probe_file(path: &str) -> Result<ImageInfo, JniErrors> {
+    let io_err = |e: std::io::Error| JniErrors::from(e).with_path(path);
+
+    let mut file = File::open(path).map_err(io_err)?;
+    let file_size = file.metadata().map_err(io_err)?.len();
+
+    let mut prefix = Vec::with_capacity(PROBE_PREFIX.min(file_size) as usize);
+    file.by_ref()
+        .take(PROBE_PREFIX)
+        .read_to_end(&mut prefix)
+        .map_err(io_err)?;
+
+    let result = probe_bytes(&prefix);
+    if file_size <= PROBE_PREFIX {
+        return result.map_err(|e| e.with_path(path));
+    }
+    match (result, &prefix[..]) {
+        // TIFF directories may lie anywhere in the file, and without them
+        // we can't tell a RAW file from a plain TIFF
+        (Ok(info), _) if info.format != Format::Tiff => Ok(info),
+        // most RAW formats are TIFF structures with their own magic number
+        (_, [b'I', b'I', ..] | [b'M', b'M', ..]) => {
+            let headers = read_tiff_headers(&mut file, &prefix, file_size).map_err(io_err)?;
+            match headers.is_camera() {
+                true => {
+                    let (width, height) = headers
+                        .camera_dimensions()
+                        .ok_or_else(|| JniErrors::Generic("Could not read dimensions of RAW image".to_string()))?;
+                    Ok(ImageInfo {
+                        width,
+                        height,
+                        format: Format::Raw,
+                        depth: BitDepth::Sixteen,
+                        colorspace: ColorSpace::RGB
+                    })
+                }
+                false => codecs::probe_tiff(headers.reader())
+            }
+            .map_err(|e| e.with_path(path))
+        }
+        (Err(_), [0xFF, 0xD8, ..]) => {
+            let headers = read_jpeg_headers(&mut file).map_err(io_err)?;
+            probe_bytes(&headers).map_err(|e| e.with_path(path))
+        }
+        (result, _) => result.map_err(|e| e.with_path(path))
+    }
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_probeNative(mut env: JNIEnv, _class: JClass, filename: JString) -> jobject {
+    jni_guard(&mut env, "probeNative", |env| {
+        let path = get_string(env, &filename)?;
+        let info = probe_file(&path)?;
+
+        let object = env.new_object(
+            IMAGE_INFO_CLASS,
+            "(JJJJJ)V",
+            &[
+                JValue::Long(info.width as _),
+                JValue::Long(info.height as _),
+                JValue::Long(format_to_im_long(info.format)),
+                JValue::Long(depth_to_im_long(info.depth)),
+                JValue::Long(colorspace_to_im_long(info.colorspace))
+            ]
+        )?;
+        Ok(object)
+    })
+    .into_raw()
+}
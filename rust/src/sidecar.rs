@@ -0,0 +1,337 @@
+//! Ratings, color labels and keywords kept in `.xmp` sidecars
+//!
+//! These are the fields Lightroom, darktable and Bridge use to cull
+//! shots, `xmp:Rating`, `xmp:Label` and `dc:subject`. They are written to
+//! the image's sidecar without touching the image, other properties the
+//! sidecar holds are kept.
+use std::collections::HashMap;
+use std::path::Path;
+
+use jni::objects::{JClass, JObject, JObjectArray, JString, JValue};
+use jni::sys::{jint, jobject, jsize};
+use jni::JNIEnv;
+
+use crate::errors::{jni_guard, JniErrors};
+use crate::get_string;
+use crate::metadata::optional_string;
+use crate::xmp::{sidecar_path, string_array, Xmp, XmpKind};
+
+const SIDECAR_TAGS_CLASS: &str = "ZilSidecarTags";
+
+const RATING: &str = "xmp:Rating";
+const LABEL: &str = "xmp:Label";
+const KEYWORDS: &str = "dc:subject";
+
+/// Lowest and highest ratings, -1 marks a rejected shot
+const MIN_RATING: i32 = -1;
+const MAX_RATING: i32 = 5;
+
+/// The culling fields of a sidecar
+#[derive(Debug, Clone, Default)]
+pub struct SidecarTags {
+    /// 0 if the image is unrated
+    pub rating: i32,
+    pub label: Option<String>,
+    pub keywords: Vec<String>
+}
+
+impl SidecarTags {
+    pub fn from_xmp(xmp: &Xmp) -> SidecarTags {
+        let text = |key| xmp.property(key).filter(|property| property.kind == XmpKind::Text).and_then(|property| property.values.first());
+        SidecarTags {
+            // some tools write fractional ratings
+            rating: text(RATING)
+                .and_then(|rating| rating.trim().parse::<f64>().ok())
+                .map_or(0, |rating| (rating.round() as i32).clamp(MIN_RATING, MAX_RATING)),
+            label: text(LABEL).filter(|label| !label.is_empty()).cloned(),
+            keywords: match xmp.property(KEYWORDS) {
+                Some(property) if property.kind != XmpKind::Xml => property.values.clone(),
+                _ => vec![]
+            }
+        }
+    }
+}
+
+/// Run `edit` on the sidecar of the image at `path`, creating it if needed
+fn update<F>(path: &str, edit: F) -> Result<(), JniErrors>
+where
+    F: FnOnce(&mut Xmp) -> Result<(), JniErrors>
+{
+    let mut xmp = Xmp::load_sidecar(path)?;
+    edit(&mut xmp)?;
+    // removing a field from an image without a sidecar shouldn't create one
+    if xmp.properties.is_empty() && !sidecar_path(path).exists() {
+        return Ok(());
+    }
+    xmp.write_sidecar(path)
+}
+
+/// Set the rating of the image at `path`, 0 removes it
+pub fn set_rating(path: &str, rating: i32) -> Result<(), JniErrors> {
+    if !(MIN_RATING..=MAX_RATING).contains(&rating) {
+        return Err(JniErrors::InvalidArgument(format!(
+            "Ratings should be between {MIN_RATING} and {MAX_RATING}, found {rating}"
+        )));
+    }
+    update(path, |xmp| match rating {
+        0 => {
+            xmp.remove(RATING);
+            Ok(())
+        }
+        rating => xmp.set(RATING, XmpKind::Text, vec![rating.to_string()])
+    })
+}
+
+/// Set the color label of the image at `path`, `None` removes it
+pub fn set_label(path: &str, label: Option<&str>) -> Result<(), JniErrors> {
+    update(path, |xmp| match label {
+        Some(label) if !label.is_empty() => xmp.set(LABEL, XmpKind::Text, vec![label.to_string()]),
+        _ => {
+            xmp.remove(LABEL);
+            Ok(())
+        }
+    })
+}
+
+/// Replace the keywords of the image at `path`, an empty list removes them
+pub fn set_keywords(path: &str, keywords: Vec<String>) -> Result<(), JniErrors> {
+    update(path, |xmp| match keywords.is_empty() {
+        true => {
+            xmp.remove(KEYWORDS);
+            Ok(())
+        }
+        false => xmp.set(KEYWORDS, XmpKind::Bag, keywords)
+    })
+}
+
+/// Ratings of the rated images in `directory`, keyed by file name
+///
+/// Each sidecar is read once, even if several files share it, e.g. a raw file and its JPEG.
+/// Like [`sidecar_path`], a `<file>.<ext>.xmp` sidecar wins over `<file>.xmp`
+pub fn directory_ratings(directory: &str) -> Result<HashMap<String, i32>, JniErrors> {
+    let io_err = |e: std::io::Error| JniErrors::from(e).with_path(directory);
+
+    let mut files = Vec::new();
+    let mut sidecars = HashMap::new();
+    for entry in std::fs::read_dir(directory).map_err(io_err)? {
+        let path = entry.map_err(io_err)?.path();
+        let is_sidecar = path
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("xmp"));
+
+        match is_sidecar {
+            true => {
+                // unreadable sidecars are treated as unrated rather than failing the whole directory
+                let rating = std::fs::read(&path)
+                    .ok()
+                    .and_then(|packet| Xmp::parse(&packet))
+                    .map_or(0, |xmp| SidecarTags::from_xmp(&xmp).rating);
+                // `IMG_1.CR2` for darktable's `IMG_1.CR2.xmp`, `IMG_1` for `IMG_1.xmp`
+                sidecars.insert(path.with_extension(""), rating);
+            }
+            false if path.is_file() => files.push(path),
+            false => {}
+        }
+    }
+    let ratings = files
+        .iter()
+        .filter_map(|file| {
+            let rating = *sidecars.get(file).or_else(|| sidecars.get(&file.with_extension("")))?;
+            let name = file.file_name()?.to_string_lossy().into_owned();
+            (rating != 0).then_some((name, rating))
+        })
+        .collect();
+    Ok(ratings)
+}
+
+fn path_string(env: &mut JNIEnv, filename: &JString) -> Result<String, JniErrors> {
+    let path = get_string(env, filename)?;
+    if Path::new(&path).file_name().is_none() {
+        return Err(JniErrors::InvalidArgument(format!("{path:?} is not a file")));
+    }
+    Ok(path)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_sidecarTagsNative(mut env: JNIEnv, _class: JClass, filename: JString) -> jobject {
+    jni_guard(&mut env, "sidecarTagsNative", |env| {
+        let path = path_string(env, &filename)?;
+        let tags = SidecarTags::from_xmp(&Xmp::load_sidecar(&path)?);
+
+        let label = optional_string(env, tags.label.as_deref())?;
+        let keywords = env.new_object_array(tags.keywords.len() as jsize, "java/lang/String", JObject::null())?;
+        for (index, keyword) in tags.keywords.iter().enumerate() {
+            let keyword = env.new_string(keyword)?;
+            env.set_object_array_element(&keywords, index as jsize, keyword)?;
+        }
+        let object = env.new_object(
+            SIDECAR_TAGS_CLASS,
+            "(ILjava/lang/String;[Ljava/lang/String;)V",
+            &[JValue::Int(tags.rating), JValue::Object(&label), JValue::Object(&keywords)]
+        )?;
+        Ok(object)
+    })
+    .into_raw()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setSidecarRatingNative(mut env: JNIEnv, _class: JClass, filename: JString, rating: jint) {
+    jni_guard(&mut env, "setSidecarRatingNative", |env| {
+        let path = path_string(env, &filename)?;
+        set_rating(&path, rating)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setSidecarLabelNative(mut env: JNIEnv, _class: JClass, filename: JString, label: JString) {
+    jni_guard(&mut env, "setSidecarLabelNative", |env| {
+        let path = path_string(env, &filename)?;
+        let label = if label.is_null() { None } else { Some(get_string(env, &label)?) };
+        set_label(&path, label.as_deref())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setSidecarKeywordsNative(mut env: JNIEnv, _class: JClass, filename: JString, keywords: JObjectArray) {
+    jni_guard(&mut env, "setSidecarKeywordsNative", |env| {
+        let path = path_string(env, &filename)?;
+        let keywords = string_array(env, &keywords)?;
+        set_keywords(&path, keywords)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_directoryRatingsNative(mut env: JNIEnv, _class: JClass, directory: JString, ratings_map: JObject) {
+    jni_guard(&mut env, "directoryRatingsNative", |env| {
+        let directory = get_string(env, &directory)?;
+        let ratings = directory_ratings(&directory)?;
+
+        let map = env.get_map(&ratings_map)?;
+        for (name, rating) in ratings {
+            env.with_local_frame(4, |env| -> Result<(), JniErrors> {
+                let name = env.new_string(name)?;
+                let rating = env.new_object("java/lang/Integer", "(I)V", &[JValue::Int(rating)])?;
+                map.put(env, &name, &rating)?;
+                Ok(())
+            })?;
+        }
+        Ok(())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ELEMENT_PACKET: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:crs="http://ns.adobe.com/camera-raw-settings/1.0/">
+   <xmp:Rating>3</xmp:Rating>
+   <xmp:Label>Red</xmp:Label>
+   <dc:subject><rdf:Bag><rdf:li>beach</rdf:li><rdf:li>sunset</rdf:li></rdf:Bag></dc:subject>
+   <crs:Exposure2012>+0.50</crs:Exposure2012>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+    // as Lightroom writes them, simple properties are attributes of the description
+    const ATTRIBUTE_PACKET: &str = r#"<x:xmpmeta xmlns:x="adobe:ns:meta/">
+ <rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+  <rdf:Description rdf:about=""
+    xmlns:xmp="http://ns.adobe.com/xap/1.0/"
+    xmlns:dc="http://purl.org/dc/elements/1.1/"
+    xmlns:crs="http://ns.adobe.com/camera-raw-settings/1.0/"
+    xmp:Rating="4" xmp:Label="Green" crs:Exposure2012="+0.50">
+   <dc:subject><rdf:Bag><rdf:li>beach</rdf:li></rdf:Bag></dc:subject>
+  </rdf:Description>
+ </rdf:RDF>
+</x:xmpmeta>"#;
+
+    fn reparse(xmp: &Xmp) -> Xmp {
+        Xmp::parse(&xmp.to_packet()).unwrap()
+    }
+
+    fn exposure(xmp: &Xmp) -> Option<&str> {
+        xmp.property("crs:Exposure2012").map(|property| property.values[0].as_str())
+    }
+
+    #[test]
+    fn reads_both_forms() {
+        let tags = SidecarTags::from_xmp(&Xmp::parse(ELEMENT_PACKET.as_bytes()).unwrap());
+        assert_eq!(tags.rating, 3);
+        assert_eq!(tags.label.as_deref(), Some("Red"));
+        assert_eq!(tags.keywords, ["beach", "sunset"]);
+
+        let tags = SidecarTags::from_xmp(&Xmp::parse(ATTRIBUTE_PACKET.as_bytes()).unwrap());
+        assert_eq!(tags.rating, 4);
+        assert_eq!(tags.label.as_deref(), Some("Green"));
+        assert_eq!(tags.keywords, ["beach"]);
+    }
+
+    #[test]
+    fn set_fields_survive_a_round_trip() {
+        for packet in [ELEMENT_PACKET, ATTRIBUTE_PACKET] {
+            let mut xmp = Xmp::parse(packet.as_bytes()).unwrap();
+            xmp.set(RATING, XmpKind::Text, vec!["-1".to_string()]).unwrap();
+            xmp.set(LABEL, XmpKind::Text, vec!["Purple".to_string()]).unwrap();
+            xmp.set(KEYWORDS, XmpKind::Bag, vec!["a & b".to_string(), "<c>".to_string()]).unwrap();
+
+            let xmp = reparse(&xmp);
+            let tags = SidecarTags::from_xmp(&xmp);
+            assert_eq!(tags.rating, -1);
+            assert_eq!(tags.label.as_deref(), Some("Purple"));
+            assert_eq!(tags.keywords, ["a & b", "<c>"]);
+            assert_eq!(exposure(&xmp), Some("+0.50"));
+        }
+    }
+
+    #[test]
+    fn removed_fields_stay_removed() {
+        for packet in [ELEMENT_PACKET, ATTRIBUTE_PACKET] {
+            let mut xmp = Xmp::parse(packet.as_bytes()).unwrap();
+            for key in [RATING, LABEL, KEYWORDS] {
+                xmp.remove(key);
+            }
+            let xmp = reparse(&xmp);
+            for key in [RATING, LABEL, KEYWORDS] {
+                assert!(xmp.property(key).is_none(), "{key} was not removed");
+            }
+            let tags = SidecarTags::from_xmp(&xmp);
+            assert_eq!((tags.rating, tags.label, tags.keywords.len()), (0, None, 0));
+            assert_eq!(exposure(&xmp), Some("+0.50"));
+        }
+    }
+
+    #[test]
+    fn prefers_darktable_sidecars() {
+        let directory = std::env::temp_dir().join(format!("sidecar-tests-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let rated = |rating: &str| {
+            format!(
+                r#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF xmlns:rdf="http://www.w3.org/1999/02/22-rdf-syntax-ns#">
+<rdf:Description xmlns:xmp="http://ns.adobe.com/xap/1.0/" xmp:Rating="{rating}"/></rdf:RDF></x:xmpmeta>"#
+            )
+        };
+        for (name, contents) in [
+            ("IMG_1.CR2", String::new()),
+            ("IMG_1.jpg", String::new()),
+            ("IMG_1.xmp", rated("2")),
+            ("IMG_1.CR2.xmp", rated("5"))
+        ] {
+            std::fs::write(directory.join(name), contents).unwrap();
+        }
+        let ratings = directory_ratings(directory.to_str().unwrap());
+        let raw = directory.join("IMG_1.CR2");
+        let sidecar = sidecar_path(raw.to_str().unwrap());
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let ratings = ratings.unwrap();
+        assert_eq!(ratings.get("IMG_1.CR2"), Some(&5));
+        assert_eq!(ratings.get("IMG_1.jpg"), Some(&2));
+        assert_eq!(ratings.len(), 2);
+        assert_eq!(sidecar, directory.join("IMG_1.CR2.xmp"));
+    }
+}
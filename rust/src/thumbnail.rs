@@ -0,0 +1,282 @@
+//! Small previews for file browsers
+//!
+//! Decoding a 24MP photo to show it 200 pixels wide wastes most of the
+//! work, so cameras embed a small JPEG in the EXIF of their files. That is
+//! used when it's large enough, otherwise SVGs are rasterized at the
+//! thumbnail's size and JPEGs are decoded at an eighth of their size from
+//! their DC coefficients (see [`codecs::decode_jpeg_dc`]) if that needs
+//! enlarging by at most [`MAX_DC_ENLARGEMENT`]. Other formats, and JPEGs that can't be
+//! decoded that way, are still decoded in full, then shrunk by averaging
+//! blocks of pixels before depth and colorspace conversion and a final
+//! resize. Color management and orientation are applied to the thumbnail
+//! rather than the full image.
+use jni::objects::{JClass, JObject, JString, JValue};
+use jni::sys::{jint, jobject};
+use jni::JNIEnv;
+use zune_core::bit_depth::BitDepth;
+use zune_core::colorspace::ColorSpace;
+use zune_core::options::DecoderOptions;
+use zune_image::codecs::ImageFormat;
+use zune_image::core_filters::colorspace::ColorspaceConv;
+use zune_image::core_filters::depth::Depth;
+use zune_image::image::Image;
+use zune_image::traits::OperationsTrait;
+use zune_imageprocs::crop::Crop;
+use zune_imageprocs::resize::{Resize, ResizeMethod};
+
+use crate::animation::Animation;
+use crate::codecs;
+use crate::decoder::{self, DecodeLimits, DecodeOptions};
+use crate::errors::{jni_guard, JniErrors};
+use crate::format::Format;
+use crate::get_string;
+use crate::handles::Entry;
+use crate::icc::{self, WorkingSpace};
+use crate::metadata::{self, Exif};
+use crate::probe;
+
+const THUMBNAIL_CLASS: &str = "ZilThumbnail";
+
+/// Limits for decoding the files of a directory, which may come from anywhere
+///
+/// Generous enough for any camera, but refusing decompression bombs
+const THUMBNAIL_LIMITS: DecodeLimits = DecodeLimits {
+    max_width: 0,
+    max_height: 0,
+    max_pixels: 200_000_000,
+    memory_budget: 1 << 30,
+    strict: false
+};
+
+/// How much the eighth size image of a JPEG may be enlarged to fill a thumbnail
+///
+/// Enlarging blurs it slightly, which costs less than decoding the photo in
+/// full, e.g. a 6000 pixel wide JPEG is decoded at 750 pixels for thumbnails
+/// up to 1500 pixels wide
+const MAX_DC_ENLARGEMENT: usize = 2;
+
+/// A thumbnail, 8 bit BGRA pixels
+pub struct Thumbnail {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<u8>,
+    /// Whether it came from the file's EXIF rather than its pixels
+    pub embedded: bool
+}
+
+/// Size of an image of `width` x `height` shrunk to fit `max_edge`, never enlarged
+fn fit(width: usize, height: usize, max_edge: usize) -> (usize, usize) {
+    let longest = width.max(height);
+    if longest <= max_edge {
+        return (width, height);
+    }
+    let scale = |side: usize| ((side * max_edge + longest / 2) / longest).max(1);
+    (scale(width), scale(height))
+}
+
+/// Average `factor` x `factor` blocks of pixels with `channels` samples
+/// each, blocks at the right and bottom edges may be smaller
+fn average_blocks<T: Copy + Into<f64>>(
+    pixels: &[T], width: usize, height: usize, channels: usize, factor: usize, from: impl Fn(f64) -> T
+) -> (Vec<T>, usize, usize) {
+    let (out_width, out_height) = (width.div_ceil(factor), height.div_ceil(factor));
+    let mut sums = vec![0.0_f64; out_width * channels];
+    let mut output = Vec::with_capacity(out_width * out_height * channels);
+
+    for out_y in 0..out_height {
+        sums.fill(0.0);
+        let rows = (out_y * factor)..((out_y + 1) * factor).min(height);
+        let block_height = rows.len();
+
+        for row in pixels.chunks_exact(width * channels).skip(rows.start).take(block_height) {
+            for (x, pixel) in row.chunks_exact(channels).enumerate() {
+                let sum = &mut sums[(x / factor) * channels..][..channels];
+                for (sum, value) in sum.iter_mut().zip(pixel) {
+                    *sum += (*value).into();
+                }
+            }
+        }
+        for (out_x, sum) in sums.chunks_exact(channels).enumerate() {
+            let block_width = factor.min(width - out_x * factor);
+            let count = (block_width * block_height) as f64;
+            output.extend(sum.iter().map(|sum| from(sum / count)));
+        }
+    }
+    (output, out_width, out_height)
+}
+
+/// Shrink an image to fit `max_edge` and convert it to 8 bit RGBA, animations keep their first frame
+fn shrink(image: &Image, max_edge: usize) -> Result<Image, JniErrors> {
+    let (width, height) = image.dimensions();
+    let (target_width, target_height) = fit(width, height, max_edge);
+    resize_to(image, target_width, target_height)
+}
+
+/// Resize an image to `target_width` x `target_height` and convert it to 8 bit RGBA
+///
+/// Blocks are averaged in the image's own depth and colorspace, so only
+/// the averaged pixels are converted
+fn resize_to(image: &Image, target_width: usize, target_height: usize) -> Result<Image, JniErrors> {
+    let mut image = match image.frames_len() {
+        1 => image.clone(),
+        _ => crate::animation::frame_image(image, 0)?
+    };
+    let (width, height) = image.dimensions();
+
+    let factor = (width / target_width).min(height / target_height);
+    if factor >= 2 {
+        let colorspace = image.colorspace();
+        let channels = colorspace.num_components();
+        image = match image.depth() {
+            BitDepth::Sixteen => {
+                let pixels = &image.flatten_frames::<u16>()[0];
+                let (averaged, width, height) = average_blocks(pixels, width, height, channels, factor, |value| value.round() as u16);
+                Image::from_u16(&averaged, width, height, colorspace)
+            }
+            BitDepth::Float32 => {
+                let pixels = &image.flatten_frames::<f32>()[0];
+                let (averaged, width, height) = average_blocks(pixels, width, height, channels, factor, |value| value as f32);
+                Image::from_f32(&averaged, width, height, colorspace)
+            }
+            _ => {
+                let pixels = &image.flatten_frames::<u8>()[0];
+                let (averaged, width, height) = average_blocks(pixels, width, height, channels, factor, |value| value.round() as u8);
+                Image::from_u8(&averaged, width, height, colorspace)
+            }
+        };
+    }
+    Depth::new(BitDepth::Eight).execute(&mut image)?;
+    ColorspaceConv::new(ColorSpace::RGBA).execute(&mut image)?;
+
+    if image.dimensions() != (target_width, target_height) {
+        Resize::new(target_width, target_height, ResizeMethod::Bilinear).execute(&mut image)?;
+    }
+    Ok(image)
+}
+
+/// Crop the bars cameras add when the thumbnail's aspect ratio differs from the image's
+fn crop_to_aspect(thumbnail: &mut Image, width: usize, height: usize) -> Result<(), JniErrors> {
+    let (thumb_width, thumb_height) = thumbnail.dimensions();
+    // compare width / height ratios without dividing
+    let (image_ratio, thumb_ratio) = (width * thumb_height, thumb_width * height);
+    if image_ratio.abs_diff(thumb_ratio) * 100 <= thumb_ratio {
+        return Ok(());
+    }
+    let (crop_width, crop_height) = match image_ratio > thumb_ratio {
+        // the image is wider, bars are above and below
+        true => (thumb_width, ((thumb_width * height + width / 2) / width).clamp(1, thumb_height)),
+        false => (((thumb_height * width + height / 2) / height).clamp(1, thumb_width), thumb_height)
+    };
+    let (x, y) = ((thumb_width - crop_width) / 2, (thumb_height - crop_height) / 2);
+    Crop::new(crop_width, crop_height, x, y).execute(thumbnail)?;
+    Ok(())
+}
+
+/// The thumbnail embedded in the EXIF of a file, if it's at least half of `max_edge`
+///
+/// EXIF thumbnails are rarely larger than 160 pixels, enlarging one that's
+/// smaller than that would be too blurry
+fn embedded(data: &[u8], format: Format, max_edge: usize) -> Option<Image> {
+    let exif = Exif::extract(data, format)?;
+    let mut thumbnail = Image::read(exif.thumbnail.as_deref()?, DecoderOptions::default()).ok()?;
+
+    let (width, height) = thumbnail.dimensions();
+    if width.max(height) * 2 < max_edge {
+        return None;
+    }
+    if let Ok(info) = probe::probe_bytes(data) {
+        crop_to_aspect(&mut thumbnail, info.width, info.height).ok()?;
+    }
+    metadata::orient(&mut thumbnail, exif.orientation()).ok()?;
+    Some(thumbnail)
+}
+
+/// Decode `data` and shrink it to fit `max_edge`, in sRGB and upright
+fn decode_shrunk(data: &[u8], format: Format, max_edge: usize) -> Result<Image, JniErrors> {
+    let info = probe::probe_bytes(data)?;
+    THUMBNAIL_LIMITS.check_info(&info)?;
+
+    let reduced = match format {
+        Format::Zune(ImageFormat::JPEG) if info.width.max(info.height).div_ceil(8) * MAX_DC_ENLARGEMENT >= max_edge => {
+            codecs::decode_jpeg_dc(data, &THUMBNAIL_LIMITS).ok()
+        }
+        _ => None
+    };
+    let (image, exif, (target_width, target_height)) = match reduced {
+        // fitted by the file's size, the eighth size image may have to be enlarged
+        Some(image) => (image, Exif::extract(data, format), fit(info.width, info.height, max_edge)),
+        None => {
+            let options = DecodeOptions {
+                limits: THUMBNAIL_LIMITS,
+                working_space: WorkingSpace::Original,
+                ..DecodeOptions::default()
+            };
+            let entry = decoder::decode_bytes(data, None, &options)?;
+            let (width, height) = entry.image.dimensions();
+            (entry.image, entry.metadata.exif, fit(width, height, max_edge))
+        }
+    };
+    // converting and turning the thumbnail is cheaper than doing it to the full image
+    let mut entry = Entry::new(resize_to(&image, target_width, target_height)?, Animation::default());
+    icc::color_manage(&mut entry, data, format, WorkingSpace::Srgb, false)?;
+    if let Some(exif) = &exif {
+        metadata::orient(&mut entry.image, exif.orientation())?;
+    }
+    Ok(entry.image)
+}
+
+/// Make a thumbnail of an encoded image fitting `max_edge`, turned upright by its EXIF orientation
+pub fn thumbnail(data: &[u8], max_edge: usize) -> Result<Thumbnail, JniErrors> {
+    let format = Format::guess(data);
+
+    let (mut image, embedded) = match embedded(data, format, max_edge) {
+        Some(image) => (shrink(&image, max_edge)?, true),
+        None if format == Format::Svg => {
+            // vectors are rasterized at the thumbnail's size
+            let image = codecs::decode_svg_to_fit(data, max_edge, &THUMBNAIL_LIMITS)?;
+            (shrink(&image, max_edge)?, false)
+        }
+        None => (decode_shrunk(data, format, max_edge)?, false)
+    };
+    ColorspaceConv::new(ColorSpace::BGRA).execute(&mut image)?;
+
+    let (width, height) = image.dimensions();
+    let pixels = image.flatten_frames::<u8>().swap_remove(0);
+    Ok(Thumbnail {
+        width,
+        height,
+        pixels,
+        embedded
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_thumbnailNative(mut env: JNIEnv, _class: JClass, filename: JString, max_edge: jint) -> jobject {
+    jni_guard(&mut env, "thumbnailNative", |env| {
+        let path = get_string(env, &filename)?;
+        let max_edge = usize::try_from(max_edge)
+            .ok()
+            .filter(|max_edge| *max_edge > 0)
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("maxEdge should be positive, found {max_edge}")))?;
+
+        let size = std::fs::metadata(&path).map_err(|e| JniErrors::from(e).with_path(&path))?.len();
+        THUMBNAIL_LIMITS.check_file_size(size).map_err(|e| e.with_path(&path))?;
+
+        let data = std::fs::read(&path).map_err(|e| JniErrors::from(e).with_path(&path))?;
+        let thumbnail = thumbnail(&data, max_edge).map_err(|e| e.with_path(&path))?;
+
+        let pixels = JObject::from(env.byte_array_from_slice(&thumbnail.pixels)?);
+        let object = env.new_object(
+            THUMBNAIL_CLASS,
+            "(II[BZ)V",
+            &[
+                JValue::Int(thumbnail.width as jint),
+                JValue::Int(thumbnail.height as jint),
+                JValue::Object(&pixels),
+                JValue::Bool(thumbnail.embedded.into())
+            ]
+        )?;
+        Ok(object)
+    })
+    .into_raw()
+}
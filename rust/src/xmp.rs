@@ -0,0 +1,584 @@
+//! XMP packets and their `.xmp` sidecars
+//!
+//! A packet is read into one property per element (or attribute) of its
+//! `rdf:Description`s. Text and arrays are exposed to Kotlin as values,
+//! anything else, such as structures, is kept as XML and written back as is.
+//!
+//! JPEG, PNG, WebP and JPEG-XL files embed the packet, other formats write
+//! it to a sidecar named after the image, as Adobe's tools do, unless the
+//! image already has a darktable style `<file>.<ext>.xmp` sidecar. Sidecars are
+//! written to a temporary file first and renamed into place.
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use jni::objects::{JClass, JObject, JObjectArray, JString, JValue};
+use jni::sys::{jlong, jobjectArray, jsize};
+use jni::JNIEnv;
+use roxmltree::{Document, Node};
+use zune_image::codecs::ImageFormat;
+
+use crate::containers;
+use crate::errors::{jni_guard, JniErrors};
+use crate::format::Format;
+use crate::get_string;
+use crate::handles;
+use crate::metadata;
+
+/// Identifier JPEG APP1 segments holding XMP start with
+pub const JPEG_XMP_MARKER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+/// Keyword of the PNG `iTXt` chunk holding XMP
+pub const PNG_XMP_KEYWORD: &str = "XML:com.adobe.xmp";
+
+const XMP_PROPERTY_CLASS: &str = "ZilXmpProperty";
+const XMP_KIND_CLASS: &str = "ZilXmpKind";
+
+const RDF: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const XML: &str = "http://www.w3.org/XML/1998/namespace";
+
+/// Prefixes of common namespaces, keys use these whatever prefix a file
+/// picked so Kotlin can look properties up by name
+const PREFIXES: &[(&str, &str)] = &[
+    ("dc", "http://purl.org/dc/elements/1.1/"),
+    ("xmp", "http://ns.adobe.com/xap/1.0/"),
+    ("xmpRights", "http://ns.adobe.com/xap/1.0/rights/"),
+    ("xmpMM", "http://ns.adobe.com/xap/1.0/mm/"),
+    ("photoshop", "http://ns.adobe.com/photoshop/1.0/"),
+    ("Iptc4xmpCore", "http://iptc.org/std/Iptc4xmpCore/1.0/xmlns/"),
+    ("Iptc4xmpExt", "http://iptc.org/std/Iptc4xmpExt/2008-02-29/"),
+    ("lr", "http://ns.adobe.com/lightroom/1.0/"),
+    ("exif", "http://ns.adobe.com/exif/1.0/"),
+    ("tiff", "http://ns.adobe.com/tiff/1.0/"),
+    ("aux", "http://ns.adobe.com/exif/1.0/aux/"),
+    ("crs", "http://ns.adobe.com/camera-raw-settings/1.0/")
+];
+
+/// How a property's values are stored, mirrors `ZilXmpKind` in Kotlin
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum XmpKind {
+    Text,
+    /// Unordered array, e.g. keywords
+    Bag,
+    /// Ordered array, e.g. authors
+    Seq,
+    /// Alternatives, e.g. a title in several languages
+    Alt,
+    /// A structure or other value kept as XML
+    Xml
+}
+
+impl XmpKind {
+    pub fn from_ordinal(ordinal: i32) -> Option<XmpKind> {
+        match ordinal {
+            0 => Some(XmpKind::Text),
+            1 => Some(XmpKind::Bag),
+            2 => Some(XmpKind::Seq),
+            3 => Some(XmpKind::Alt),
+            4 => Some(XmpKind::Xml),
+            _ => None
+        }
+    }
+
+    fn ordinal(self) -> i32 {
+        self as i32
+    }
+
+    fn array_name(self) -> Option<&'static str> {
+        match self {
+            XmpKind::Bag => Some("Bag"),
+            XmpKind::Seq => Some("Seq"),
+            XmpKind::Alt => Some("Alt"),
+            XmpKind::Text | XmpKind::Xml => None
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct XmpProperty {
+    pub namespace: String,
+    pub prefix: String,
+    pub name: String,
+    pub kind: XmpKind,
+    /// The text, the items of an array or the XML of the element
+    pub values: Vec<String>,
+    /// `xml:lang` of each item of an `Alt` array
+    pub languages: Vec<String>
+}
+
+impl XmpProperty {
+    /// `prefix:name`, e.g. `dc:subject`
+    pub fn key(&self) -> String {
+        format!("{}:{}", self.prefix, self.name)
+    }
+}
+
+/// A parsed XMP packet
+#[derive(Debug, Clone, Default)]
+pub struct Xmp {
+    pub properties: Vec<XmpProperty>
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c)
+        }
+    }
+    escaped
+}
+
+/// The prefix keys use for `namespace`, `fallback` for namespaces we don't know
+fn prefix_for<'a>(namespace: &str, fallback: &'a str) -> &'a str {
+    PREFIXES
+        .iter()
+        .find(|(_, uri)| *uri == namespace)
+        .map_or(fallback, |(prefix, _)| prefix)
+}
+
+/// The XML of `element`, declaring the namespaces it inherits so it stands on its own
+fn element_xml(text: &str, element: Node) -> String {
+    let xml = &text[element.range()];
+    let name_end = xml.find(|c: char| c.is_whitespace() || c == '>' || c == '/').unwrap_or(xml.len());
+    let start_tag = &xml[..xml.find('>').unwrap_or(xml.len())];
+
+    let mut declarations = String::new();
+    for namespace in element.namespaces() {
+        if namespace.uri() == XML {
+            continue;
+        }
+        let attribute = match namespace.name() {
+            Some(prefix) => format!("xmlns:{prefix}"),
+            None => "xmlns".to_string()
+        };
+        // declared by the element itself
+        if start_tag.contains(&format!("{attribute}=")) {
+            continue;
+        }
+        declarations.push_str(&format!(" {attribute}=\"{}\"", escape(namespace.uri())));
+    }
+    format!("{}{declarations}{}", &xml[..name_end], &xml[name_end..])
+}
+
+/// Read a property element of an `rdf:Description`
+fn property(text: &str, element: Node) -> Option<XmpProperty> {
+    let namespace = element.tag_name().namespace()?;
+    let prefix = prefix_for(namespace, element.lookup_prefix(namespace).unwrap_or("ns"));
+
+    let mut property = XmpProperty {
+        namespace: namespace.to_string(),
+        prefix: prefix.to_string(),
+        name: element.tag_name().name().to_string(),
+        kind: XmpKind::Text,
+        values: vec![],
+        languages: vec![]
+    };
+    let children: Vec<Node> = element.children().filter(Node::is_element).collect();
+    let array = match children.as_slice() {
+        [child] if child.tag_name().namespace() == Some(RDF) => match child.tag_name().name() {
+            "Bag" => Some((XmpKind::Bag, child)),
+            "Seq" => Some((XmpKind::Seq, child)),
+            "Alt" => Some((XmpKind::Alt, child)),
+            _ => None
+        },
+        _ => None
+    };
+    match (children.is_empty(), array) {
+        // a URI or plain text
+        (true, _) => {
+            let value = element.attribute((RDF, "resource")).or(element.text()).unwrap_or_default();
+            property.values.push(value.to_string());
+        }
+        (false, Some((kind, array))) => {
+            let items: Vec<Node> = array.children().filter(Node::is_element).collect();
+            // arrays of structures are kept as XML
+            if items.iter().any(|item| item.children().any(|child| child.is_element())) {
+                property.kind = XmpKind::Xml;
+                property.values.push(element_xml(text, element));
+                return Some(property);
+            }
+            property.kind = kind;
+            for item in items {
+                property.values.push(item.text().unwrap_or_default().to_string());
+                if kind == XmpKind::Alt {
+                    property.languages.push(item.attribute((XML, "lang")).unwrap_or("x-default").to_string());
+                }
+            }
+        }
+        (false, None) => {
+            property.kind = XmpKind::Xml;
+            property.values.push(element_xml(text, element));
+        }
+    }
+    Some(property)
+}
+
+impl Xmp {
+    /// Parse an XMP packet, `None` if it isn't valid XML
+    pub fn parse(packet: &[u8]) -> Option<Xmp> {
+        let text = std::str::from_utf8(packet).ok()?;
+        // packets are often padded with NULs or whitespace to allow in place edits
+        let text = text.trim_start_matches('\u{feff}').trim_end_matches(['\0', ' ', '\n', '\r', '\t']);
+        let document = Document::parse(text).ok()?;
+
+        let mut properties = Vec::new();
+        for description in document.descendants().filter(|node| node.has_tag_name((RDF, "Description"))) {
+            // simple properties may be written as attributes
+            for attribute in description.attributes() {
+                let Some(namespace) = attribute.namespace() else {
+                    continue;
+                };
+                if namespace == RDF || namespace == XML {
+                    continue;
+                }
+                let prefix = prefix_for(namespace, description.lookup_prefix(namespace).unwrap_or("ns"));
+                properties.push(XmpProperty {
+                    namespace: namespace.to_string(),
+                    prefix: prefix.to_string(),
+                    name: attribute.name().to_string(),
+                    kind: XmpKind::Text,
+                    values: vec![attribute.value().to_string()],
+                    languages: vec![]
+                });
+            }
+            properties.extend(
+                description
+                    .children()
+                    .filter(Node::is_element)
+                    .filter_map(|element| property(document.input_text(), element))
+            );
+        }
+        Some(Xmp { properties })
+    }
+
+    /// Read the XMP packet embedded in an encoded image, if it has any
+    pub fn extract(data: &[u8], format: Format) -> Option<Xmp> {
+        let packet = match format {
+            Format::Zune(ImageFormat::JPEG) => containers::jpeg_segments(data)
+                .filter(|(marker, _)| *marker == 0xE1)
+                .find_map(|(_, segment)| segment.strip_prefix(JPEG_XMP_MARKER))?
+                .to_vec(),
+            Format::Zune(ImageFormat::PNG) => {
+                let (_, chunk) = containers::png_chunks(data)
+                    .find(|(kind, chunk)| *kind == b"iTXt" && chunk.starts_with(PNG_XMP_KEYWORD.as_bytes()))?;
+                itxt_text(chunk)?
+            }
+            Format::WebP => containers::riff_chunks(data).find(|(kind, _)| *kind == b"XMP ")?.1.to_vec(),
+            Format::Zune(ImageFormat::JPEG_XL) => containers::bmff_boxes(data).find(|(kind, _)| *kind == b"xml ")?.1.to_vec(),
+            _ => return None
+        };
+        Xmp::parse(&packet)
+    }
+
+    /// Read the sidecar of the image at `path`, if there is one
+    pub fn read_sidecar(path: &str) -> Option<Xmp> {
+        Xmp::parse(&std::fs::read(sidecar_path(path)).ok()?)
+    }
+
+    /// Read the sidecar of the image at `path`, an empty packet if it has none
+    ///
+    /// Unlike [`Xmp::read_sidecar`], a sidecar that can't be parsed is an error,
+    /// writing to it would lose whatever it holds
+    pub fn load_sidecar(path: &str) -> Result<Xmp, JniErrors> {
+        let sidecar = sidecar_path(path);
+        match std::fs::read(&sidecar) {
+            Ok(packet) => Xmp::parse(&packet)
+                .ok_or_else(|| JniErrors::Generic(format!("{} is not a valid XMP sidecar", sidecar.display()))),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Xmp::default()),
+            Err(e) => Err(JniErrors::from(e).with_path(sidecar.to_string_lossy()))
+        }
+    }
+
+    /// Write the packet to the sidecar of the image at `path`, replacing what it held
+    pub fn write_sidecar(&self, path: &str) -> Result<(), JniErrors> {
+        write_atomically(&sidecar_path(path), &self.to_packet())
+    }
+
+    /// Merge the packet into the sidecar of the image at `path`, creating it if needed
+    ///
+    /// The sidecar may describe another file with the same name, e.g. `IMG_1.CR2`
+    /// next to a saved `IMG_1.bmp`, so the properties it holds are kept and only
+    /// those the packet also has are replaced
+    pub fn merge_into_sidecar(&self, path: &str) -> Result<(), JniErrors> {
+        let mut merged = Xmp::load_sidecar(path)?;
+        for property in &self.properties {
+            let existing = merged
+                .properties
+                .iter_mut()
+                .find(|existing| existing.namespace == property.namespace && existing.name == property.name);
+            match existing {
+                Some(existing) => *existing = property.clone(),
+                None => merged.properties.push(property.clone())
+            }
+        }
+        write_atomically(&sidecar_path(path), &merged.to_packet())
+    }
+
+    /// The property whose key is `key`, e.g. `dc:subject`
+    pub fn property(&self, key: &str) -> Option<&XmpProperty> {
+        self.properties.iter().find(|property| property.key() == key)
+    }
+
+    /// Replace or add a property, the prefix of `key` must be one we know or one the packet uses
+    pub fn set(&mut self, key: &str, kind: XmpKind, values: Vec<String>) -> Result<(), JniErrors> {
+        let (prefix, name) = key
+            .split_once(':')
+            .filter(|(prefix, name)| !prefix.is_empty() && !name.is_empty() && !name.contains(':'))
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("XMP keys should look like prefix:name, found {key:?}")))?;
+
+        let namespace = PREFIXES
+            .iter()
+            .find(|(known, _)| *known == prefix)
+            .map(|(_, uri)| uri.to_string())
+            .or_else(|| {
+                self.properties
+                    .iter()
+                    .find(|property| property.prefix == prefix)
+                    .map(|property| property.namespace.clone())
+            })
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown XMP namespace prefix {prefix:?}")))?;
+
+        if kind == XmpKind::Xml {
+            return Err(JniErrors::InvalidArgument("XMP properties can only be set to text or arrays".to_string()));
+        }
+        if kind == XmpKind::Text && values.len() != 1 {
+            return Err(JniErrors::InvalidArgument(format!("{key} is text and needs one value, found {}", values.len())));
+        }
+        let property = XmpProperty {
+            namespace,
+            prefix: prefix.to_string(),
+            name: name.to_string(),
+            kind,
+            languages: match kind {
+                XmpKind::Alt => values.iter().map(|_| "x-default".to_string()).collect(),
+                _ => vec![]
+            },
+            values
+        };
+        match self.properties.iter_mut().find(|existing| existing.key() == key) {
+            Some(existing) => *existing = property,
+            None => self.properties.push(property)
+        }
+        Ok(())
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.properties.retain(|property| property.key() != key);
+    }
+
+    /// Serialize as an XMP packet
+    pub fn to_packet(&self) -> Vec<u8> {
+        let mut namespaces: Vec<(&str, &str)> = vec![];
+        for property in &self.properties {
+            if !namespaces.iter().any(|(prefix, _)| *prefix == property.prefix) {
+                namespaces.push((&property.prefix, &property.namespace));
+            }
+        }
+        let mut packet = String::from("<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n");
+        packet.push_str("<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n");
+        packet.push_str(&format!(" <rdf:RDF xmlns:rdf=\"{RDF}\">\n"));
+        packet.push_str("  <rdf:Description rdf:about=\"\"");
+        for (prefix, namespace) in namespaces {
+            packet.push_str(&format!("\n    xmlns:{prefix}=\"{}\"", escape(namespace)));
+        }
+        packet.push_str(">\n");
+
+        for property in &self.properties {
+            let key = property.key();
+            match (property.kind, property.kind.array_name()) {
+                (XmpKind::Xml, _) => {
+                    for xml in &property.values {
+                        packet.push_str(&format!("   {xml}\n"));
+                    }
+                }
+                (_, Some(array)) => {
+                    packet.push_str(&format!("   <{key}>\n    <rdf:{array}>\n"));
+                    for (index, value) in property.values.iter().enumerate() {
+                        match property.languages.get(index) {
+                            Some(language) => packet.push_str(&format!(
+                                "     <rdf:li xml:lang=\"{}\">{}</rdf:li>\n",
+                                escape(language),
+                                escape(value)
+                            )),
+                            None => packet.push_str(&format!("     <rdf:li>{}</rdf:li>\n", escape(value)))
+                        }
+                    }
+                    packet.push_str(&format!("    </rdf:{array}>\n   </{key}>\n"));
+                }
+                _ => {
+                    let value = property.values.first().map(String::as_str).unwrap_or_default();
+                    packet.push_str(&format!("   <{key}>{}</{key}>\n", escape(value)));
+                }
+            }
+        }
+        packet.push_str("  </rdf:Description>\n </rdf:RDF>\n</x:xmpmeta>\n<?xpacket end=\"w\"?>");
+        packet.into_bytes()
+    }
+
+    /// Serialize as an XMP packet of at most `max_length` bytes, leaving out
+    /// the largest properties (usually an editor's history) until it fits
+    pub fn to_packet_within(&self, max_length: usize) -> Option<Vec<u8>> {
+        let mut xmp = self.clone();
+        loop {
+            let packet = xmp.to_packet();
+            if packet.len() <= max_length {
+                return Some(packet);
+            }
+            let largest = (0..xmp.properties.len())
+                .max_by_key(|index| xmp.properties[*index].values.iter().map(String::len).sum::<usize>())?;
+            xmp.properties.remove(largest);
+        }
+    }
+}
+
+/// Text of an uncompressed `iTXt` chunk
+///
+/// Writers don't compress XMP, so that it can be found by scanning the file
+fn itxt_text(chunk: &[u8]) -> Option<Vec<u8>> {
+    // keyword, then the compression flag and method
+    let keyword_end = chunk.iter().position(|byte| *byte == 0)?;
+    if chunk.get(keyword_end + 1) != Some(&0) {
+        return None;
+    }
+    let rest = chunk.get(keyword_end + 3..)?;
+    // language tag and translated keyword
+    let language_end = rest.iter().position(|byte| *byte == 0)?;
+    let rest = &rest[language_end + 1..];
+    let translated_end = rest.iter().position(|byte| *byte == 0)?;
+    Some(rest[translated_end + 1..].to_vec())
+}
+
+/// Write `contents` to a temporary file next to `path` and rename it over `path`,
+/// so a failed write leaves the previous sidecar intact
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<(), JniErrors> {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(format!(".{}-{}.tmp", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed)));
+    let temporary = PathBuf::from(temporary);
+
+    let result = File::create(&temporary)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            file.sync_all()
+        })
+        .and_then(|_| std::fs::rename(&temporary, path));
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result.map_err(|e| JniErrors::from(e).with_path(path.to_string_lossy()))
+}
+
+/// The sidecar of an image, `<file>.<ext>.xmp` as darktable names them if
+/// there is one, otherwise `<file>.xmp` as Adobe's tools do
+pub fn sidecar_path(path: &str) -> PathBuf {
+    let mut darktable = Path::new(path).as_os_str().to_owned();
+    darktable.push(".xmp");
+    let darktable = PathBuf::from(darktable);
+    match darktable.is_file() {
+        true => darktable,
+        false => Path::new(path).with_extension("xmp")
+    }
+}
+
+/// Whether `format` can embed an XMP packet
+pub fn embeds_xmp(format: Format) -> bool {
+    matches!(
+        format,
+        Format::WebP | Format::Zune(ImageFormat::JPEG | ImageFormat::PNG | ImageFormat::JPEG_XL)
+    )
+}
+
+/// Convert the properties of a packet to Kotlin `ZilXmpProperty`s
+fn xmp_properties<'local>(env: &mut JNIEnv<'local>, xmp: &Xmp) -> Result<JObjectArray<'local>, JniErrors> {
+    let kinds = JObjectArray::from(
+        env.call_static_method(XMP_KIND_CLASS, "values", format!("()[L{XMP_KIND_CLASS};"), &[])?.l()?
+    );
+    let array = env.new_object_array(xmp.properties.len() as jsize, XMP_PROPERTY_CLASS, JObject::null())?;
+
+    for (index, property) in xmp.properties.iter().enumerate() {
+        env.with_local_frame(8 + property.values.len() as i32, |env| -> Result<(), JniErrors> {
+            let key = JObject::from(env.new_string(property.key())?);
+            let namespace = JObject::from(env.new_string(&property.namespace)?);
+            let kind = env.get_object_array_element(&kinds, property.kind.ordinal())?;
+
+            let values = env.new_object_array(property.values.len() as jsize, "java/lang/String", JObject::null())?;
+            for (index, value) in property.values.iter().enumerate() {
+                let value = env.new_string(value)?;
+                env.set_object_array_element(&values, index as jsize, value)?;
+            }
+            let object = env.new_object(
+                XMP_PROPERTY_CLASS,
+                format!("(Ljava/lang/String;Ljava/lang/String;L{XMP_KIND_CLASS};[Ljava/lang/String;)V"),
+                &[
+                    JValue::Object(&key),
+                    JValue::Object(&namespace),
+                    JValue::Object(&kind),
+                    JValue::Object(&values)
+                ]
+            )?;
+            env.set_object_array_element(&array, index as jsize, object)?;
+            Ok(())
+        })?;
+    }
+    Ok(array)
+}
+
+/// Read a Kotlin `Array<String>`
+pub fn string_array(env: &mut JNIEnv, array: &JObjectArray) -> Result<Vec<String>, JniErrors> {
+    let mut strings = Vec::new();
+    for index in 0..env.get_array_length(array)? {
+        let string = JString::from(env.get_object_array_element(array, index)?);
+        strings.push(get_string(env, &string)?);
+    }
+    Ok(strings)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_xmpPropertiesNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong) -> jobjectArray {
+    jni_guard(&mut env, "xmpPropertiesNative", |env| {
+        let xmp = handles::with_entry(image_ptr, |entry| entry.metadata.xmp.clone())?.unwrap_or_default();
+        xmp_properties(env, &xmp)
+    })
+    .into_raw()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_setXmpPropertyNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, key: JString, kind: JObject, values: JObjectArray) {
+    jni_guard(&mut env, "setXmpPropertyNative", |env| {
+        let key = get_string(env, &key)?;
+        let ordinal = metadata::ordinal(env, &kind, "kind")?;
+        let kind = XmpKind::from_ordinal(ordinal)
+            .ok_or_else(|| JniErrors::InvalidArgument(format!("Unknown XMP kind {ordinal}")))?;
+        let values = string_array(env, &values)?;
+
+        handles::with_entry(image_ptr, |entry| entry.metadata.xmp.get_or_insert_with(Xmp::default).set(&key, kind, values))?
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_removeXmpPropertyNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, key: JString) {
+    jni_guard(&mut env, "removeXmpPropertyNative", |env| {
+        let key = get_string(env, &key)?;
+        handles::with_entry(image_ptr, |entry| {
+            if let Some(xmp) = &mut entry.metadata.xmp {
+                xmp.remove(&key);
+            }
+        })?;
+        Ok(())
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_ZilImageJni_writeXmpSidecarNative(mut env: JNIEnv, _class: JClass, image_ptr: jlong, filename: JString) {
+    jni_guard(&mut env, "writeXmpSidecarNative", |env| {
+        let path = get_string(env, &filename)?;
+        let xmp = handles::with_entry(image_ptr, |entry| entry.metadata.xmp.clone())?.unwrap_or_default();
+        xmp.write_sidecar(&path)
+    })
+}